@@ -78,6 +78,30 @@ Convert structured mediaSourcePaths to string format
   {{- if .defaultShoebox -}}
     {{- $path = printf "%s;%s" $path .defaultShoebox -}}
   {{- end -}}
+  {{- if .watch -}}
+    {{- $path = printf "%s;watch=true" $path -}}
+  {{- end -}}
+  {{- if .schedule -}}
+    {{- $path = printf "%s;schedule=%s" $path .schedule -}}
+  {{- end -}}
+  {{- if .sidecars -}}
+    {{- $path = printf "%s;sidecars=%s" $path (join "|" .sidecars) -}}
+  {{- end -}}
+  {{- if .extensions -}}
+    {{- $path = printf "%s;extensions=%s" $path (join "|" .extensions) -}}
+  {{- end -}}
+  {{- if .exclude -}}
+    {{- $path = printf "%s;exclude=%s" $path (join "|" .exclude) -}}
+  {{- end -}}
+  {{- if .pathRules -}}
+    {{- $path = printf "%s;rules=%s" $path (join "|" .pathRules) -}}
+  {{- end -}}
+  {{- if .geocode -}}
+    {{- $path = printf "%s;geocode=%s" $path .geocode -}}
+  {{- end -}}
+  {{- if .thumbnail -}}
+    {{- $path = printf "%s;thumbnail=%s" $path .thumbnail -}}
+  {{- end -}}
   {{- $paths = append $paths $path -}}
 {{- end -}}
 {{- join "," $paths -}}
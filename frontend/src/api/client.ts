@@ -307,8 +307,8 @@ export const eventApi = {
 
 export const scanApi = {
   // Scan directories for new videos
-  scanDirectories: async (): Promise<ScanResponse> => {
-    const response = await apiClient.post('/scan');
+  scanDirectories: async (force = false): Promise<ScanResponse> => {
+    const response = await apiClient.post('/scan', { force });
     return response.data;
   },
 };
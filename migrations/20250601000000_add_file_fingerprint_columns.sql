@@ -0,0 +1,11 @@
+-- Add file fingerprint columns to videos table
+-- Up migration
+
+ALTER TABLE videos ADD COLUMN file_mtime BIGINT;
+ALTER TABLE videos ADD COLUMN file_inode BIGINT;
+ALTER TABLE videos ADD COLUMN partial_hash VARCHAR(64);
+
+-- Down migration
+-- ALTER TABLE videos DROP COLUMN file_mtime;
+-- ALTER TABLE videos DROP COLUMN file_inode;
+-- ALTER TABLE videos DROP COLUMN partial_hash;
@@ -0,0 +1,13 @@
+-- Track videos whose files have disappeared from disk
+-- Up migration
+
+ALTER TABLE videos ADD COLUMN offline_since TIMESTAMP;
+
+-- Offline videos are relinked by matching file size and partial hash
+CREATE INDEX IF NOT EXISTS idx_videos_offline_since ON videos (offline_since);
+CREATE INDEX IF NOT EXISTS idx_videos_file_size ON videos (file_size);
+
+-- Down migration
+-- DROP INDEX IF EXISTS idx_videos_file_size;
+-- DROP INDEX IF EXISTS idx_videos_offline_since;
+-- ALTER TABLE videos DROP COLUMN offline_since;
@@ -0,0 +1,10 @@
+-- Add content hash column to videos table for duplicate detection
+-- Up migration
+
+ALTER TABLE videos ADD COLUMN content_hash VARCHAR(64);
+
+CREATE INDEX IF NOT EXISTS idx_videos_content_hash ON videos (content_hash);
+
+-- Down migration
+-- DROP INDEX IF EXISTS idx_videos_content_hash;
+-- ALTER TABLE videos DROP COLUMN content_hash;
@@ -0,0 +1,35 @@
+-- Add scan job history with per-file errors
+-- Up migration
+
+-- Scan jobs table
+CREATE TABLE IF NOT EXISTS scan_jobs (
+    id VARCHAR(36) PRIMARY KEY NOT NULL,
+    status VARCHAR(20) NOT NULL,
+    sources TEXT[] NOT NULL DEFAULT '{}',
+    force_full_rescan BOOLEAN NOT NULL DEFAULT FALSE,
+    new_count INTEGER NOT NULL DEFAULT 0,
+    updated_count INTEGER NOT NULL DEFAULT 0,
+    skipped_count INTEGER NOT NULL DEFAULT 0,
+    failed_count INTEGER NOT NULL DEFAULT 0,
+    started_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished_at TIMESTAMP
+);
+
+-- Errors encountered while processing individual files
+CREATE TABLE IF NOT EXISTS scan_job_errors (
+    id VARCHAR(36) PRIMARY KEY NOT NULL,
+    scan_job_id VARCHAR(36) NOT NULL,
+    file_path TEXT NOT NULL,
+    stage VARCHAR(50) NOT NULL,
+    message TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (scan_job_id) REFERENCES scan_jobs (id) ON DELETE CASCADE
+);
+
+-- Create indexes
+CREATE INDEX IF NOT EXISTS idx_scan_jobs_started_at ON scan_jobs (started_at);
+CREATE INDEX IF NOT EXISTS idx_scan_job_errors_scan_job_id ON scan_job_errors (scan_job_id);
+
+-- Down migration
+-- DROP TABLE IF EXISTS scan_job_errors;
+-- DROP TABLE IF EXISTS scan_jobs;
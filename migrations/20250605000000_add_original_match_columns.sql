@@ -0,0 +1,12 @@
+-- Add original file match status and candidates to videos table
+-- Up migration
+
+ALTER TABLE videos ADD COLUMN original_match_status VARCHAR(20);
+ALTER TABLE videos ADD COLUMN original_candidates JSONB;
+
+CREATE INDEX IF NOT EXISTS idx_videos_original_match_status ON videos (original_match_status);
+
+-- Down migration
+-- DROP INDEX IF EXISTS idx_videos_original_match_status;
+-- ALTER TABLE videos DROP COLUMN original_candidates;
+-- ALTER TABLE videos DROP COLUMN original_match_status;
@@ -0,0 +1,11 @@
+-- Add GPS position columns to videos table
+-- Up migration
+
+ALTER TABLE videos ADD COLUMN latitude DOUBLE PRECISION;
+ALTER TABLE videos ADD COLUMN longitude DOUBLE PRECISION;
+ALTER TABLE videos ADD COLUMN altitude DOUBLE PRECISION;
+
+-- Down migration
+-- ALTER TABLE videos DROP COLUMN altitude;
+-- ALTER TABLE videos DROP COLUMN longitude;
+-- ALTER TABLE videos DROP COLUMN latitude;
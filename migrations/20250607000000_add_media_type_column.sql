@@ -0,0 +1,10 @@
+-- Add media type to videos table so photos can be stored alongside videos
+-- Up migration
+
+ALTER TABLE videos ADD COLUMN media_type VARCHAR(20) NOT NULL DEFAULT 'video';
+
+CREATE INDEX IF NOT EXISTS idx_videos_media_type ON videos (media_type);
+
+-- Down migration
+-- DROP INDEX IF EXISTS idx_videos_media_type;
+-- ALTER TABLE videos DROP COLUMN media_type;
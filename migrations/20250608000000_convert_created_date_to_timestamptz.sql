@@ -0,0 +1,31 @@
+-- Store creation dates as TIMESTAMPTZ, keeping the UTC offset of the camera's clock so the local
+-- time a clip was shot can be recovered
+-- Up migration
+
+-- Existing values are RFC 3339 strings; anything that doesn't parse becomes NULL
+CREATE FUNCTION pg_temp.try_timestamptz(value TEXT) RETURNS TIMESTAMPTZ AS $$
+BEGIN
+    RETURN value::TIMESTAMPTZ;
+EXCEPTION WHEN OTHERS THEN
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+-- The existing idx_videos_created_date index is rebuilt for the new type and serves ordering
+ALTER TABLE videos ALTER COLUMN created_date TYPE TIMESTAMPTZ USING pg_temp.try_timestamptz(created_date);
+
+-- Minutes east of UTC, NULL when the metadata didn't record an offset
+ALTER TABLE videos ADD COLUMN created_date_offset INTEGER;
+
+-- Wall-clock time where the file was recorded, used for filtering by day
+ALTER TABLE videos ADD COLUMN created_date_local TIMESTAMP GENERATED ALWAYS AS (
+    (created_date AT TIME ZONE 'UTC') + make_interval(mins => COALESCE(created_date_offset, 0))
+) STORED;
+
+CREATE INDEX IF NOT EXISTS idx_videos_created_date_local ON videos (created_date_local);
+
+-- Down migration
+-- DROP INDEX IF EXISTS idx_videos_created_date_local;
+-- ALTER TABLE videos DROP COLUMN created_date_local;
+-- ALTER TABLE videos DROP COLUMN created_date_offset;
+-- ALTER TABLE videos ALTER COLUMN created_date TYPE VARCHAR(50) USING to_char(created_date AT TIME ZONE 'UTC', 'YYYY-MM-DD"T"HH24:MI:SS"+00:00"');
@@ -0,0 +1,7 @@
+-- Add the place found by reverse geocoding a video's GPS position
+-- Up migration
+
+ALTER TABLE videos ADD COLUMN suggested_location VARCHAR(255);
+
+-- Down migration
+-- ALTER TABLE videos DROP COLUMN suggested_location;
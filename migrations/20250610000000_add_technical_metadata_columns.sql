@@ -0,0 +1,39 @@
+-- Add typed technical metadata columns to videos table, so clips can be filtered and sorted
+-- by codec, resolution, frame rate and audio format
+-- Up migration
+
+ALTER TABLE videos ADD COLUMN width INTEGER;
+ALTER TABLE videos ADD COLUMN height INTEGER;
+ALTER TABLE videos ADD COLUMN video_codec VARCHAR(50);
+ALTER TABLE videos ADD COLUMN video_profile VARCHAR(100);
+ALTER TABLE videos ADD COLUMN frame_rate DOUBLE PRECISION;
+ALTER TABLE videos ADD COLUMN bit_rate BIGINT;
+ALTER TABLE videos ADD COLUMN bit_depth INTEGER;
+ALTER TABLE videos ADD COLUMN color_primaries VARCHAR(50);
+ALTER TABLE videos ADD COLUMN hdr BOOLEAN;
+ALTER TABLE videos ADD COLUMN rotation INTEGER;
+ALTER TABLE videos ADD COLUMN audio_codec VARCHAR(50);
+ALTER TABLE videos ADD COLUMN audio_channels INTEGER;
+ALTER TABLE videos ADD COLUMN audio_sample_rate INTEGER;
+
+CREATE INDEX IF NOT EXISTS idx_videos_resolution ON videos (width, height);
+CREATE INDEX IF NOT EXISTS idx_videos_video_codec ON videos (video_codec);
+CREATE INDEX IF NOT EXISTS idx_videos_frame_rate ON videos (frame_rate);
+
+-- Down migration
+-- DROP INDEX IF EXISTS idx_videos_frame_rate;
+-- DROP INDEX IF EXISTS idx_videos_video_codec;
+-- DROP INDEX IF EXISTS idx_videos_resolution;
+-- ALTER TABLE videos DROP COLUMN audio_sample_rate;
+-- ALTER TABLE videos DROP COLUMN audio_channels;
+-- ALTER TABLE videos DROP COLUMN audio_codec;
+-- ALTER TABLE videos DROP COLUMN rotation;
+-- ALTER TABLE videos DROP COLUMN hdr;
+-- ALTER TABLE videos DROP COLUMN color_primaries;
+-- ALTER TABLE videos DROP COLUMN bit_depth;
+-- ALTER TABLE videos DROP COLUMN bit_rate;
+-- ALTER TABLE videos DROP COLUMN frame_rate;
+-- ALTER TABLE videos DROP COLUMN video_profile;
+-- ALTER TABLE videos DROP COLUMN video_codec;
+-- ALTER TABLE videos DROP COLUMN height;
+-- ALTER TABLE videos DROP COLUMN width;
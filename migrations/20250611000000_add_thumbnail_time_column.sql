@@ -0,0 +1,7 @@
+-- Add the time of a thumbnail chosen by hand, which scans keep instead of picking their own
+-- Up migration
+
+ALTER TABLE videos ADD COLUMN thumbnail_time DOUBLE PRECISION;
+
+-- Down migration
+-- ALTER TABLE videos DROP COLUMN thumbnail_time;
@@ -0,0 +1,7 @@
+-- Add a hash of each video's thumbnail, which versions the scaled copies served to clients
+-- Up migration
+
+ALTER TABLE videos ADD COLUMN thumbnail_hash VARCHAR(64);
+
+-- Down migration
+-- ALTER TABLE videos DROP COLUMN thumbnail_hash;
@@ -0,0 +1,10 @@
+-- Link camera originals that FFmpeg can't decode, such as BRAW, to the proxy they take their thumbnail,
+-- duration and playback from
+-- Up migration
+
+ALTER TABLE videos ADD COLUMN proxy_video_id VARCHAR(36) REFERENCES videos (id) ON DELETE SET NULL;
+CREATE INDEX IF NOT EXISTS idx_videos_original_file_path ON videos (original_file_path);
+
+-- Down migration
+-- DROP INDEX IF EXISTS idx_videos_original_file_path;
+-- ALTER TABLE videos DROP COLUMN proxy_video_id;
@@ -0,0 +1,8 @@
+-- Remember the rating last read from a sidecar, so edits to the sidecar replace it unless the rating was
+-- changed in the app since
+-- Up migration
+
+ALTER TABLE videos ADD COLUMN sidecar_rating INTEGER;
+
+-- Down migration
+-- ALTER TABLE videos DROP COLUMN sidecar_rating;
@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
 use std::env;
+use std::path::Path;
 use anyhow::Result;
 
+use crate::utils::{get_file_extension, glob_matches, media_type, media_type_for_extension, PathTemplate};
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Config {
     pub server: ServerConfig,
@@ -21,13 +24,313 @@ pub struct DatabaseConfig {
     pub max_connections: u32,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct MediaPathConfig {
     pub name: Option<String>,
     pub path: String,
     pub original_path: Option<String>,
     pub original_extension: Option<String>,
     pub default_shoebox: Option<String>,
+    pub watch: bool,
+    pub schedule: Option<ScanSchedule>,
+    /// Sidecar types read next to media files, all of them if not set
+    pub sidecars: Option<Vec<SidecarKind>>,
+    /// Lowercase extensions of the files to ingest, the default video and photo formats if not set
+    pub extensions: Option<Vec<String>>,
+    /// Glob patterns of files and directories to skip, relative to the source or original root
+    pub exclude: Vec<String>,
+    /// Path templates such as `{year}/{event}/{person}/*` that derive metadata from folder names
+    pub path_rules: Vec<String>,
+    /// What to do with the place name found for a file's GPS position
+    pub geocode: GeocodeMode,
+    /// Where in a video its thumbnail is taken from
+    pub thumbnail: ThumbnailPosition,
+    /// Root of the source when only a sub-path of it is scanned, so exclude patterns and path rules keep their meaning
+    #[serde(skip)]
+    pub source_root: Option<String>,
+}
+
+impl MediaPathConfig {
+    /// Root directory of the source, even when only a sub-path of it is scanned
+    pub fn root(&self) -> &str {
+        self.source_root.as_deref().unwrap_or(&self.path)
+    }
+
+    pub fn reads_sidecar(&self, kind: SidecarKind) -> bool {
+        self.sidecars.as_ref().is_none_or(|sidecars| sidecars.contains(&kind))
+    }
+
+    /// Media type of a file in this source, or `None` if the source doesn't ingest it.
+    /// This is the one place that decides what counts as media.
+    pub fn classify(&self, path: &Path) -> Option<&'static str> {
+        if self.is_excluded(path) {
+            return None;
+        }
+
+        let ext = get_file_extension(path)?;
+        match &self.extensions {
+            Some(extensions) => extensions.contains(&ext).then(|| media_type_for_extension(&ext)),
+            None => media_type(path),
+        }
+    }
+
+    /// Check if a file or directory under the source or original root matches an exclude pattern
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        if self.exclude.is_empty() {
+            return false;
+        }
+
+        let roots = [Some(self.root()), self.original_path.as_deref()];
+        let Some(relative_path) = roots
+            .into_iter()
+            .flatten()
+            .find_map(|root| path.strip_prefix(root).ok())
+        else {
+            return false;
+        };
+
+        self.exclude.iter().any(|pattern| glob_matches(pattern, relative_path))
+    }
+}
+
+/// Metadata files written next to media files by cameras and other tools
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SidecarKind {
+    /// DJI telemetry subtitles
+    Srt,
+    /// Adobe XMP, as written by Lightroom and Resolve
+    Xmp,
+    /// Google Takeout metadata
+    Json,
+}
+
+impl SidecarKind {
+    pub const ALL: [SidecarKind; 3] = [SidecarKind::Srt, SidecarKind::Xmp, SidecarKind::Json];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "srt" => Some(SidecarKind::Srt),
+            "xmp" => Some(SidecarKind::Xmp),
+            "json" => Some(SidecarKind::Json),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SidecarKind::Srt => "srt",
+            SidecarKind::Xmp => "xmp",
+            SidecarKind::Json => "json",
+        }
+    }
+}
+
+/// How a source uses offline reverse geocoding of GPS positions
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GeocodeMode {
+    /// Don't look up place names
+    #[default]
+    Off,
+    /// Store the nearest place as a suggested location
+    Suggest,
+    /// Also use the nearest place as the location of videos that don't have one
+    Fill,
+}
+
+impl GeocodeMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "off" | "false" | "none" => Some(GeocodeMode::Off),
+            "suggest" => Some(GeocodeMode::Suggest),
+            "fill" => Some(GeocodeMode::Fill),
+            _ => None,
+        }
+    }
+}
+
+/// Where in a video its thumbnail is taken from. Except for `First`, several candidate frames
+/// are scored and the best one is used, so black, blurry and flat frames are skipped.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum ThumbnailPosition {
+    /// Frames sampled across the whole video
+    #[default]
+    Auto,
+    /// The first frame, without scoring
+    First,
+    /// Frames from a few seconds starting at a share of the duration, from 0 to 1
+    Fraction(f64),
+    /// Frames from a few seconds starting at a time, in seconds
+    Seconds(f64),
+}
+
+impl ThumbnailPosition {
+    /// Parse `auto`, `first`, a percentage such as `25%` or a time such as `12.5s` or `12.5`
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_lowercase();
+        match value.as_str() {
+            "auto" => Some(ThumbnailPosition::Auto),
+            "first" => Some(ThumbnailPosition::First),
+            _ => match value.strip_suffix('%') {
+                Some(percent) => percent
+                    .trim()
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|percent| (0.0..=100.0).contains(percent))
+                    .map(|percent| ThumbnailPosition::Fraction(percent / 100.0)),
+                None => parse_seconds(&value).map(ThumbnailPosition::Seconds),
+            },
+        }
+    }
+}
+
+/// Parse a non-negative time in seconds, with an optional `s` suffix
+pub fn parse_seconds(value: &str) -> Option<f64> {
+    let value = value.trim();
+    value
+        .strip_suffix('s')
+        .unwrap_or(value)
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+}
+
+/// A size thumbnails are scaled down to, such as `small` at 320 pixels wide
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ThumbnailSize {
+    pub name: String,
+    pub width: u32,
+}
+
+const DEFAULT_THUMBNAIL_SIZES: &str = "small=320,medium=640,large=1280";
+
+/// Image format of scaled thumbnails
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThumbnailFormat {
+    Jpeg,
+    Webp,
+    Avif,
+}
+
+impl ThumbnailFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "jpeg" | "jpg" => Some(ThumbnailFormat::Jpeg),
+            "webp" => Some(ThumbnailFormat::Webp),
+            "avif" => Some(ThumbnailFormat::Avif),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ThumbnailFormat::Jpeg => "jpg",
+            ThumbnailFormat::Webp => "webp",
+            ThumbnailFormat::Avif => "avif",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ThumbnailFormat::Jpeg => "image/jpeg",
+            ThumbnailFormat::Webp => "image/webp",
+            ThumbnailFormat::Avif => "image/avif",
+        }
+    }
+}
+
+/// Container and codec of hover previews
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PreviewFormat {
+    /// H.264 in MP4, which every browser plays
+    Mp4,
+    /// VP9 in WebM, which is smaller
+    Webm,
+}
+
+impl PreviewFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "mp4" => Some(PreviewFormat::Mp4),
+            "webm" => Some(PreviewFormat::Webm),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PreviewFormat::Mp4 => "mp4",
+            PreviewFormat::Webm => "webm",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            PreviewFormat::Mp4 => "video/mp4",
+            PreviewFormat::Webm => "video/webm",
+        }
+    }
+}
+
+// Parse sizes such as "small=320,medium=640", skipping invalid ones
+fn parse_thumbnail_sizes(value: &str) -> Vec<ThumbnailSize> {
+    value
+        .split(',')
+        .filter(|size| !size.trim().is_empty())
+        .filter_map(|size| {
+            let parsed = size.split_once('=').and_then(|(name, width)| {
+                let name = name.trim().to_lowercase();
+                let width = width.trim().parse::<u32>().ok().filter(|width| *width > 0)?;
+                (!name.is_empty() && name != "original").then_some(ThumbnailSize { name, width })
+            });
+            if parsed.is_none() {
+                tracing::warn!("Invalid thumbnail size '{}'", size);
+            }
+            parsed
+        })
+        .collect()
+}
+
+/// When a source is rescanned automatically
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum ScanSchedule {
+    /// Rescan at a fixed interval, in seconds
+    Interval(u64),
+    /// Rescan whenever the cron expression matches, in local time
+    Cron(String),
+}
+
+impl ScanSchedule {
+    // Parse an interval such as "30m", "6h" or "1d", or a cron expression such as "0 3 * * *"
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+
+        if value.contains(char::is_whitespace) {
+            return croner::Cron::new(value)
+                .parse()
+                .ok()
+                .map(|_| ScanSchedule::Cron(value.to_string()));
+        }
+
+        let (number, unit) = value.split_at(value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len()));
+        let multiplier = match unit {
+            "" | "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            _ => return None,
+        };
+
+        number
+            .parse::<u64>()
+            .ok()
+            .filter(|number| *number > 0)
+            .map(|number| ScanSchedule::Interval(number * multiplier))
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -40,6 +343,15 @@ pub struct MediaSourceConfig {
     pub original_extension: Option<String>,
     #[serde(rename = "defaultShoebox")]
     pub default_shoebox: Option<String>,
+    pub watch: Option<bool>,
+    pub schedule: Option<String>,
+    pub sidecars: Option<Vec<String>>,
+    pub extensions: Option<Vec<String>>,
+    pub exclude: Option<Vec<String>>,
+    #[serde(rename = "pathRules")]
+    pub path_rules: Option<Vec<String>>,
+    pub geocode: Option<String>,
+    pub thumbnail: Option<String>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -60,9 +372,46 @@ pub struct MediaConfig {
     pub thumbnail_path: String,
     #[serde(rename = "maxConcurrentTasks")]
     pub max_concurrent_tasks: usize,
+    #[serde(rename = "partialHash")]
+    pub partial_hash: bool,
+    #[serde(rename = "contentHash")]
+    pub content_hash: bool,
+    #[serde(rename = "watchSettleSeconds")]
+    pub watch_settle_seconds: u64,
+    /// GeoNames cities file used for reverse geocoding
+    #[serde(rename = "geonamesPath")]
+    pub geonames_path: String,
+    /// Frames in each video's seek preview sprite sheet, 0 to disable
+    #[serde(rename = "spriteFrames")]
+    pub sprite_frames: u32,
+    /// Named widths the thumbnails are scaled down to on request
+    #[serde(rename = "thumbnailSizes")]
+    pub thumbnail_sizes: Vec<ThumbnailSize>,
+    /// Image format of the scaled thumbnails, unless a request asks for another
+    #[serde(rename = "thumbnailFormat")]
+    pub thumbnail_format: ThumbnailFormat,
+    /// Generate a short looping preview of each video during scans
+    #[serde(rename = "scanPreviews")]
+    pub scan_previews: bool,
+    #[serde(rename = "previewFormat")]
+    pub preview_format: PreviewFormat,
+    /// Directory HLS segments transcoded for browsers are cached in
+    #[serde(rename = "transcodeCachePath")]
+    pub transcode_cache_path: String,
+    /// Size the transcode cache is kept under, in megabytes
+    #[serde(rename = "transcodeCacheSizeMb")]
+    pub transcode_cache_size_mb: u64,
 }
 
 impl MediaConfig {
+    /// The source a file belongs to, preferring the most specific one when sources are nested
+    pub fn source_for(&self, file_path: &str) -> Option<&MediaPathConfig> {
+        self.source_paths
+            .iter()
+            .filter(|source| Path::new(file_path).starts_with(&source.path))
+            .max_by_key(|source| source.path.len())
+    }
+
     // Convert MediaSourcePathsConfig to Vec<MediaPathConfig>
     pub fn convert_source_paths(&mut self) {
         if let Some(sources) = &self.media_source_paths_config.sources {
@@ -74,6 +423,15 @@ impl MediaConfig {
                     original_path: source.original_path.clone(),
                     original_extension: source.original_extension.clone(),
                     default_shoebox: source.default_shoebox.clone(),
+                    watch: source.watch.unwrap_or(false),
+                    schedule: source.schedule.as_deref().and_then(|schedule| parse_schedule_option(schedule, &source.path)),
+                    sidecars: source.sidecars.as_ref().map(|sidecars| parse_sidecar_kinds(sidecars.iter().map(String::as_str), &source.path)),
+                    extensions: source.extensions.as_ref().map(|extensions| parse_extensions(extensions.iter().map(String::as_str))),
+                    exclude: source.exclude.clone().unwrap_or_default(),
+                    path_rules: parse_path_rules(source.path_rules.iter().flatten().map(String::as_str), &source.path),
+                    geocode: source.geocode.as_deref().map(|geocode| parse_geocode_option(geocode, &source.path)).unwrap_or_default(),
+                    thumbnail: source.thumbnail.as_deref().map(|thumbnail| parse_thumbnail_option(thumbnail, &source.path)).unwrap_or_default(),
+                    source_root: None,
                 }
             }).collect();
         } else if let Some(legacy_string) = &self.media_source_paths_config.legacy_string {
@@ -118,6 +476,46 @@ impl Config {
                     .unwrap_or_else(|_| "4".to_string())
                     .parse()
                     .unwrap_or(4),
+                partial_hash: env::var("SCAN_PARTIAL_HASH")
+                    .unwrap_or_else(|_| "true".to_string())
+                    .parse()
+                    .unwrap_or(true),
+                // Reading every file in full is slow on large libraries, so duplicate detection is opt-in
+                content_hash: env::var("SCAN_CONTENT_HASH")
+                    .unwrap_or_else(|_| "false".to_string())
+                    .parse()
+                    .unwrap_or(false),
+                watch_settle_seconds: env::var("WATCH_SETTLE_SECONDS")
+                    .unwrap_or_else(|_| "5".to_string())
+                    .parse()
+                    .unwrap_or(5),
+                geonames_path: env::var("GEONAMES_PATH")
+                    .unwrap_or_else(|_| "./geonames/cities15000.txt".to_string()),
+                sprite_frames: env::var("THUMBNAIL_SPRITE_FRAMES")
+                    .unwrap_or_else(|_| "100".to_string())
+                    .parse()
+                    .unwrap_or(100),
+                thumbnail_sizes: parse_thumbnail_sizes(
+                    &env::var("THUMBNAIL_SIZES").unwrap_or_else(|_| DEFAULT_THUMBNAIL_SIZES.to_string())
+                ),
+                thumbnail_format: env::var("THUMBNAIL_FORMAT")
+                    .ok()
+                    .and_then(|format| ThumbnailFormat::parse(&format))
+                    .unwrap_or(ThumbnailFormat::Webp),
+                scan_previews: env::var("SCAN_PREVIEWS")
+                    .unwrap_or_else(|_| "false".to_string())
+                    .parse()
+                    .unwrap_or(false),
+                preview_format: env::var("PREVIEW_FORMAT")
+                    .ok()
+                    .and_then(|format| PreviewFormat::parse(&format))
+                    .unwrap_or(PreviewFormat::Mp4),
+                transcode_cache_path: env::var("TRANSCODE_CACHE_PATH")
+                    .unwrap_or_else(|_| "./transcode-cache".to_string()),
+                transcode_cache_size_mb: env::var("TRANSCODE_CACHE_SIZE_MB")
+                    .unwrap_or_else(|_| "10240".to_string())
+                    .parse()
+                    .unwrap_or(10240),
             },
         };
 
@@ -128,227 +526,301 @@ impl Config {
     }
 }
 
-fn parse_comma_separated_paths_from_string(paths_str: &str) -> Vec<MediaPathConfig> {
-    paths_str
-        .split(',')
-        .map(|s| s.trim().to_string())
-        .map(|path_config| {
-            // Check if the path contains a named section (e.g., "bmpcc:")
-            if let Some(colon_pos) = path_config.find(':') {
-                let name = path_config[..colon_pos].trim().to_string();
-                let config_str = path_config[colon_pos + 1..].trim().to_string();
-
-                // Split the configuration by semicolons
-                let parts: Vec<&str> = config_str.split(';').collect();
-
-                if parts.is_empty() {
-                    // Invalid configuration, return a default
-                    return MediaPathConfig {
-                        name: Some(name),
-                        path: "./media".to_string(),
-                        original_path: None,
-                        original_extension: None,
-                        default_shoebox: None,
-                    };
-                }
+/// Options a source can take after its positional settings
+const SOURCE_OPTIONS: &[&str] = &["watch", "schedule", "sidecars", "extensions", "exclude", "rules", "geocode", "thumbnail"];
+
+// Split a source configuration into positional parts and trailing key=value options.
+// Only known option names count as options, so paths containing an equals sign stay positional.
+fn split_source_options(config_str: &str) -> (Vec<&str>, Vec<(String, String)>) {
+    let mut parts = Vec::new();
+    let mut options = Vec::new();
+
+    for (index, part) in config_str.split(';').enumerate() {
+        // The first part is always the path
+        let option = part
+            .split_once('=')
+            .map(|(key, value)| (key.trim().to_lowercase(), value.trim().to_string()))
+            .filter(|(key, _)| index > 0 && SOURCE_OPTIONS.contains(&key.as_str()));
+
+        match option {
+            Some(option) => options.push(option),
+            None => parts.push(part),
+        }
+    }
+
+    (parts, options)
+}
 
-                let path = parts[0].to_string();
-
-                // Parse original_path if provided
-                let original_path = parts.get(1)
-                    .filter(|&p| !p.is_empty())
-                    .map(|p| p.to_string());
-
-                // Parse original_extension if provided
-                let original_extension = parts.get(2)
-                    .filter(|&e| !e.is_empty())
-                    .map(|e| e.to_string());
-
-                // Parse default_shoebox if provided
-                let default_shoebox = parts.get(3)
-                    .filter(|&s| !s.is_empty())
-                    .map(|s| s.to_string());
-
-                // If original_path is provided without extension, use the same extension as path
-                let original_extension = if original_path.is_some() && original_extension.is_none() {
-                    // Extract extension from path
-                    std::path::Path::new(&path)
-                        .extension()
-                        .and_then(|ext| ext.to_str())
-                        .map(|ext| ext.to_string())
-                } else {
-                    original_extension
-                };
+// Apply key=value options (e.g. "watch=true") to a media path configuration
+fn apply_source_options(path_config: &mut MediaPathConfig, options: &[(String, String)]) {
+    for (key, value) in options {
+        match key.as_str() {
+            "watch" => path_config.watch = parse_bool_option(value),
+            "schedule" => path_config.schedule = parse_schedule_option(value, &path_config.path),
+            "sidecars" => path_config.sidecars = Some(parse_sidecar_kinds(value.split('|'), &path_config.path)),
+            "extensions" => path_config.extensions = Some(parse_extensions(value.split('|'))),
+            "exclude" => path_config.exclude.extend(
+                value.split('|').map(str::trim).filter(|pattern| !pattern.is_empty()).map(String::from)
+            ),
+            "rules" => path_config.path_rules.extend(parse_path_rules(value.split('|'), &path_config.path)),
+            "geocode" => path_config.geocode = parse_geocode_option(value, &path_config.path),
+            "thumbnail" => path_config.thumbnail = parse_thumbnail_option(value, &path_config.path),
+            // split_source_options only passes on SOURCE_OPTIONS
+            _ => {},
+        }
+    }
+}
 
-                MediaPathConfig {
-                    name: Some(name),
-                    path,
-                    original_path,
-                    original_extension,
-                    default_shoebox,
-                }
-            }
-            // Backward compatibility: Check if the path contains configuration options without a name
-            else if path_config.contains(';') {
-                let parts: Vec<&str> = path_config.split(';').collect();
-                let path = parts[0].to_string();
-
-                // Parse original_path if provided
-                let original_path = parts.get(1)
-                    .filter(|&p| !p.is_empty())
-                    .map(|p| p.to_string());
-
-                // Parse original_extension if provided
-                let original_extension = parts.get(2)
-                    .filter(|&e| !e.is_empty())
-                    .map(|e| e.to_string());
-
-                // Parse default_shoebox if provided
-                let default_shoebox = parts.get(3)
-                    .filter(|&s| !s.is_empty())
-                    .map(|s| s.to_string());
-
-                // If original_path is provided without extension, use the same extension as path
-                let original_extension = if original_path.is_some() && original_extension.is_none() {
-                    // Extract extension from path
-                    std::path::Path::new(&path)
-                        .extension()
-                        .and_then(|ext| ext.to_str())
-                        .map(|ext| ext.to_string())
-                } else {
-                    original_extension
-                };
+fn parse_bool_option(value: &str) -> bool {
+    matches!(value.to_lowercase().as_str(), "true" | "1" | "yes" | "on")
+}
 
-                MediaPathConfig {
-                    name: None,
-                    path,
-                    original_path,
-                    original_extension,
-                    default_shoebox,
-                }
-            } else {
-                // Simple path without additional configuration
-                MediaPathConfig {
-                    name: None,
-                    path: path_config,
-                    original_path: None,
-                    original_extension: None,
-                    default_shoebox: None,
-                }
+// Normalize a list of extensions such as ".MP4" to "mp4"
+fn parse_extensions<'a>(values: impl Iterator<Item = &'a str>) -> Vec<String> {
+    values
+        .map(|value| value.trim().trim_start_matches('.').to_lowercase())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+// Keep the path rules that parse as templates
+fn parse_path_rules<'a>(values: impl Iterator<Item = &'a str>, path: &str) -> Vec<String> {
+    values
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .filter(|value| {
+            let valid = PathTemplate::parse(value).is_some();
+            if !valid {
+                tracing::warn!("Invalid path rule '{}' for path {}", value, path);
             }
+            valid
         })
+        .map(String::from)
         .collect()
 }
 
-fn parse_comma_separated_paths(env_var: &str) -> Vec<MediaPathConfig> {
-    env::var(env_var)
-        .unwrap_or_else(|_| "./media".to_string())
-        .split(',')
-        .map(|s| s.trim().to_string())
-        .map(|path_config| {
-            // Check if the path contains a named section (e.g., "bmpcc:")
-            if let Some(colon_pos) = path_config.find(':') {
-                let name = path_config[..colon_pos].trim().to_string();
-                let config_str = path_config[colon_pos + 1..].trim().to_string();
-
-                // Split the configuration by semicolons
-                let parts: Vec<&str> = config_str.split(';').collect();
-
-                if parts.is_empty() {
-                    // Invalid configuration, return a default
-                    return MediaPathConfig {
-                        name: Some(name),
-                        path: "./media".to_string(),
-                        original_path: None,
-                        original_extension: None,
-                        default_shoebox: None,
-                    };
-                }
+// Parse a list of sidecar types; "none" disables sidecars
+fn parse_sidecar_kinds<'a>(values: impl Iterator<Item = &'a str>, path: &str) -> Vec<SidecarKind> {
+    let mut kinds = Vec::new();
+    for value in values.filter(|value| !value.trim().is_empty() && value.trim() != "none") {
+        match SidecarKind::parse(value) {
+            Some(kind) if !kinds.contains(&kind) => kinds.push(kind),
+            Some(_) => {},
+            None => tracing::warn!("Unknown sidecar type '{}' for path {}", value, path),
+        }
+    }
+    kinds
+}
 
-                let path = parts[0].to_string();
-
-                // Parse original_path if provided
-                let original_path = parts.get(1)
-                    .filter(|&p| !p.is_empty())
-                    .map(|p| p.to_string());
-
-                // Parse original_extension if provided
-                let original_extension = parts.get(2)
-                    .filter(|&e| !e.is_empty())
-                    .map(|e| e.to_string());
-
-                // Parse default_shoebox if provided
-                let default_shoebox = parts.get(3)
-                    .filter(|&s| !s.is_empty())
-                    .map(|s| s.to_string());
-
-                // If original_path is provided without extension, use the same extension as path
-                let original_extension = if original_path.is_some() && original_extension.is_none() {
-                    // Extract extension from path
-                    std::path::Path::new(&path)
-                        .extension()
-                        .and_then(|ext| ext.to_str())
-                        .map(|ext| ext.to_string())
-                } else {
-                    original_extension
-                };
+fn parse_geocode_option(value: &str, path: &str) -> GeocodeMode {
+    GeocodeMode::parse(value).unwrap_or_else(|| {
+        tracing::warn!("Invalid geocode mode '{}' for path {}", value, path);
+        GeocodeMode::Off
+    })
+}
 
-                MediaPathConfig {
-                    name: Some(name),
-                    path,
-                    original_path,
-                    original_extension,
-                    default_shoebox,
-                }
-            }
-            // Backward compatibility: Check if the path contains configuration options without a name
-            else if path_config.contains(';') {
-                let parts: Vec<&str> = path_config.split(';').collect();
-                let path = parts[0].to_string();
-
-                // Parse original_path if provided
-                let original_path = parts.get(1)
-                    .filter(|&p| !p.is_empty())
-                    .map(|p| p.to_string());
-
-                // Parse original_extension if provided
-                let original_extension = parts.get(2)
-                    .filter(|&e| !e.is_empty())
-                    .map(|e| e.to_string());
-
-                // Parse default_shoebox if provided
-                let default_shoebox = parts.get(3)
-                    .filter(|&s| !s.is_empty())
-                    .map(|s| s.to_string());
-
-                // If original_path is provided without extension, use the same extension as path
-                let original_extension = if original_path.is_some() && original_extension.is_none() {
-                    // Extract extension from path
-                    std::path::Path::new(&path)
-                        .extension()
-                        .and_then(|ext| ext.to_str())
-                        .map(|ext| ext.to_string())
-                } else {
-                    original_extension
-                };
+fn parse_thumbnail_option(value: &str, path: &str) -> ThumbnailPosition {
+    ThumbnailPosition::parse(value).unwrap_or_else(|| {
+        tracing::warn!("Invalid thumbnail position '{}' for path {}", value, path);
+        ThumbnailPosition::Auto
+    })
+}
 
-                MediaPathConfig {
-                    name: None,
-                    path,
-                    original_path,
-                    original_extension,
-                    default_shoebox,
-                }
-            } else {
-                // Simple path without additional configuration
-                MediaPathConfig {
-                    name: None,
-                    path: path_config,
-                    original_path: None,
-                    original_extension: None,
-                    default_shoebox: None,
-                }
-            }
+fn parse_schedule_option(value: &str, path: &str) -> Option<ScanSchedule> {
+    let schedule = ScanSchedule::parse(value);
+    if schedule.is_none() {
+        tracing::warn!("Invalid scan schedule '{}' for path {}", value, path);
+    }
+    schedule
+}
+
+fn parse_comma_separated_paths_from_string(paths_str: &str) -> Vec<MediaPathConfig> {
+    paths_str.split(',').map(|source| parse_source_config(source.trim())).collect()
+}
+
+fn parse_comma_separated_paths(env_var: &str) -> Vec<MediaPathConfig> {
+    parse_comma_separated_paths_from_string(&env::var(env_var).unwrap_or_else(|_| "./media".to_string()))
+}
+
+// Parse one source, "[name:]path[;original_path[;original_extension[;default_shoebox]]][;key=value...]"
+fn parse_source_config(source: &str) -> MediaPathConfig {
+    // Check if the path contains a named section (e.g., "bmpcc:")
+    let (name, config_str) = match source.split_once(':') {
+        Some((name, config_str)) => (Some(name.trim().to_string()), config_str.trim()),
+        None => (None, source),
+    };
+
+    let (parts, options) = split_source_options(config_str);
+    let positional = |index: usize| parts.get(index).filter(|part| !part.is_empty()).map(|part| part.to_string());
+
+    let path = parts[0].to_string();
+    let original_path = positional(1);
+    let default_shoebox = positional(3);
+
+    // If original_path is provided without extension, use the same extension as path
+    let original_extension = positional(2).or_else(|| {
+        original_path.as_ref().and_then(|_| {
+            std::path::Path::new(&path)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| ext.to_string())
         })
-        .collect()
+    });
+
+    if parts.len() > 4 {
+        tracing::warn!("Ignoring unknown settings '{}' for path {}", parts[4..].join(";"), path);
+    }
+
+    let mut path_config = MediaPathConfig {
+        name,
+        path,
+        original_path,
+        original_extension,
+        default_shoebox,
+        ..Default::default()
+    };
+    apply_source_options(&mut path_config, &options);
+    path_config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Configuration, positional parts and options
+    type Case = (&'static str, Vec<&'static str>, Vec<(&'static str, &'static str)>);
+
+    #[test]
+    fn splits_known_source_options() {
+        let cases: [Case; 5] = [
+            ("/mnt/videos", vec!["/mnt/videos"], vec![]),
+            ("/mnt/videos;/home/user/videos;braw;Watch = true", vec!["/mnt/videos", "/home/user/videos", "braw"], vec![("watch", "true")]),
+            ("/mnt/a=b;/originals/x=y;watch=true", vec!["/mnt/a=b", "/originals/x=y"], vec![("watch", "true")]),
+            ("/mnt/videos;;;;exclude=**/cache|*.lrf;rules={year}/*", vec!["/mnt/videos", "", "", ""], vec![("exclude", "**/cache|*.lrf"), ("rules", "{year}/*")]),
+            ("/mnt/videos;wacth=true", vec!["/mnt/videos", "wacth=true"], vec![]),
+        ];
+
+        for (config_str, parts, options) in cases {
+            let options: Vec<(String, String)> = options.into_iter().map(|(key, value)| (key.to_string(), value.to_string())).collect();
+            assert_eq!(split_source_options(config_str), (parts, options), "{config_str}");
+        }
+    }
+
+    #[test]
+    fn parses_named_and_unnamed_sources_alike() {
+        let sources = parse_comma_separated_paths_from_string(
+            "bmpcc:/mnt/bmpcc;/originals/bmpcc;;Camera;watch=true;geocode=fill, /mnt/phone;/originals/phone.mp4;mov;;schedule=6h, /mnt/plain",
+        );
+
+        assert_eq!(sources.len(), 3);
+
+        assert_eq!(sources[0].name.as_deref(), Some("bmpcc"));
+        assert_eq!(sources[0].path, "/mnt/bmpcc");
+        assert_eq!(sources[0].original_path.as_deref(), Some("/originals/bmpcc"));
+        assert_eq!(sources[0].original_extension, None);
+        assert_eq!(sources[0].default_shoebox.as_deref(), Some("Camera"));
+        assert!(sources[0].watch);
+        assert_eq!(sources[0].geocode, GeocodeMode::Fill);
+
+        assert_eq!(sources[1].name, None);
+        assert_eq!(sources[1].path, "/mnt/phone");
+        assert_eq!(sources[1].original_extension.as_deref(), Some("mov"));
+        assert!(sources[1].schedule.is_some());
+        assert!(!sources[1].watch);
+
+        assert_eq!(sources[2].name, None);
+        assert_eq!(sources[2].path, "/mnt/plain");
+        assert_eq!(sources[2].original_path, None);
+    }
+
+    #[test]
+    fn parses_thumbnail_positions() {
+        let cases = [
+            ("auto", Some(ThumbnailPosition::Auto)),
+            (" First ", Some(ThumbnailPosition::First)),
+            ("25%", Some(ThumbnailPosition::Fraction(0.25))),
+            ("0%", Some(ThumbnailPosition::Fraction(0.0))),
+            ("100 %", Some(ThumbnailPosition::Fraction(1.0))),
+            ("101%", None),
+            ("-5%", None),
+            ("%", None),
+            ("10s", Some(ThumbnailPosition::Seconds(10.0))),
+            ("12.5", Some(ThumbnailPosition::Seconds(12.5))),
+            ("-1s", None),
+            ("middle", None),
+            ("", None),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(ThumbnailPosition::parse(value), expected, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn parses_seconds() {
+        let cases = [
+            ("0", Some(0.0)),
+            ("12.5", Some(12.5)),
+            (" 3s ", Some(3.0)),
+            ("3 s", Some(3.0)),
+            ("1e2", Some(100.0)),
+            ("-1", None),
+            ("inf", None),
+            ("NaN", None),
+            ("s", None),
+            ("3ms", None),
+            ("", None),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(parse_seconds(value), expected, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn parses_scan_schedules() {
+        let cron = |expression: &str| Some(ScanSchedule::Cron(expression.to_string()));
+        let cases = [
+            ("45", Some(ScanSchedule::Interval(45))),
+            ("45s", Some(ScanSchedule::Interval(45))),
+            ("30m", Some(ScanSchedule::Interval(30 * 60))),
+            (" 6h ", Some(ScanSchedule::Interval(6 * 60 * 60))),
+            ("1d", Some(ScanSchedule::Interval(24 * 60 * 60))),
+            ("0", None),
+            ("0h", None),
+            ("h", None),
+            ("5w", None),
+            ("1.5h", None),
+            ("-1h", None),
+            ("", None),
+            ("0 3 * * *", cron("0 3 * * *")),
+            (" */15 * * * * ", cron("*/15 * * * *")),
+            ("0 9 * * 1-5", cron("0 9 * * 1-5")),
+            ("0 3 * *", None),
+            ("61 * * * *", None),
+            ("every day", None),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(ScanSchedule::parse(value), expected, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn parses_thumbnail_sizes() {
+        let size = |name: &str, width| ThumbnailSize { name: name.to_string(), width };
+        let cases = [
+            ("small=320,medium=640", vec![size("small", 320), size("medium", 640)]),
+            (" Small = 320 , ", vec![size("small", 320)]),
+            ("small=320,,large=1280", vec![size("small", 320), size("large", 1280)]),
+            ("small=0,medium=640", vec![size("medium", 640)]),
+            ("small=wide,medium=-1,=320,large", vec![]),
+            ("original=640,small=320", vec![size("small", 320)]),
+            ("", vec![]),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(parse_thumbnail_sizes(value), expected, "{value:?}");
+        }
+    }
 }
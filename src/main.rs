@@ -43,8 +43,25 @@ async fn main() -> anyhow::Result<()> {
         db: db_pool,
         config: config.clone(),
         scan_status: std::sync::Arc::new(tokio::sync::RwLock::new(services::ScanStatus::default())),
+        task_permits: std::sync::Arc::new(tokio::sync::Semaphore::new(config.media.max_concurrent_tasks.max(1))),
     };
 
+    // Scans that were running when the server stopped will never finish
+    if let Err(e) = services::ScanJobService::new(app_state.db.clone()).mark_interrupted().await {
+        tracing::error!("Failed to update unfinished scan jobs: {}", e);
+    }
+
+    // Point out a missing reverse geocoding dataset now rather than at the next scan
+    services::GeocoderService::warn_if_missing(&config);
+
+    // Start watching media sources that have automatic ingest enabled
+    if let Err(e) = services::WatcherService::start(app_state.clone()) {
+        tracing::error!("Failed to start filesystem watcher: {}", e);
+    }
+
+    // Start scheduled rescans of media sources
+    services::SchedulerService::start(app_state.clone());
+
     // Determine the path to the frontend dist directory
     let frontend_path = std::env::var("FRONTEND_PATH").unwrap_or_else(|_| {
         if std::path::Path::new("/app/frontend/dist").exists() {
@@ -2,10 +2,18 @@ mod video;
 mod tag;
 mod person;
 mod shoebox;
+mod duplicate;
+mod scan_job;
+mod thumbnail;
+mod playback;
 
 pub use video::*;
 pub use tag::*;
 pub use person::*;
 pub use shoebox::*;
+pub use duplicate::*;
+pub use scan_job::*;
+pub use thumbnail::*;
+pub use playback::*;
 
 // Re-export all models for convenience
@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Video, VideoWithMetadata};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateGroup {
+    pub content_hash: String,
+    pub file_size: Option<i64>,
+    pub videos: Vec<Video>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolveDuplicatesDto {
+    pub keep_id: String,
+    pub duplicate_ids: Vec<String>,
+    #[serde(default)]
+    pub delete_duplicates: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolveDuplicatesResult {
+    pub video: VideoWithMetadata,
+    pub deleted_count: usize,
+}
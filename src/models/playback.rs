@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+/// How a browser should play a video
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaybackInfo {
+    /// Whether the file can be played as it is
+    pub direct_play: bool,
+    /// The file's stream when it can be played directly, otherwise the transcoded HLS playlist
+    pub url: String,
+    /// Why the file has to be transcoded; empty for direct play
+    pub reasons: Vec<String>,
+}
@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+pub const SCAN_JOB_RUNNING: &str = "running";
+pub const SCAN_JOB_COMPLETED: &str = "completed";
+pub const SCAN_JOB_FAILED: &str = "failed";
+pub const SCAN_JOB_CANCELLED: &str = "cancelled";
+pub const SCAN_JOB_INTERRUPTED: &str = "interrupted";
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ScanJob {
+    pub id: String,
+    pub status: String,
+    pub sources: Vec<String>,
+    pub force_full_rescan: bool,
+    pub new_count: i32,
+    pub updated_count: i32,
+    pub skipped_count: i32,
+    pub failed_count: i32,
+    pub started_at: chrono::NaiveDateTime,
+    pub finished_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ScanJobError {
+    pub id: String,
+    pub scan_job_id: String,
+    pub file_path: String,
+    pub stage: String,
+    pub message: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanJobWithErrors {
+    #[serde(flatten)]
+    pub job: ScanJob,
+    pub errors: Vec<ScanJobError>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScanJobQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
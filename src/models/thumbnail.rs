@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+/// Files in the thumbnail directory that no video refers to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThumbnailCleanupResult {
+    pub dry_run: bool,
+    /// Files deleted, or that would be deleted in a dry run
+    pub files: Vec<String>,
+    /// Total size of the files in bytes
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ThumbnailRepairRequest {
+    /// Only report the videos whose thumbnails need regenerating
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThumbnailFailure {
+    pub video_id: String,
+    pub file_path: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThumbnailRegenerateResult {
+    pub dry_run: bool,
+    /// Videos looked at
+    pub checked: usize,
+    /// Videos whose thumbnails were regenerated, or would be in a dry run
+    pub regenerated: Vec<String>,
+    pub failed: Vec<ThumbnailFailure>,
+}
@@ -1,7 +1,14 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
-use chrono::Utc;
+use chrono::{DateTime, FixedOffset, Utc};
+
+pub const ORIGINAL_MATCH_MATCHED: &str = "matched";
+pub const ORIGINAL_MATCH_AMBIGUOUS: &str = "ambiguous";
+pub const ORIGINAL_MATCH_MANUAL: &str = "manual";
+
+pub const MEDIA_TYPE_VIDEO: &str = "video";
+pub const MEDIA_TYPE_PHOTO: &str = "photo";
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Video {
@@ -10,19 +17,95 @@ pub struct Video {
     pub file_name: String,
     pub title: Option<String>,
     pub description: Option<String>,
-    pub created_date: Option<String>,
+    pub created_date: Option<DateTime<Utc>>,
+    /// UTC offset of the camera's clock in minutes, if the metadata recorded one
+    pub created_date_offset: Option<i32>,
+    /// Local time where the file was recorded; the UTC time when the offset isn't known
+    pub created_date_local: Option<chrono::NaiveDateTime>,
     pub file_size: Option<i64>,
     pub thumbnail_path: Option<String>,
+    /// Seconds into the video of a thumbnail chosen by hand, kept by scans
+    pub thumbnail_time: Option<f64>,
+    /// SHA-256 of the thumbnail file; pass it as `v` when requesting a thumbnail to get a cacheable URL
+    pub thumbnail_hash: Option<String>,
     pub rating: Option<i32>,
     pub duration: Option<i64>,
     pub original_file_path: Option<String>,
+    /// Proxy whose thumbnail, duration and file stand in for this video, when FFmpeg can't decode it
+    pub proxy_video_id: Option<String>,
     pub exif_data: Option<serde_json::Value>,
     pub location: Option<String>,
     pub event: Option<String>,
+    pub file_mtime: Option<i64>,
+    pub file_inode: Option<i64>,
+    pub partial_hash: Option<String>,
+    pub content_hash: Option<String>,
+    pub offline_since: Option<chrono::NaiveDateTime>,
+    /// How `original_file_path` was determined: "matched", "ambiguous" or "manual"
+    pub original_match_status: Option<String>,
+    /// Candidate original files when the match is ambiguous
+    pub original_candidates: Option<serde_json::Value>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// Altitude in meters
+    pub altitude: Option<f64>,
+    /// Nearest place to the GPS position, from offline reverse geocoding
+    pub suggested_location: Option<String>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub video_codec: Option<String>,
+    pub video_profile: Option<String>,
+    /// Frames per second
+    pub frame_rate: Option<f64>,
+    /// Overall bit rate in bits per second
+    pub bit_rate: Option<i64>,
+    /// Bits per color channel
+    pub bit_depth: Option<i32>,
+    pub color_primaries: Option<String>,
+    pub hdr: Option<bool>,
+    /// Clockwise rotation in degrees needed to show the video upright
+    pub rotation: Option<i32>,
+    pub audio_codec: Option<String>,
+    pub audio_channels: Option<i32>,
+    pub audio_sample_rate: Option<i32>,
+    /// "video" or "photo"
+    pub media_type: String,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
 }
 
+/// When a file was recorded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CreationTime {
+    pub utc: DateTime<Utc>,
+    /// Minutes east of UTC of the camera's clock, if the metadata recorded it
+    pub offset_minutes: Option<i32>,
+}
+
+impl CreationTime {
+    /// A time whose local offset isn't known
+    pub fn from_utc(utc: DateTime<Utc>) -> Self {
+        Self { utc, offset_minutes: None }
+    }
+
+    /// A time in the offset it was recorded in
+    pub fn from_local(local: DateTime<FixedOffset>) -> Self {
+        Self {
+            utc: local.with_timezone(&Utc),
+            offset_minutes: Some(local.offset().local_minus_utc() / 60),
+        }
+    }
+}
+
+/// Where a file was recorded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GpsPosition {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Meters above sea level
+    pub altitude: Option<f64>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VideoWithMetadata {
     #[serde(flatten)]
@@ -38,7 +121,8 @@ pub struct CreateVideoDto {
     pub file_name: String,
     pub title: Option<String>,
     pub description: Option<String>,
-    pub created_date: Option<String>,
+    pub created_date: Option<DateTime<Utc>>,
+    pub created_date_offset: Option<i32>,
     pub file_size: Option<i64>,
     pub thumbnail_path: Option<String>,
     pub rating: Option<i32>,
@@ -47,11 +131,38 @@ pub struct CreateVideoDto {
     pub exif_data: Option<serde_json::Value>,
     pub location: Option<String>,
     pub event: Option<String>,
+    pub file_mtime: Option<i64>,
+    pub file_inode: Option<i64>,
+    pub partial_hash: Option<String>,
+    pub content_hash: Option<String>,
+    pub media_type: String,
     pub tags: Vec<String>,
     pub people: Vec<String>,
     pub shoeboxes: Vec<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetThumbnailParams {
+    /// Time of the frame to use, in seconds with an optional `s` suffix, e.g. `12.5s`
+    pub at: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ThumbnailParams {
+    /// One of the configured sizes, or `original`
+    pub size: Option<String>,
+    /// `webp`, `avif` or `jpeg`, instead of the configured format
+    pub format: Option<String>,
+    /// The video's `thumbnail_hash`; when it matches, the response may be cached forever
+    pub v: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetOriginalFileDto {
+    /// Path of the original file, or null to record that the video has no original
+    pub original_file_path: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UpdateVideoDto {
     pub title: Option<String>,
@@ -88,6 +199,51 @@ pub struct VideoSearchParams {
     pub end_date: Option<String>,
     pub min_duration: Option<i64>,
     pub max_duration: Option<i64>,
+    pub offline: Option<bool>,
+    pub media_type: Option<String>,
+    pub video_codec: Option<String>,
+    pub audio_codec: Option<String>,
+    pub min_width: Option<i32>,
+    pub min_height: Option<i32>,
+    pub min_frame_rate: Option<f64>,
+    pub max_frame_rate: Option<f64>,
+    pub min_bit_depth: Option<i32>,
+    pub hdr: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PurgeOfflineRequest {
+    /// Only purge these videos; all offline videos are purged when omitted
+    pub video_ids: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PathRulesRequest {
+    /// Source names or sub-paths to apply the rules to; every source when omitted
+    pub sources: Option<Vec<String>>,
+}
+
+/// What the path rules would change on an existing video
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathRuleAssignment {
+    pub video_id: String,
+    pub file_path: String,
+    /// Set only when the video has no event yet
+    pub event: Option<String>,
+    /// Set only when the video has no location yet
+    pub location: Option<String>,
+    pub add_people: Vec<String>,
+    pub add_tags: Vec<String>,
+    pub add_shoeboxes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathRulesResult {
+    pub dry_run: bool,
+    /// Videos matched by a path rule
+    pub matched: usize,
+    /// Videos the rules change, or would change in a dry run
+    pub assignments: Vec<PathRuleAssignment>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -108,14 +264,44 @@ impl Video {
             title: None,
             description: None,
             created_date: None,
+            created_date_offset: None,
+            created_date_local: None,
             file_size: None,
             thumbnail_path: None,
+            thumbnail_time: None,
+            thumbnail_hash: None,
             rating: None,
             duration: None,
             original_file_path: None,
+            proxy_video_id: None,
             exif_data: None,
             location: None,
             event: None,
+            file_mtime: None,
+            file_inode: None,
+            partial_hash: None,
+            content_hash: None,
+            offline_since: None,
+            original_match_status: None,
+            original_candidates: None,
+            latitude: None,
+            longitude: None,
+            altitude: None,
+            suggested_location: None,
+            width: None,
+            height: None,
+            video_codec: None,
+            video_profile: None,
+            frame_rate: None,
+            bit_rate: None,
+            bit_depth: None,
+            color_primaries: None,
+            hdr: None,
+            rotation: None,
+            audio_codec: None,
+            audio_channels: None,
+            audio_sample_rate: None,
+            media_type: MEDIA_TYPE_VIDEO.to_string(),
             created_at: now.clone(),
             updated_at: now,
         }
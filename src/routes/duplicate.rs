@@ -0,0 +1,42 @@
+use axum::{
+    extract::State,
+    routing::{get, post},
+    Json, Router,
+};
+
+use crate::error::Result;
+use crate::models::{DuplicateGroup, ResolveDuplicatesDto, ResolveDuplicatesResult};
+use crate::services::AppState;
+use crate::services::{DuplicateService, VideoService, TagService, PersonService, ThumbnailService, ShoeboxService};
+
+pub fn router(app_state: AppState) -> Router {
+    Router::new()
+        .route("/", get(list_duplicates))
+        .route("/resolve", post(resolve_duplicates))
+        .with_state(app_state)
+}
+
+fn duplicate_service(state: &AppState) -> DuplicateService {
+    let video_service = VideoService::new(
+        state.db.clone(),
+        TagService::new(state.db.clone()),
+        PersonService::new(state.db.clone()),
+        ThumbnailService::new(&state.config),
+        ShoeboxService::new(state.db.clone()),
+    );
+
+    DuplicateService::new(state.db.clone(), video_service)
+}
+
+async fn list_duplicates(State(state): State<AppState>) -> Result<Json<Vec<DuplicateGroup>>> {
+    let groups = duplicate_service(&state).find_groups().await?;
+    Ok(Json(groups))
+}
+
+async fn resolve_duplicates(
+    State(state): State<AppState>,
+    Json(resolve_dto): Json<ResolveDuplicatesDto>,
+) -> Result<Json<ResolveDuplicatesResult>> {
+    let result = duplicate_service(&state).resolve(resolve_dto).await?;
+    Ok(Json(result))
+}
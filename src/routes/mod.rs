@@ -7,6 +7,8 @@ mod system;
 mod location;
 mod event;
 mod shoebox;
+mod duplicate;
+mod thumbnail;
 pub mod media;
 
 use axum::Router;
@@ -29,6 +31,10 @@ pub fn api_router(app_state: AppState) -> Router {
         .nest("/shoeboxes", shoebox::router(app_state.clone()))
         // Scan routes
         .nest("/scan", scan::router(app_state.clone()))
+        // Duplicate detection routes
+        .nest("/duplicates", duplicate::router(app_state.clone()))
+        // Thumbnail maintenance routes
+        .nest("/thumbnails", thumbnail::router(app_state.clone()))
         // Export routes
         .nest("/export", export::router(app_state.clone()))
         // System info routes
@@ -1,155 +1,141 @@
-use axum::{extract::State, routing::{post, get}, Json, Router};
-use serde::Serialize;
+use axum::{
+    extract::{Path, Query, State},
+    response::sse::{Event, KeepAlive, Sse},
+    routing::{post, get},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use tokio_stream::{wrappers::WatchStream, Stream, StreamExt};
 
 use crate::error::Result;
-use crate::services::AppState;
-use crate::services::{ScannerService, VideoService, ThumbnailService, TagService, PersonService};
+use crate::models::{ScanJob, ScanJobQuery, ScanJobWithErrors};
+use crate::services::{AppState, ScanStatusSnapshot};
+use crate::services::{ScannerService, ScanJobService, ScanOptions};
 
 pub fn router(app_state: AppState) -> Router {
     Router::new()
-        .route("/", post(start_scan))
+        .route("/", post(start_scan).delete(cancel_scan))
         .route("/status", get(get_scan_status))
+        .route("/events", get(scan_events))
+        .route("/history", get(list_scan_jobs))
+        .route("/{id}", get(get_scan_job))
         .with_state(app_state)
 }
 
+#[derive(Debug, Default, Deserialize)]
+struct StartScanRequest {
+    /// Re-probe every file instead of skipping unchanged ones
+    #[serde(default)]
+    force: bool,
+    /// Source names or sub-paths to scan instead of every source
+    sources: Option<Vec<String>>,
+}
+
 #[derive(Debug, Serialize)]
 struct ScanResponse {
     message: String,
     scan_started: bool,
+    job_id: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
-struct ScanStatusResponse {
-    in_progress: bool,
-    new_videos_count: usize,
-    updated_videos_count: usize,
+struct CancelScanResponse {
+    message: String,
+    cancelled: bool,
+    job_id: Option<String>,
 }
 
-async fn start_scan(State(state): State<AppState>) -> Result<Json<ScanResponse>> {
-    // Check if a scan is already in progress
-    let is_scan_in_progress = {
-        let status = state.scan_status.read().await;
-        status.in_progress
+async fn start_scan(
+    State(state): State<AppState>,
+    request: Option<Json<StartScanRequest>>,
+) -> Result<Json<ScanResponse>> {
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+
+    // Resolve the requested sources before touching the scan status
+    let source_paths = match &request.sources {
+        Some(targets) if !targets.is_empty() => {
+            ScannerService::resolve_scan_targets(&state.config.media.source_paths, targets)?
+        },
+        _ => state.config.media.source_paths.clone(),
     };
 
-    if is_scan_in_progress {
+    let options = ScanOptions {
+        force_full_rescan: request.force,
+    };
+
+    // Record the requested targets, or every source name for a full scan
+    let sources: Vec<String> = request.sources.clone().filter(|targets| !targets.is_empty()).unwrap_or_else(|| {
+        source_paths
+            .iter()
+            .map(|source| source.name.clone().unwrap_or_else(|| source.path.clone()))
+            .collect()
+    });
+
+    let Some(job) = ScannerService::start_scan(&state, source_paths, sources, options).await? else {
         return Ok(Json(ScanResponse {
             message: "A scan is already in progress".to_string(),
             scan_started: false,
+            job_id: None,
         }));
-    }
-
-    // Mark scan as in progress
-    {
-        let mut status = state.scan_status.write().await;
-        status.in_progress = true;
-        status.new_videos_count = 0;
-        status.updated_videos_count = 0;
-    }
-
-    // Clone what we need for the background task
-    let db = state.db.clone();
-    let config = state.config.clone();
-    let scan_status = state.scan_status.clone();
-    let source_paths = config.media.source_paths.clone();
-
-    // Spawn a background task to perform the scan
-    tokio::spawn(async move {
-        let video_service = VideoService::new(
-            db.clone(),
-            TagService::new(db.clone()),
-            PersonService::new(db.clone()),
-            ThumbnailService::new(&config),
-            crate::services::ShoeboxService::new(db.clone()),
-        );
-
-        let thumbnail_service = ThumbnailService::new(&config);
-
-        // Start the scan but don't wait for it to complete
-        match ScannerService::scan_directories(
-            &source_paths,
-            video_service,
-            thumbnail_service,
-            &config,
-        ).await {
-            Ok((new_videos_arc, updated_videos_arc, tasks)) => {
-                // Spawn another task to wait for all processing tasks to complete
-                // This ensures the main scan task returns quickly
-                tokio::spawn(async move {
-                    // Periodically update the scan status with progress
-                    let update_interval = tokio::time::Duration::from_secs(2);
-                    let mut interval = tokio::time::interval(update_interval);
-
-                    // Track tasks that are still running
-                    let mut remaining_tasks = tasks;
-
-                    while !remaining_tasks.is_empty() {
-                        interval.tick().await;
-
-                        // Update status with current progress
-                        {
-                            let new_count = {
-                                let guard = new_videos_arc.lock().await;
-                                guard.len()
-                            };
-
-                            let updated_count = {
-                                let guard = updated_videos_arc.lock().await;
-                                guard.len()
-                            };
-
-                            let mut status = scan_status.write().await;
-                            status.new_videos_count = new_count;
-                            status.updated_videos_count = updated_count;
-                        }
-
-                        // Check which tasks have completed
-                        remaining_tasks.retain(|task| !task.is_finished());
-                    }
-
-                    // All tasks completed, collect final results
-                    match ScannerService::collect_scan_results(
-                        new_videos_arc,
-                        updated_videos_arc,
-                        Vec::new() // Empty vec since we've already waited for tasks
-                    ).await {
-                        Ok((new_videos, updated_videos)) => {
-                            // Update scan status with final results
-                            let mut status = scan_status.write().await;
-                            status.in_progress = false;
-                            status.new_videos_count = new_videos.len();
-                            status.updated_videos_count = updated_videos.len();
-                        },
-                        Err(e) => {
-                            tracing::error!("Error collecting scan results: {}", e);
-                            // Mark scan as not in progress even if it failed
-                            let mut status = scan_status.write().await;
-                            status.in_progress = false;
-                        }
-                    }
-                });
-            },
-            Err(e) => {
-                tracing::error!("Error starting scan: {}", e);
-                // Mark scan as not in progress if it failed to start
-                let mut status = scan_status.write().await;
-                status.in_progress = false;
-            }
-        }
-    });
+    };
 
     Ok(Json(ScanResponse {
         message: "Scan started successfully".to_string(),
         scan_started: true,
+        job_id: Some(job.id),
     }))
 }
 
-async fn get_scan_status(State(state): State<AppState>) -> Result<Json<ScanStatusResponse>> {
+async fn cancel_scan(State(state): State<AppState>) -> Result<Json<CancelScanResponse>> {
     let status = state.scan_status.read().await;
 
-    Ok(Json(ScanStatusResponse {
-        in_progress: status.in_progress,
-        new_videos_count: status.new_videos_count,
-        updated_videos_count: status.updated_videos_count,
-    }))
+    match (&status.cancel_token, status.in_progress) {
+        (Some(cancel_token), true) => {
+            cancel_token.cancel();
+            Ok(Json(CancelScanResponse {
+                message: "Scan cancellation requested".to_string(),
+                cancelled: true,
+                job_id: status.job_id.clone(),
+            }))
+        },
+        _ => Ok(Json(CancelScanResponse {
+            message: "No scan is in progress".to_string(),
+            cancelled: false,
+            job_id: None,
+        })),
+    }
+}
+
+async fn get_scan_status(State(state): State<AppState>) -> Result<Json<ScanStatusSnapshot>> {
+    let status = state.scan_status.read().await;
+    Ok(Json(status.snapshot()))
+}
+
+// Stream the scan status as "progress" events, starting with the current status
+async fn scan_events(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = std::result::Result<Event, axum::Error>>> {
+    let updates = state.scan_status.read().await.subscribe();
+    let stream = WatchStream::new(updates)
+        .map(|snapshot| Event::default().event("progress").json_data(snapshot));
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+async fn list_scan_jobs(
+    State(state): State<AppState>,
+    Query(query): Query<ScanJobQuery>,
+) -> Result<Json<Vec<ScanJob>>> {
+    let jobs = ScanJobService::new(state.db.clone())
+        .find_all(query.limit.unwrap_or(50), query.offset.unwrap_or(0))
+        .await?;
+    Ok(Json(jobs))
+}
+
+async fn get_scan_job(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<ScanJobWithErrors>> {
+    let job = ScanJobService::new(state.db.clone()).find_with_errors(&id).await?;
+    Ok(Json(job))
 }
@@ -0,0 +1,82 @@
+use axum::{
+    extract::State,
+    routing::{get, post},
+    Json, Router,
+};
+use std::sync::Arc;
+
+use crate::error::Result;
+use crate::models::{ThumbnailCleanupResult, ThumbnailRegenerateResult, ThumbnailRepairRequest, VideoSearchParams};
+use crate::services::AppState;
+use crate::services::{ThumbnailMaintenanceService, VideoService, TagService, PersonService, ThumbnailService, ShoeboxService};
+
+pub fn router(app_state: AppState) -> Router {
+    Router::new()
+        .route("/orphans", get(list_orphans).delete(delete_orphans))
+        .route("/repair", post(repair_thumbnails))
+        .route("/regenerate", post(regenerate_thumbnails))
+        .with_state(app_state)
+}
+
+fn video_service(state: &AppState) -> VideoService {
+    VideoService::new(
+        state.db.clone(),
+        TagService::new(state.db.clone()),
+        PersonService::new(state.db.clone()),
+        ThumbnailService::new(&state.config),
+        ShoeboxService::new(state.db.clone()),
+    )
+}
+
+async fn list_orphans(State(state): State<AppState>) -> Result<Json<ThumbnailCleanupResult>> {
+    let thumbnail_service = ThumbnailService::new(&state.config);
+    let result = ThumbnailMaintenanceService::remove_orphans(&video_service(&state), &thumbnail_service, true).await?;
+    Ok(Json(result))
+}
+
+async fn delete_orphans(State(state): State<AppState>) -> Result<Json<ThumbnailCleanupResult>> {
+    let thumbnail_service = ThumbnailService::new(&state.config);
+    let result = ThumbnailMaintenanceService::remove_orphans(&video_service(&state), &thumbnail_service, false).await?;
+    Ok(Json(result))
+}
+
+async fn repair_thumbnails(
+    State(state): State<AppState>,
+    request: Option<Json<ThumbnailRepairRequest>>,
+) -> Result<Json<ThumbnailRegenerateResult>> {
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+
+    let result = ThumbnailMaintenanceService::repair(
+        Arc::new(video_service(&state)),
+        Arc::new(ThumbnailService::new(&state.config)),
+        &state.config,
+        state.task_permits.clone(),
+        request.dry_run,
+    ).await?;
+    Ok(Json(result))
+}
+
+async fn regenerate_thumbnails(
+    State(state): State<AppState>,
+    Json(mut params): Json<VideoSearchParams>,
+) -> Result<Json<ThumbnailRegenerateResult>> {
+    let video_service = video_service(&state);
+
+    // Regenerate every matching video rather than the first page of results
+    params.limit.get_or_insert(i64::from(i32::MAX));
+    let videos = video_service
+        .search(params)
+        .await?
+        .into_iter()
+        .map(|video| video.video)
+        .collect();
+
+    let result = ThumbnailMaintenanceService::regenerate(
+        Arc::new(video_service),
+        Arc::new(ThumbnailService::new(&state.config)),
+        &state.config,
+        state.task_permits.clone(),
+        videos,
+    ).await;
+    Ok(Json(result))
+}
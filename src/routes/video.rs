@@ -13,9 +13,9 @@ use std::fs;
 use std::io::Read;
 
 use crate::error::{Result, AppError};
-use crate::models::{CreateVideoDto, UpdateVideoDto, VideoSearchParams, BulkUpdateVideoDto};
+use crate::models::{CreateVideoDto, UpdateVideoDto, VideoSearchParams, BulkUpdateVideoDto, PurgeOfflineRequest, SetOriginalFileDto, SetThumbnailParams, ThumbnailParams, PathRulesRequest, PathRulesResult, PlaybackInfo, Video, MEDIA_TYPE_VIDEO};
 use crate::services::AppState;
-use crate::services::VideoService;
+use crate::services::{TranscodeService, VideoService};
 
 pub fn router(app_state: AppState) -> Router {
     Router::new()
@@ -23,10 +23,27 @@ pub fn router(app_state: AppState) -> Router {
         .route("/", post(create_video))
         .route("/search", post(search_videos))
         .route("/bulk-update", post(bulk_update_videos))
+        .route("/offline", get(list_offline_videos))
+        .route("/offline/purge", post(purge_offline_videos))
+        .route("/originals/ambiguous", get(list_ambiguous_originals))
+        .route("/path-rules/preview", post(preview_path_rules))
+        .route("/path-rules/apply", post(apply_path_rules))
         .route("/{id}", get(get_video))
         .route("/{id}", put(update_video))
         .route("/{id}", delete(delete_video))
         .route("/{id}/stream", get(stream_video))
+        .route("/{id}/thumbnail", get(get_thumbnail))
+        .route("/{id}/thumbnail", put(set_thumbnail))
+        .route("/{id}/thumbnail", delete(clear_thumbnail))
+        .route("/{id}/thumbnails.vtt", get(get_sprite_track))
+        .route("/{id}/sprite.jpg", get(get_sprite_sheet))
+        .route("/{id}/preview", get(get_preview))
+        .route("/{id}/playback", get(get_playback))
+        .route("/{id}/hls/master.m3u8", get(get_hls_master_playlist))
+        .route("/{id}/hls/index.m3u8", get(get_hls_media_playlist))
+        .route("/{id}/hls/{segment}", get(get_hls_segment))
+        .route("/{id}/original", put(set_original_file))
+        .route("/{id}/original", delete(clear_original_file))
         .with_state(app_state)
 }
 
@@ -152,6 +169,488 @@ async fn bulk_update_videos(
     Ok(Json(videos))
 }
 
+async fn list_offline_videos(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<crate::models::Video>>> {
+    let video_service = VideoService::new(
+        state.db.clone(),
+        crate::services::TagService::new(state.db.clone()),
+        crate::services::PersonService::new(state.db.clone()),
+        crate::services::ThumbnailService::new(&state.config),
+        crate::services::ShoeboxService::new(state.db.clone()),
+    );
+
+    let videos = video_service.find_offline().await?;
+    Ok(Json(videos))
+}
+
+#[derive(serde::Serialize)]
+struct PurgeOfflineResponse {
+    count: usize,
+}
+
+async fn purge_offline_videos(
+    State(state): State<AppState>,
+    request: Option<Json<PurgeOfflineRequest>>,
+) -> Result<Json<PurgeOfflineResponse>> {
+    let video_service = VideoService::new(
+        state.db.clone(),
+        crate::services::TagService::new(state.db.clone()),
+        crate::services::PersonService::new(state.db.clone()),
+        crate::services::ThumbnailService::new(&state.config),
+        crate::services::ShoeboxService::new(state.db.clone()),
+    );
+
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    let count = video_service.purge_offline(request.video_ids).await?;
+    Ok(Json(PurgeOfflineResponse { count }))
+}
+
+async fn list_ambiguous_originals(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<crate::models::Video>>> {
+    let video_service = VideoService::new(
+        state.db.clone(),
+        crate::services::TagService::new(state.db.clone()),
+        crate::services::PersonService::new(state.db.clone()),
+        crate::services::ThumbnailService::new(&state.config),
+        crate::services::ShoeboxService::new(state.db.clone()),
+    );
+
+    let videos = video_service.find_ambiguous_originals().await?;
+    Ok(Json(videos))
+}
+
+async fn preview_path_rules(
+    State(state): State<AppState>,
+    request: Option<Json<PathRulesRequest>>,
+) -> Result<Json<PathRulesResult>> {
+    run_path_rules(state, request, true).await
+}
+
+async fn apply_path_rules(
+    State(state): State<AppState>,
+    request: Option<Json<PathRulesRequest>>,
+) -> Result<Json<PathRulesResult>> {
+    run_path_rules(state, request, false).await
+}
+
+// Apply the path rules of the requested sources to their existing videos, or only preview them
+async fn run_path_rules(
+    state: AppState,
+    request: Option<Json<PathRulesRequest>>,
+    dry_run: bool,
+) -> Result<Json<PathRulesResult>> {
+    let video_service = VideoService::new(
+        state.db.clone(),
+        crate::services::TagService::new(state.db.clone()),
+        crate::services::PersonService::new(state.db.clone()),
+        crate::services::ThumbnailService::new(&state.config),
+        crate::services::ShoeboxService::new(state.db.clone()),
+    );
+
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    let source_paths = match &request.sources {
+        Some(targets) if !targets.is_empty() => {
+            crate::services::ScannerService::resolve_scan_targets(&state.config.media.source_paths, targets)?
+        },
+        _ => state.config.media.source_paths.clone(),
+    };
+
+    let result = crate::services::PathRuleService::apply_to_existing(&video_service, &source_paths, dry_run).await?;
+    Ok(Json(result))
+}
+
+async fn set_original_file(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(dto): Json<SetOriginalFileDto>,
+) -> Result<Json<crate::models::Video>> {
+    let video_service = VideoService::new(
+        state.db.clone(),
+        crate::services::TagService::new(state.db.clone()),
+        crate::services::PersonService::new(state.db.clone()),
+        crate::services::ThumbnailService::new(&state.config),
+        crate::services::ShoeboxService::new(state.db.clone()),
+    );
+
+    // Originals are exported along with their video, so only files in the original path of the
+    // video's source may be chosen
+    let original_file_path = match &dto.original_file_path {
+        Some(original_file_path) => {
+            let video = video_service.find_by_id(&id).await?;
+            let original_root = state
+                .config
+                .media
+                .source_for(&video.file_path)
+                .and_then(|source| source.original_path.as_deref())
+                .ok_or_else(|| AppError::BadRequest(format!("The source of video {id} has no original path")))?;
+
+            let original_root = fs::canonicalize(original_root)
+                .map_err(|_| AppError::BadRequest(format!("Original path not found: {original_root}")))?;
+            let path = fs::canonicalize(original_file_path)
+                .map_err(|_| AppError::BadRequest(format!("Original file not found: {original_file_path}")))?;
+            if !path.starts_with(&original_root) {
+                return Err(AppError::BadRequest(format!(
+                    "Original file must be in {}: {original_file_path}",
+                    original_root.display()
+                )));
+            }
+            if !path.is_file() {
+                return Err(AppError::BadRequest(format!("Original file not found: {original_file_path}")));
+            }
+
+            Some(path.to_string_lossy().to_string())
+        },
+        None => None,
+    };
+
+    let video = video_service.set_original_override(&id, original_file_path.as_deref()).await?;
+    Ok(Json(video))
+}
+
+async fn clear_original_file(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<crate::models::Video>> {
+    let video_service = VideoService::new(
+        state.db.clone(),
+        crate::services::TagService::new(state.db.clone()),
+        crate::services::PersonService::new(state.db.clone()),
+        crate::services::ThumbnailService::new(&state.config),
+        crate::services::ShoeboxService::new(state.db.clone()),
+    );
+
+    let video = video_service.clear_original_override(&id).await?;
+    Ok(Json(video))
+}
+
+async fn get_thumbnail(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(params): Query<ThumbnailParams>,
+) -> Result<Response> {
+    let video_service = VideoService::new(
+        state.db.clone(),
+        crate::services::TagService::new(state.db.clone()),
+        crate::services::PersonService::new(state.db.clone()),
+        crate::services::ThumbnailService::new(&state.config),
+        crate::services::ShoeboxService::new(state.db.clone()),
+    );
+    let thumbnail_service = crate::services::ThumbnailService::new(&state.config);
+
+    let format = match &params.format {
+        Some(format) => Some(
+            crate::config::ThumbnailFormat::parse(format)
+                .ok_or_else(|| AppError::BadRequest(format!("Unknown thumbnail format: {format}")))?
+        ),
+        None => None,
+    };
+
+    let video = video_service.find_by_id(&id).await?;
+    let thumbnail_path = video
+        .thumbnail_path
+        .as_deref()
+        .ok_or_else(|| AppError::NotFound(format!("Video has no thumbnail: {id}")))?;
+    let source_path = thumbnail_service.resolve_path(thumbnail_path);
+    if !tokio::fs::try_exists(&source_path).await.unwrap_or(false) {
+        return Err(AppError::NotFound(format!("Thumbnail file not found: {}", source_path.display())));
+    }
+    let thumbnail_hash = video_service.thumbnail_hash(&video).await?;
+    let size = params.size.as_deref().unwrap_or(crate::services::ORIGINAL_THUMBNAIL_SIZE);
+
+    let variant = thumbnail_service
+        .variant(&id, &source_path, crate::services::thumbnail_version(&thumbnail_hash), size, format)
+        .await?;
+
+    // Versioned URLs never change content, others have to be revalidated against the ETag
+    let etag = format!("\"{}\"", variant.tag);
+    let cache_control = if params.v.as_deref() == Some(thumbnail_hash.as_str()) {
+        "public, max-age=31536000, immutable"
+    } else {
+        "public, no-cache"
+    };
+
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag || tag.trim() == "*"));
+    if not_modified {
+        let response = Response::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .header(header::ETAG, &etag)
+            .header(header::CACHE_CONTROL, cache_control)
+            .body(Body::empty())
+            .unwrap();
+        return Ok(response);
+    }
+
+    let contents = tokio::fs::read(&variant.path).await.map_err(|err| {
+        AppError::InternalServerError(format!("Failed to read thumbnail: {err}"))
+    })?;
+
+    let response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, variant.format.content_type())
+        .header(header::CONTENT_LENGTH, contents.len())
+        .header(header::ETAG, &etag)
+        .header(header::CACHE_CONTROL, cache_control)
+        .header("X-Content-Type-Options", "nosniff")
+        .body(Body::from(contents))
+        .unwrap();
+
+    Ok(response)
+}
+
+async fn set_thumbnail(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<SetThumbnailParams>,
+) -> Result<Json<crate::models::Video>> {
+    let video_service = VideoService::new(
+        state.db.clone(),
+        crate::services::TagService::new(state.db.clone()),
+        crate::services::PersonService::new(state.db.clone()),
+        crate::services::ThumbnailService::new(&state.config),
+        crate::services::ShoeboxService::new(state.db.clone()),
+    );
+
+    let time = crate::config::parse_seconds(&params.at)
+        .ok_or_else(|| AppError::BadRequest(format!("Invalid thumbnail time: {}", params.at)))?;
+
+    let video = video_service.set_thumbnail_time(&id, time).await?;
+    Ok(Json(video))
+}
+
+async fn clear_thumbnail(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<crate::models::Video>> {
+    let video_service = VideoService::new(
+        state.db.clone(),
+        crate::services::TagService::new(state.db.clone()),
+        crate::services::PersonService::new(state.db.clone()),
+        crate::services::ThumbnailService::new(&state.config),
+        crate::services::ShoeboxService::new(state.db.clone()),
+    );
+
+    let video = video_service.clear_thumbnail_time(&id).await?;
+    Ok(Json(video))
+}
+
+async fn get_sprite_track(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Response> {
+    let thumbnail_service = crate::services::ThumbnailService::new(&state.config);
+    serve_sprite_file(&state, &id, thumbnail_service.sprite_track_path(&id), "text/vtt").await
+}
+
+async fn get_sprite_sheet(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Response> {
+    let thumbnail_service = crate::services::ThumbnailService::new(&state.config);
+    serve_sprite_file(&state, &id, thumbnail_service.sprite_sheet_path(&id), "image/jpeg").await
+}
+
+async fn get_preview(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Response> {
+    let video_service = VideoService::new(
+        state.db.clone(),
+        crate::services::TagService::new(state.db.clone()),
+        crate::services::PersonService::new(state.db.clone()),
+        crate::services::ThumbnailService::new(&state.config),
+        crate::services::ShoeboxService::new(state.db.clone()),
+    );
+    let thumbnail_service = crate::services::ThumbnailService::new(&state.config);
+
+    // Only look for previews of videos that exist
+    video_service.find_by_id(&id).await?;
+
+    let contents = match tokio::fs::read(thumbnail_service.preview_path(&id)).await {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Err(AppError::NotFound(format!("No preview for video: {id}")));
+        },
+        Err(err) => {
+            return Err(AppError::InternalServerError(format!("Failed to read preview: {err}")));
+        }
+    };
+    let content_type = thumbnail_service.preview_format().content_type();
+    let file_size = contents.len();
+
+    // Previews are small enough to read whole, but Safari only plays video that is served in ranges
+    if let Some(range) = headers.get(header::RANGE) {
+        let range = range
+            .to_str()
+            .ok()
+            .and_then(|range| range.strip_prefix("bytes="))
+            .and_then(|range| range.split_once('-'))
+            .and_then(|(start, end)| match (start.parse::<usize>(), end.parse::<usize>()) {
+                (Ok(start), Ok(end)) => Some((start, end.min(file_size.saturating_sub(1)))),
+                (Ok(start), Err(_)) if end.is_empty() => Some((start, file_size.saturating_sub(1))),
+                // A suffix range such as "bytes=-500" asks for the last bytes
+                (Err(_), Ok(suffix)) if start.is_empty() => Some((file_size.saturating_sub(suffix), file_size.saturating_sub(1))),
+                _ => None,
+            })
+            .filter(|(start, end)| start <= end && *start < file_size);
+
+        let Some((start, end)) = range else {
+            let response = Response::builder()
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{file_size}"))
+                .body(Body::empty())
+                .unwrap();
+            return Ok(response);
+        };
+
+        let response = Response::builder()
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::CONTENT_LENGTH, end - start + 1)
+            .header(header::CONTENT_RANGE, format!("bytes {start}-{end}/{file_size}"))
+            .header(header::ACCEPT_RANGES, "bytes")
+            .header(header::CACHE_CONTROL, "no-cache")
+            .body(Body::from(contents[start..=end].to_vec()))
+            .unwrap();
+        return Ok(response);
+    }
+
+    let response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CONTENT_LENGTH, file_size)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(Body::from(contents))
+        .unwrap();
+
+    Ok(response)
+}
+
+async fn get_playback(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<PlaybackInfo>> {
+    let video = find_playable_video(&state, &id).await?;
+    let reasons = TranscodeService::transcode_reasons(&video);
+
+    let direct_play = reasons.is_empty();
+    let url = if direct_play {
+        format!("/api/videos/{id}/stream")
+    } else {
+        format!("/api/videos/{id}/hls/master.m3u8")
+    };
+
+    Ok(Json(PlaybackInfo { direct_play, url, reasons }))
+}
+
+async fn get_hls_master_playlist(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Response> {
+    let video = find_playable_video(&state, &id).await?;
+    Ok(hls_response("application/vnd.apple.mpegurl", TranscodeService::master_playlist(&video)))
+}
+
+async fn get_hls_media_playlist(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Response> {
+    let video = find_playable_video(&state, &id).await?;
+    let duration = video
+        .duration
+        .filter(|duration| *duration > 0)
+        .ok_or_else(|| AppError::BadRequest(format!("Duration of video {id} isn't known")))?;
+
+    Ok(hls_response("application/vnd.apple.mpegurl", TranscodeService::media_playlist(duration)))
+}
+
+async fn get_hls_segment(
+    State(state): State<AppState>,
+    Path((id, segment)): Path<(String, String)>,
+) -> Result<Response> {
+    let index = segment
+        .strip_suffix(".ts")
+        .and_then(|index| index.parse::<u32>().ok())
+        .ok_or_else(|| AppError::NotFound(format!("No such HLS file: {segment}")))?;
+
+    let video = find_playable_video(&state, &id).await?;
+    let segment_path = TranscodeService::new(&state.config, state.task_permits.clone()).segment(&video, index).await?;
+
+    let contents = tokio::fs::read(&segment_path).await.map_err(|err| {
+        AppError::InternalServerError(format!("Failed to read transcoded segment: {err}"))
+    })?;
+
+    Ok(hls_response("video/mp2t", contents))
+}
+
+// The video whose file is played for a video, which is its proxy for originals FFmpeg can't decode
+async fn find_playable_video(state: &AppState, id: &str) -> Result<Video> {
+    let video_service = VideoService::new(
+        state.db.clone(),
+        crate::services::TagService::new(state.db.clone()),
+        crate::services::PersonService::new(state.db.clone()),
+        crate::services::ThumbnailService::new(&state.config),
+        crate::services::ShoeboxService::new(state.db.clone()),
+    );
+
+    let video = video_service.find_by_id(id).await?;
+    if video.media_type != MEDIA_TYPE_VIDEO {
+        return Err(AppError::BadRequest(format!("Only videos can be played: {id}")));
+    }
+
+    video_service.playback_video(&video).await
+}
+
+fn hls_response(content_type: &str, body: impl Into<Body>) -> Response {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(body.into())
+        .unwrap()
+}
+
+// Serve a file generated for a video's seek previews
+async fn serve_sprite_file(state: &AppState, id: &str, path: PathBuf, content_type: &str) -> Result<Response> {
+    let video_service = VideoService::new(
+        state.db.clone(),
+        crate::services::TagService::new(state.db.clone()),
+        crate::services::PersonService::new(state.db.clone()),
+        crate::services::ThumbnailService::new(&state.config),
+        crate::services::ShoeboxService::new(state.db.clone()),
+    );
+
+    // Only look for files of videos that exist
+    video_service.find_by_id(id).await?;
+
+    let contents = match tokio::fs::read(&path).await {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Err(AppError::NotFound(format!("No seek previews for video: {id}")));
+        },
+        Err(err) => {
+            return Err(AppError::InternalServerError(format!("Failed to read seek previews: {err}")));
+        }
+    };
+
+    let response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CONTENT_LENGTH, contents.len())
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(Body::from(contents))
+        .unwrap();
+
+    Ok(response)
+}
+
 async fn stream_video(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -165,13 +664,13 @@ async fn stream_video(
         crate::services::ShoeboxService::new(state.db.clone()),
     );
 
-    // Get the video to find its file path
+    // Get the video to find its file path, which is its proxy's for originals browsers can't play
     let video = video_service.find_by_id(&id).await?;
+    let file_path = video_service.playback_video(&video).await?.file_path;
 
     // Check if the file exists
-    let path = PathBuf::from(&video.file_path);
+    let path = PathBuf::from(&file_path);
     if !path.exists() {
-        let file_path = &video.file_path;
         return Err(AppError::NotFound(format!("Video file not found: {file_path}")));
     }
 
@@ -0,0 +1,117 @@
+use sqlx::{Pool, Postgres};
+use tracing::{info, error};
+
+use crate::error::{AppError, Result};
+use crate::models::{DuplicateGroup, ResolveDuplicatesDto, ResolveDuplicatesResult, Video};
+use crate::services::video::VideoService;
+
+pub struct DuplicateService {
+    db: Pool<Postgres>,
+    video_service: VideoService,
+}
+
+impl DuplicateService {
+    pub fn new(db: Pool<Postgres>, video_service: VideoService) -> Self {
+        Self { db, video_service }
+    }
+
+    // Group videos that share the same content hash
+    pub async fn find_groups(&self) -> Result<Vec<DuplicateGroup>> {
+        let videos = sqlx::query_as::<_, Video>(
+            "SELECT * FROM videos
+             WHERE content_hash IN (
+                 SELECT content_hash FROM videos
+                 WHERE content_hash IS NOT NULL
+                 GROUP BY content_hash
+                 HAVING COUNT(*) > 1
+             )
+             ORDER BY content_hash, created_at"
+        )
+        .fetch_all(&self.db)
+        .await
+        .map_err(AppError::Database)?;
+
+        let mut groups: Vec<DuplicateGroup> = Vec::new();
+        for mut video in videos {
+            video.thumbnail_path = self.video_service.transform_thumbnail_path(video.thumbnail_path.clone());
+            let content_hash = video.content_hash.clone().unwrap_or_default();
+
+            match groups.last_mut() {
+                Some(group) if group.content_hash == content_hash => group.videos.push(video),
+                _ => groups.push(DuplicateGroup {
+                    content_hash,
+                    file_size: video.file_size,
+                    videos: vec![video],
+                }),
+            }
+        }
+
+        Ok(groups)
+    }
+
+    // Keep one video of a duplicate group, merging the others' tags, people and shoeboxes into it
+    pub async fn resolve(&self, dto: ResolveDuplicatesDto) -> Result<ResolveDuplicatesResult> {
+        let keep = self.video_service.find_by_id(&dto.keep_id).await?;
+        let content_hash = keep.content_hash.clone().ok_or_else(|| {
+            AppError::BadRequest(format!("Video {} has no content hash", keep.id))
+        })?;
+
+        let duplicate_ids: Vec<String> = dto.duplicate_ids
+            .into_iter()
+            .filter(|id| *id != keep.id)
+            .collect();
+
+        if duplicate_ids.is_empty() {
+            return Err(AppError::BadRequest("No duplicates to resolve".to_string()));
+        }
+
+        // Only videos with identical content can be merged
+        for id in &duplicate_ids {
+            let duplicate = self.video_service.find_by_id(id).await?;
+            if duplicate.content_hash.as_deref() != Some(content_hash.as_str()) {
+                return Err(AppError::BadRequest(format!(
+                    "Video {id} is not a duplicate of {}", keep.id
+                )));
+            }
+        }
+
+        let mut tx = self.db.begin().await.map_err(AppError::Database)?;
+        let now = chrono::Utc::now().naive_utc();
+
+        let relations = [
+            ("video_tags", "tag_id"),
+            ("video_people", "person_id"),
+            ("video_shoeboxes", "shoebox_id"),
+        ];
+
+        for (table, column) in relations {
+            sqlx::query(&format!(
+                "INSERT INTO {table} (video_id, {column}, created_at)
+                 SELECT DISTINCT $1, {column}, $2::timestamp FROM {table} WHERE video_id = ANY($3)
+                 ON CONFLICT DO NOTHING"
+            ))
+            .bind(&keep.id)
+            .bind(now)
+            .bind(&duplicate_ids)
+            .execute(&mut *tx)
+            .await
+            .map_err(AppError::Database)?;
+        }
+
+        tx.commit().await.map_err(AppError::Database)?;
+        info!("Merged {} duplicates into video {}", duplicate_ids.len(), keep.id);
+
+        let mut deleted_count = 0;
+        if dto.delete_duplicates {
+            for id in &duplicate_ids {
+                match self.video_service.delete(id).await {
+                    Ok(_) => deleted_count += 1,
+                    Err(e) => error!("Failed to delete duplicate video {}: {}", id, e),
+                }
+            }
+        }
+
+        let video = self.video_service.find_with_metadata(&keep.id).await?;
+        Ok(ResolveDuplicatesResult { video, deleted_count })
+    }
+}
@@ -116,9 +116,11 @@ impl ExportService {
                 json!({
                     "id": v.video.id,
                     "file_name": v.video.file_name,
+                    "media_type": v.video.media_type,
                     "title": v.video.title,
                     "description": v.video.description,
                     "created_date": v.video.created_date,
+                    "created_date_local": v.video.created_date_local,
                     "file_size": v.video.file_size,
                     "rating": v.video.rating,
                     "tags": v.tags,
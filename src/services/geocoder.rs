@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::config::{Config, GeocodeMode, MediaPathConfig};
+use crate::models::GpsPosition;
+
+/// Places further away than this from a position aren't suggested
+const MAX_DISTANCE_KM: f64 = 50.0;
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+const KM_PER_DEGREE: f64 = 111.32;
+
+/// A populated place from the GeoNames dataset
+#[derive(Clone, Debug)]
+struct Place {
+    name: String,
+    latitude: f64,
+    longitude: f64,
+    region: Option<String>,
+    country: String,
+}
+
+impl Place {
+    // Name such as "Honolulu, Hawaii, United States"
+    fn label(&self) -> String {
+        let mut parts = vec![self.name.as_str()];
+        if let Some(region) = self.region.as_deref().filter(|region| *region != self.name) {
+            parts.push(region);
+        }
+        if !self.country.is_empty() {
+            parts.push(&self.country);
+        }
+        parts.join(", ")
+    }
+}
+
+/// Offline reverse geocoding against a GeoNames cities file such as `cities15000.txt`.
+/// Region and country names are read from `admin1CodesASCII.txt` and `countryInfo.txt`
+/// next to it when they exist; otherwise places are labelled with their country code.
+pub struct GeocoderService {
+    places: Vec<Place>,
+    /// Indexes into `places` by whole degrees of latitude and longitude
+    grid: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl GeocoderService {
+    /// Load the dataset if any of the sources uses reverse geocoding
+    pub async fn load_for_sources(config: &Config, sources: &[MediaPathConfig]) -> Option<Arc<Self>> {
+        if sources.iter().all(|source| source.geocode == GeocodeMode::Off) {
+            return None;
+        }
+
+        let path = PathBuf::from(&config.media.geonames_path);
+        let result = tokio::task::spawn_blocking({
+            let path = path.clone();
+            move || Self::load(&path)
+        }).await;
+
+        match result {
+            Ok(Ok(geocoder)) => {
+                info!("Loaded {} places for reverse geocoding from {}", geocoder.places.len(), path.display());
+                Some(Arc::new(geocoder))
+            },
+            Ok(Err(e)) => {
+                warn!("Reverse geocoding is disabled, couldn't read {}: {}", path.display(), e);
+                None
+            },
+            Err(e) => {
+                warn!("Reverse geocoding is disabled, loading {} failed: {}", path.display(), e);
+                None
+            },
+        }
+    }
+
+    /// Warn when sources use reverse geocoding but the dataset they need isn't there
+    pub fn warn_if_missing(config: &Config) {
+        let sources: Vec<String> = config.media.source_paths
+            .iter()
+            .filter(|source| source.geocode != GeocodeMode::Off)
+            .map(|source| source.name.clone().unwrap_or_else(|| source.path.clone()))
+            .collect();
+        if sources.is_empty() {
+            return;
+        }
+
+        let path = Path::new(&config.media.geonames_path);
+        if !path.is_file() {
+            warn!(
+                "Reverse geocoding is enabled for {} but the GeoNames dataset {} is missing, so no places will be \
+                 suggested. Download cities15000.txt from https://download.geonames.org/export/dump/ to enable it.",
+                sources.join(", "),
+                path.display()
+            );
+        }
+    }
+
+    fn load(path: &Path) -> std::io::Result<Self> {
+        let directory = path.parent().unwrap_or(Path::new("."));
+        let regions = read_names(&directory.join("admin1CodesASCII.txt"), 1);
+        let countries = read_names(&directory.join("countryInfo.txt"), 4);
+
+        let contents = std::fs::read_to_string(path)?;
+        let mut geocoder = Self {
+            places: Vec::new(),
+            grid: HashMap::new(),
+        };
+
+        for line in contents.lines() {
+            // geonameid, name, asciiname, alternatenames, latitude, longitude, feature class,
+            // feature code, country code, cc2, admin1 code, ...
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 11 {
+                continue;
+            }
+            let (Ok(latitude), Ok(longitude)) = (fields[4].parse::<f64>(), fields[5].parse::<f64>()) else {
+                continue;
+            };
+
+            let country_code = fields[8];
+            let place = Place {
+                name: fields[1].to_string(),
+                latitude,
+                longitude,
+                region: regions.get(&format!("{}.{}", country_code, fields[10])).cloned(),
+                country: countries.get(country_code).cloned().unwrap_or_else(|| country_code.to_string()),
+            };
+
+            geocoder.grid.entry(grid_cell(latitude, longitude)).or_default().push(geocoder.places.len());
+            geocoder.places.push(place);
+        }
+
+        Ok(geocoder)
+    }
+
+    /// Name of the nearest place within `MAX_DISTANCE_KM` of a position
+    pub fn nearest(&self, position: &GpsPosition) -> Option<String> {
+        let (latitude_cell, longitude_cell) = grid_cell(position.latitude, position.longitude);
+
+        // Degrees of longitude get shorter towards the poles, so search more of them
+        let km_per_longitude_degree = KM_PER_DEGREE * position.latitude.to_radians().cos();
+        let longitude_cells = (MAX_DISTANCE_KM / km_per_longitude_degree.max(1.0)).ceil().min(180.0) as i32;
+
+        (latitude_cell - 1..=latitude_cell + 1)
+            .flat_map(|latitude| {
+                (longitude_cell - longitude_cells..=longitude_cell + longitude_cells)
+                    .map(move |longitude| (latitude, wrap_longitude(longitude)))
+            })
+            .filter_map(|cell| self.grid.get(&cell))
+            .flatten()
+            .map(|&index| {
+                let place = &self.places[index];
+                (place, distance_km(position.latitude, position.longitude, place.latitude, place.longitude))
+            })
+            .filter(|(_, distance)| *distance <= MAX_DISTANCE_KM)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(place, _)| place.label())
+    }
+}
+
+// Names keyed by the first column of a GeoNames tab-separated file, or nothing if it can't be read
+fn read_names(path: &Path, name_column: usize) -> HashMap<String, String> {
+    let Ok(contents) = std::fs::read_to_string(path) else {
+        return HashMap::new();
+    };
+
+    contents
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let name = fields.get(name_column).filter(|name| !name.is_empty())?;
+            Some((fields[0].to_string(), name.to_string()))
+        })
+        .collect()
+}
+
+fn grid_cell(latitude: f64, longitude: f64) -> (i32, i32) {
+    (latitude.floor() as i32, wrap_longitude(longitude.floor() as i32))
+}
+
+// Keep longitude cells in -180..180 so searches wrap around the antimeridian
+fn wrap_longitude(longitude: i32) -> i32 {
+    (longitude + 180).rem_euclid(360) - 180
+}
+
+// Great-circle distance using the haversine formula
+fn distance_km(latitude1: f64, longitude1: f64, latitude2: f64, longitude2: f64) -> f64 {
+    let delta_latitude = (latitude2 - latitude1).to_radians();
+    let delta_longitude = (longitude2 - longitude1).to_radians();
+    let a = (delta_latitude / 2.0).sin().powi(2)
+        + latitude1.to_radians().cos() * latitude2.to_radians().cos() * (delta_longitude / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A GeoNames line with the columns the geocoder reads
+    fn city(name: &str, latitude: f64, longitude: f64, country_code: &str, admin1_code: &str) -> String {
+        format!("1\t{name}\t{name}\t\t{latitude}\t{longitude}\tP\tPPL\t{country_code}\t\t{admin1_code}\t\t\t\t1000\t\t\tUTC\t2020-01-01")
+    }
+
+    #[test]
+    fn finds_the_nearest_city() {
+        let directory = std::env::temp_dir().join(format!("shoebox-geonames-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let cities = [
+            city("Honolulu", 21.30694, -157.85833, "US", "HI"),
+            city("Pearl City", 21.39734, -157.97516, "US", "HI"),
+            city("Suva", -18.14161, 178.44149, "FJ", "C"),
+            city("Taveuni", -16.85, 179.99, "FJ", "N"),
+            city("Longyearbyen", 78.2232, 15.6267, "SJ", "21"),
+            "not a city".to_string(),
+        ];
+        std::fs::write(directory.join("cities15000.txt"), cities.join("\n")).unwrap();
+        std::fs::write(directory.join("admin1CodesASCII.txt"), "US.HI\tHawaii\tHawaii\t5855797\n").unwrap();
+        std::fs::write(directory.join("countryInfo.txt"), "# ISO\tISO3\tISO-Numeric\tfips\tCountry\nUS\tUSA\t840\tUS\tUnited States\n").unwrap();
+
+        let geocoder = GeocoderService::load(&directory.join("cities15000.txt")).unwrap();
+        let position = |latitude, longitude| GpsPosition { latitude, longitude, altitude: None };
+        let cases = [
+            ("in the city", position(21.3, -157.85), Some("Honolulu, Hawaii, United States")),
+            ("closer to the smaller city", position(21.39, -157.96), Some("Pearl City, Hawaii, United States")),
+            ("region and country codes without names", position(-18.1, 178.4), Some("Suva, FJ")),
+            ("across the antimeridian", position(-16.85, -179.95), Some("Taveuni, FJ")),
+            ("near the pole", position(78.2, 16.5), Some("Longyearbyen, SJ")),
+            ("further than 50 km", position(20.0, -156.0), None),
+            ("nowhere near", position(0.0, 0.0), None),
+        ];
+
+        for (name, position, expected) in cases {
+            assert_eq!(geocoder.nearest(&position).as_deref(), expected, "{name}");
+        }
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -7,6 +7,18 @@ mod export;
 mod location;
 mod event;
 mod shoebox;
+mod watcher;
+mod duplicate;
+mod scan_job;
+mod scheduler;
+mod probe;
+mod native_probe;
+mod original_matcher;
+mod sidecar;
+mod path_rules;
+mod geocoder;
+mod thumbnail_maintenance;
+mod transcode;
 
 pub use scanner::*;
 pub use thumbnail::*;
@@ -17,11 +29,23 @@ pub use export::*;
 pub use location::*;
 pub use event::*;
 pub use shoebox::*;
+pub use watcher::*;
+pub use duplicate::*;
+pub use scan_job::*;
+pub use scheduler::*;
+pub use probe::*;
+pub use path_rules::*;
+pub use thumbnail_maintenance::*;
+pub use transcode::*;
+pub use geocoder::*;
 
+use serde::Serialize;
 use sqlx::{Pool, Postgres};
 use crate::config::Config;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Instant;
+use tokio::sync::{watch, RwLock, Semaphore};
+use tokio_util::sync::CancellationToken;
 
 /// Represents the current status of a scan operation
 #[derive(Clone, Debug)]
@@ -29,6 +53,36 @@ pub struct ScanStatus {
     pub in_progress: bool,
     pub new_videos_count: usize,
     pub updated_videos_count: usize,
+    pub skipped_videos_count: usize,
+    pub failed_videos_count: usize,
+    /// Media files discovered in the scanned sources
+    pub total_files: usize,
+    /// Files that have been processed so far, whatever the outcome
+    pub processed_files: usize,
+    /// File most recently picked up for processing
+    pub current_file: Option<String>,
+    pub started_at: Option<Instant>,
+    pub job_id: Option<String>,
+    /// Cancels the running scan
+    pub cancel_token: Option<CancellationToken>,
+    /// Streams snapshots of the status to live progress listeners
+    updates: watch::Sender<ScanStatusSnapshot>,
+}
+
+/// Point-in-time view of a scan's progress, as sent to clients
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ScanStatusSnapshot {
+    pub in_progress: bool,
+    pub new_videos_count: usize,
+    pub updated_videos_count: usize,
+    pub skipped_videos_count: usize,
+    pub failed_videos_count: usize,
+    pub total_files: usize,
+    pub processed_files: usize,
+    pub current_file: Option<String>,
+    /// Estimated seconds until the scan finishes, once files have been processed
+    pub eta_seconds: Option<u64>,
+    pub job_id: Option<String>,
 }
 
 impl Default for ScanStatus {
@@ -37,8 +91,61 @@ impl Default for ScanStatus {
             in_progress: false,
             new_videos_count: 0,
             updated_videos_count: 0,
+            skipped_videos_count: 0,
+            failed_videos_count: 0,
+            total_files: 0,
+            processed_files: 0,
+            current_file: None,
+            started_at: None,
+            job_id: None,
+            cancel_token: None,
+            updates: watch::Sender::new(ScanStatusSnapshot::default()),
+        }
+    }
+}
+
+impl ScanStatus {
+    pub fn snapshot(&self) -> ScanStatusSnapshot {
+        // Extrapolate from the average time per file so far
+        let eta_seconds = match self.started_at {
+            Some(started_at) if self.in_progress && self.processed_files > 0 => {
+                let remaining = self.total_files.saturating_sub(self.processed_files) as f64;
+                let per_file = started_at.elapsed().as_secs_f64() / self.processed_files as f64;
+                Some((remaining * per_file).ceil() as u64)
+            },
+            _ => None,
+        };
+
+        ScanStatusSnapshot {
+            in_progress: self.in_progress,
+            new_videos_count: self.new_videos_count,
+            updated_videos_count: self.updated_videos_count,
+            skipped_videos_count: self.skipped_videos_count,
+            failed_videos_count: self.failed_videos_count,
+            total_files: self.total_files,
+            processed_files: self.processed_files,
+            current_file: self.current_file.clone(),
+            eta_seconds,
+            job_id: self.job_id.clone(),
         }
     }
+
+    /// Send the current status to live progress listeners, if it changed
+    pub fn publish(&self) {
+        let snapshot = self.snapshot();
+        self.updates.send_if_modified(|current| {
+            if *current == snapshot {
+                return false;
+            }
+            *current = snapshot;
+            true
+        });
+    }
+
+    /// Listen for status changes
+    pub fn subscribe(&self) -> watch::Receiver<ScanStatusSnapshot> {
+        self.updates.subscribe()
+    }
 }
 
 #[derive(Clone)]
@@ -46,4 +153,7 @@ pub struct AppState {
     pub db: Pool<Postgres>,
     pub config: Config,
     pub scan_status: Arc<RwLock<ScanStatus>>,
+    /// Media processing tasks (scanning, ingesting, thumbnails, transcoding) running at once,
+    /// shared so that together they stay within `max_concurrent_tasks`
+    pub task_permits: Arc<Semaphore>,
 }
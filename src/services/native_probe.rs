@@ -0,0 +1,1038 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::error::{AppError, Result};
+use crate::models::CreationTime;
+use crate::services::probe::{parse_creation_time, MediaInfo, MediaProbe, ProbeFuture};
+
+/// Largest metadata section read into memory (the moov box or a Matroska Info/Tracks element)
+const MAX_METADATA_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Seconds between 1904-01-01 (ISO-BMFF epoch) and 1970-01-01
+const ISO_BMFF_EPOCH_OFFSET: i64 = 2_082_844_800;
+
+/// Seconds between 1970-01-01 and 2001-01-01 (Matroska epoch)
+const MATROSKA_EPOCH_OFFSET: i64 = 978_307_200;
+
+/// Reads metadata straight from ISO-BMFF (MP4/MOV) and Matroska (MKV/WebM) containers,
+/// without any external binaries
+pub struct NativeProbe;
+
+impl MediaProbe for NativeProbe {
+    fn name(&self) -> &'static str {
+        "native"
+    }
+
+    fn probe<'a>(&'a self, path: &'a Path) -> ProbeFuture<'a> {
+        let path = path.to_path_buf();
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || Self::probe_file(&path))
+                .await
+                .map_err(|e| AppError::InternalServerError(format!("Native probe task failed: {e}")))?
+        })
+    }
+}
+
+impl NativeProbe {
+    fn probe_file(path: &Path) -> Result<MediaInfo> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let file_size = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+
+        let mut magic = [0u8; 8];
+        if file_size < 8 {
+            return Err(AppError::BadRequest(format!("File too small to probe: {}", path.display())));
+        }
+        reader.read_exact(&mut magic)?;
+        reader.seek(SeekFrom::Start(0))?;
+
+        let info = if magic[..4] == [0x1A, 0x45, 0xDF, 0xA3] {
+            Self::probe_matroska(&mut reader, file_size)?
+        } else if Self::is_iso_bmff_box_type(&magic[4..8]) {
+            Self::probe_iso_bmff(&mut reader, file_size)?
+        } else {
+            None
+        };
+
+        let mut info = info.ok_or_else(|| AppError::BadRequest(format!("Unsupported container: {}", path.display())))?;
+
+        // Overall bit rate, as FFprobe reports it
+        info.bit_rate = info
+            .duration_ms
+            .filter(|duration| *duration > 0)
+            .map(|duration| (file_size as f64 * 8.0 * 1000.0 / duration as f64) as i64);
+
+        Ok(info)
+    }
+
+    // Top-level box types that can start an ISO-BMFF or QuickTime file
+    fn is_iso_bmff_box_type(box_type: &[u8]) -> bool {
+        matches!(box_type, b"ftyp" | b"moov" | b"mdat" | b"wide" | b"free" | b"skip" | b"pnot")
+    }
+
+    fn probe_iso_bmff<R: Read + Seek>(reader: &mut R, file_size: u64) -> io::Result<Option<MediaInfo>> {
+        let mut position: u64 = 0;
+
+        // Walk the top-level boxes until the movie box is found. Sizes come from the file,
+        // so parsing stops at any box that doesn't fit in it.
+        while position.checked_add(8).is_some_and(|end| end <= file_size) {
+            reader.seek(SeekFrom::Start(position))?;
+            let mut header = [0u8; 8];
+            reader.read_exact(&mut header)?;
+
+            let mut box_size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+            let mut header_size = 8;
+            if box_size == 1 {
+                if position + 16 > file_size {
+                    return Ok(None);
+                }
+                let mut large_size = [0u8; 8];
+                reader.read_exact(&mut large_size)?;
+                box_size = u64::from_be_bytes(large_size);
+                header_size = 16;
+            } else if box_size == 0 {
+                box_size = file_size - position;
+            }
+
+            if box_size < header_size {
+                return Ok(None);
+            }
+            let Some(box_end) = position.checked_add(box_size).filter(|end| *end <= file_size) else {
+                return Ok(None);
+            };
+
+            if &header[4..8] == b"moov" {
+                let payload_size = box_size - header_size;
+                if payload_size > MAX_METADATA_SIZE {
+                    return Ok(None);
+                }
+
+                let mut moov = vec![0u8; payload_size as usize];
+                reader.read_exact(&mut moov)?;
+                return Ok(Some(Self::parse_moov(&moov)));
+            }
+
+            position = box_end;
+        }
+
+        Ok(None)
+    }
+
+    fn parse_moov(moov: &[u8]) -> MediaInfo {
+        let mut info = MediaInfo::default();
+        let mut local_creation_time = None;
+
+        for (box_type, payload) in iso_boxes(moov) {
+            match box_type {
+                b"mvhd" => Self::parse_mvhd(payload, &mut info),
+                b"trak" => Self::parse_trak(payload, &mut info),
+                b"meta" => local_creation_time = Self::quicktime_creation_date(payload),
+                _ => {}
+            }
+        }
+
+        // The QuickTime creation date records the camera's UTC offset, which mvhd doesn't
+        if local_creation_time.is_some() {
+            info.creation_time = local_creation_time;
+        }
+
+        info
+    }
+
+    // The com.apple.quicktime.creationdate item of a QuickTime metadata box, if it has one
+    fn quicktime_creation_date(meta: &[u8]) -> Option<CreationTime> {
+        // QuickTime's meta box has no version and flags, the ISO one does
+        let meta = if meta.get(4..8) == Some(b"hdlr") { meta } else { meta.get(4..)? };
+
+        let keys = iso_boxes(meta).find(|(box_type, _)| *box_type == b"keys")?.1;
+        let ilst = iso_boxes(meta).find(|(box_type, _)| *box_type == b"ilst")?.1;
+
+        // Keys are numbered from 1 after the version, flags and entry count
+        let index = iso_boxes(keys.get(8..)?)
+            .position(|(_, key)| key == b"com.apple.quicktime.creationdate")? as u32
+            + 1;
+
+        // Items are boxes typed with their key's number, holding a data box with a type and locale before the value
+        let item = iso_boxes(ilst).find(|(box_type, _)| *box_type == index.to_be_bytes())?.1;
+        let data = iso_boxes(item).find(|(box_type, _)| *box_type == b"data")?.1;
+        let value = std::str::from_utf8(data.get(8..)?).ok()?;
+
+        parse_creation_time(value).filter(|time| time.offset_minutes.is_some())
+    }
+
+    fn parse_mvhd(payload: &[u8], info: &mut MediaInfo) {
+        let version = payload.first().copied().unwrap_or(0);
+        let (creation_time, timescale, duration) = if version == 1 {
+            (read_u64(payload, 4), read_u32(payload, 20).map(u64::from), read_u64(payload, 24))
+        } else {
+            (read_u32(payload, 4).map(u64::from), read_u32(payload, 12).map(u64::from), read_u32(payload, 16).map(u64::from))
+        };
+
+        if let (Some(timescale), Some(duration)) = (timescale, duration) {
+            if timescale > 0 && duration > 0 && duration != u32::MAX as u64 && duration != u64::MAX {
+                info.duration_ms = Some((duration as f64 * 1000.0 / timescale as f64) as i64);
+            }
+        }
+
+        // A zero creation time means the muxer didn't set one
+        if let Some(creation_time) = creation_time.filter(|time| *time > 0) {
+            info.creation_time = chrono::DateTime::from_timestamp(creation_time as i64 - ISO_BMFF_EPOCH_OFFSET, 0)
+                .map(CreationTime::from_utc);
+        }
+    }
+
+    // Read the first video and the first audio track
+    fn parse_trak(trak: &[u8], info: &mut MediaInfo) {
+        let mut dimensions = None;
+        let mut rotation = None;
+        let mut handler = None;
+        let mut timescale = None;
+        let mut sample_table = None;
+
+        for (box_type, payload) in iso_boxes(trak) {
+            match box_type {
+                b"tkhd" => {
+                    // The transformation matrix is followed by the width and height as 16.16 fixed point values
+                    let matrix = if payload.first() == Some(&1) { 52 } else { 40 };
+                    if let (Some(width), Some(height)) = (read_u32(payload, matrix + 36), read_u32(payload, matrix + 40)) {
+                        dimensions = Some(((width >> 16) as i32, (height >> 16) as i32));
+                    }
+                    if let (Some(a), Some(b)) = (read_u32(payload, matrix), read_u32(payload, matrix + 4)) {
+                        let degrees = (b as i32 as f64).atan2(a as i32 as f64).to_degrees();
+                        rotation = Some((degrees.round() as i32).rem_euclid(360));
+                    }
+                },
+                b"mdia" => {
+                    for (mdia_type, mdia_payload) in iso_boxes(payload) {
+                        match mdia_type {
+                            b"hdlr" => handler = mdia_payload.get(8..12),
+                            b"mdhd" => {
+                                let offset = if mdia_payload.first() == Some(&1) { 20 } else { 12 };
+                                timescale = read_u32(mdia_payload, offset);
+                            },
+                            b"minf" => {
+                                sample_table = iso_boxes(mdia_payload)
+                                    .find(|(box_type, _)| *box_type == b"stbl")
+                                    .map(|(_, stbl)| stbl);
+                            },
+                            _ => {}
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        let Some(stbl) = sample_table else {
+            return;
+        };
+        let sample_entry = iso_boxes(stbl)
+            .find(|(box_type, _)| *box_type == b"stsd")
+            .and_then(|(_, stsd)| stsd.get(8..));
+
+        match handler {
+            Some(b"vide") if info.video_codec.is_none() => {
+                info.video_codec = sample_entry.and_then(Self::video_codec_name);
+                if let Some((width, height)) = dimensions.filter(|(width, height)| *width > 0 && *height > 0) {
+                    info.width = Some(width);
+                    info.height = Some(height);
+                }
+                info.rotation = rotation;
+                info.frame_rate = timescale.and_then(|timescale| Self::frame_rate(stbl, timescale));
+                if let Some(entry) = sample_entry {
+                    Self::parse_visual_sample_entry(entry, info);
+                }
+            },
+            Some(b"soun") if info.audio_codec.is_none() => {
+                if let Some(entry) = sample_entry {
+                    Self::parse_audio_sample_entry(entry, info);
+                }
+            },
+            _ => {}
+        }
+    }
+
+    // Codec of a video sample entry, named the way FFprobe does
+    fn video_codec_name(entry: &[u8]) -> Option<String> {
+        let fourcc = entry.get(4..8)?;
+
+        let codec = match fourcc {
+            // Dolby Vision entries are named after the codec of their base layer
+            b"avc1" | b"avc3" | b"dva1" | b"dvav" => "h264",
+            b"hvc1" | b"hev1" | b"dvh1" | b"dvhe" => "hevc",
+            b"av01" | b"dav1" => "av1",
+            b"vp09" => "vp9",
+            b"vp08" => "vp8",
+            b"mp4v" => "mpeg4",
+            b"ap4h" | b"ap4x" | b"apch" | b"apcn" | b"apcs" | b"apco" => "prores",
+            b"mjpa" | b"mjpb" | b"jpeg" => "mjpeg",
+            _ => return Some(String::from_utf8_lossy(fourcc).trim().to_lowercase()),
+        };
+
+        Some(codec.to_string())
+    }
+
+    // Profile, bit depth and color of a visual sample entry, from the codec configuration and color boxes
+    // that follow its fixed fields
+    fn parse_visual_sample_entry(entry: &[u8], info: &mut MediaInfo) {
+        let size = read_u32(entry, 0).map_or(entry.len(), |size| (size as usize).min(entry.len()));
+        let Some(children) = entry.get(86..size) else {
+            return;
+        };
+
+        let mut transfer = None;
+        let mut dolby_vision = false;
+        for (box_type, payload) in iso_boxes(children) {
+            match box_type {
+                b"avcC" | b"hvcC" | b"av1C" => parse_codec_configuration(box_type, payload, info),
+                b"vpcC" => {
+                    // Version 1 layout, after the version and flags
+                    info.video_profile = payload.get(4).map(|profile| format!("Profile {profile}"));
+                    info.bit_depth = payload.get(6).map(|bits| (bits >> 4) as i32).filter(|bits| *bits > 0);
+                    if let (Some(primaries), Some(vp9_transfer)) = (payload.get(7), payload.get(8)) {
+                        info.color_primaries = info.color_primaries.take().or(color_primaries_name(*primaries as u64));
+                        transfer = transfer.or(Some(*vp9_transfer as u16));
+                    }
+                },
+                // nclx in MP4, nclc in QuickTime; ICC profiles say nothing about the transfer function
+                b"colr" if matches!(payload.get(..4), Some(b"nclx" | b"nclc")) => {
+                    info.color_primaries = read_u16(payload, 4).and_then(|primaries| color_primaries_name(primaries as u64));
+                    transfer = read_u16(payload, 6);
+                },
+                b"dvcC" | b"dvvC" | b"dvwC" => dolby_vision = true,
+                _ => {}
+            }
+        }
+
+        // PQ (HDR10, Dolby Vision) and HLG transfer functions, as FFprobe reports them
+        info.hdr = Some(dolby_vision || matches!(transfer, Some(16 | 18)));
+    }
+
+    // Average frame rate from the sample durations in stts
+    fn frame_rate(stbl: &[u8], timescale: u32) -> Option<f64> {
+        let stts = iso_boxes(stbl).find(|(box_type, _)| *box_type == b"stts")?.1;
+        let entry_count = read_u32(stts, 4)? as usize;
+
+        let (mut samples, mut duration) = (0u64, 0u64);
+        for entry in 0..entry_count {
+            let (Some(count), Some(delta)) = (read_u32(stts, 8 + entry * 8), read_u32(stts, 12 + entry * 8)) else {
+                break;
+            };
+            samples += count as u64;
+            duration += count as u64 * delta as u64;
+        }
+
+        (samples > 0 && duration > 0 && timescale > 0).then(|| samples as f64 * timescale as f64 / duration as f64)
+    }
+
+    // Codec, channel count and sample rate of an audio sample entry, in its MP4 or QuickTime layout
+    fn parse_audio_sample_entry(entry: &[u8], info: &mut MediaInfo) {
+        let Some(fourcc) = entry.get(4..8) else {
+            return;
+        };
+
+        let codec = match fourcc {
+            b"mp4a" => "aac".to_string(),
+            b"ac-3" => "ac3".to_string(),
+            b"ec-3" => "eac3".to_string(),
+            b"Opus" => "opus".to_string(),
+            b"alac" => "alac".to_string(),
+            b"fLaC" => "flac".to_string(),
+            b"lpcm" | b"sowt" | b"twos" | b"in24" | b"in32" | b"fl32" | b"fl64" => "pcm".to_string(),
+            _ => String::from_utf8_lossy(fourcc).trim().to_lowercase(),
+        };
+        info.audio_codec = Some(codec);
+
+        // QuickTime version 2 sound descriptions keep the real values in a later extension
+        if read_u16(entry, 16) == Some(2) {
+            info.audio_sample_rate = read_u64(entry, 40).map(|rate| f64::from_bits(rate) as i32);
+            info.audio_channels = read_u32(entry, 48).map(|channels| channels as i32);
+        } else {
+            info.audio_channels = read_u16(entry, 24).map(|channels| channels as i32);
+            info.audio_sample_rate = read_u32(entry, 32).map(|rate| (rate >> 16) as i32);
+        }
+
+        info.audio_channels = info.audio_channels.filter(|channels| *channels > 0);
+        info.audio_sample_rate = info.audio_sample_rate.filter(|rate| *rate > 0);
+    }
+
+    fn probe_matroska<R: Read + Seek>(reader: &mut R, file_size: u64) -> io::Result<Option<MediaInfo>> {
+        // Skip the EBML header
+        let Some((_, header_size)) = read_element_header(reader)? else {
+            return Ok(None);
+        };
+        let Some(header_size) = header_size.filter(|size| *size <= file_size) else {
+            return Ok(None);
+        };
+        reader.seek(SeekFrom::Current(header_size as i64))?;
+
+        // The segment may have an unknown size when written by a live recorder
+        let Some((segment_id, segment_size)) = read_element_header(reader)? else {
+            return Ok(None);
+        };
+        if segment_id != 0x1853_8067 {
+            return Ok(None);
+        }
+        let segment_start = reader.stream_position()?;
+        let segment_end = segment_size
+            .map(|size| (segment_start + size).min(file_size))
+            .unwrap_or(file_size);
+
+        let mut info = MediaInfo::default();
+        let mut found_info = false;
+        let mut found_tracks = false;
+
+        while !(found_info && found_tracks) && reader.stream_position()? < segment_end {
+            let Some((id, size)) = read_element_header(reader)? else {
+                break;
+            };
+
+            // Elements of unknown size (usually clusters) can't be skipped,
+            // and sizes that run past the segment come from a truncated or corrupt file
+            let Some(size) = size else {
+                break;
+            };
+            if reader.stream_position()?.checked_add(size).is_none_or(|end| end > segment_end) {
+                break;
+            }
+
+            match id {
+                0x1549_A966 | 0x1654_AE6B if size <= MAX_METADATA_SIZE => {
+                    let mut payload = vec![0u8; size as usize];
+                    reader.read_exact(&mut payload)?;
+
+                    if id == 0x1549_A966 {
+                        Self::parse_matroska_info(&payload, &mut info);
+                        found_info = true;
+                    } else {
+                        Self::parse_matroska_tracks(&payload, &mut info);
+                        found_tracks = true;
+                    }
+                },
+                _ => {
+                    reader.seek(SeekFrom::Current(size as i64))?;
+                }
+            }
+        }
+
+        Ok((found_info || found_tracks).then_some(info))
+    }
+
+    fn parse_matroska_info(payload: &[u8], info: &mut MediaInfo) {
+        let mut timecode_scale = 1_000_000u64;
+        let mut duration = None;
+
+        for (id, data) in ebml_elements(payload) {
+            match id {
+                0x2A_D7B1 => timecode_scale = read_ebml_uint(data).unwrap_or(timecode_scale),
+                0x4489 => duration = read_ebml_float(data),
+                0x4461 => {
+                    // Nanoseconds since 2001-01-01
+                    if let Some(nanos) = read_ebml_uint(data).map(|value| value as i64) {
+                        let seconds = nanos.div_euclid(1_000_000_000) + MATROSKA_EPOCH_OFFSET;
+                        info.creation_time = chrono::DateTime::from_timestamp(seconds, 0)
+                            .map(CreationTime::from_utc);
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        if let Some(duration) = duration.filter(|duration| *duration > 0.0) {
+            info.duration_ms = Some((duration * timecode_scale as f64 / 1_000_000.0) as i64);
+        }
+    }
+
+    // Read the first video and the first audio track
+    fn parse_matroska_tracks(payload: &[u8], info: &mut MediaInfo) {
+        for (id, entry) in ebml_elements(payload) {
+            if id != 0xAE {
+                continue;
+            }
+
+            let mut track_type = None;
+            let mut codec_id = None;
+            let mut default_duration = None;
+            let mut video = None;
+            let mut audio = None;
+            let mut codec_private = None;
+
+            for (entry_id, data) in ebml_elements(entry) {
+                match entry_id {
+                    0x83 => track_type = read_ebml_uint(data),
+                    0x86 => codec_id = Some(String::from_utf8_lossy(data).trim_end_matches('\0').to_string()),
+                    0x23_E383 => default_duration = read_ebml_uint(data),
+                    0xE0 => video = Some(data),
+                    0xE1 => audio = Some(data),
+                    0x63A2 => codec_private = Some(data),
+                    _ => {}
+                }
+            }
+
+            // Track type 1 is video, 2 is audio
+            match track_type {
+                Some(1) if info.video_codec.is_none() => {
+                    info.video_codec = codec_id.map(|codec_id| Self::matroska_codec_name(&codec_id));
+                    // CodecPrivate holds the same configuration record as the MP4 sample entry
+                    let configuration_box: Option<&[u8]> = match info.video_codec.as_deref() {
+                        Some("h264") => Some(b"avcC"),
+                        Some("hevc") => Some(b"hvcC"),
+                        Some("av1") => Some(b"av1C"),
+                        _ => None,
+                    };
+                    if let (Some(box_type), Some(config)) = (configuration_box, codec_private) {
+                        parse_codec_configuration(box_type, config, info);
+                    }
+                    // Nanoseconds per frame
+                    info.frame_rate = default_duration
+                        .filter(|duration| *duration > 0)
+                        .map(|duration| 1_000_000_000.0 / duration as f64);
+                    if let Some(video) = video {
+                        Self::parse_matroska_video(video, info);
+                    }
+                    // Videos whose Colour element doesn't name a transfer function are SDR
+                    info.hdr = info.hdr.or(Some(false));
+                },
+                Some(2) if info.audio_codec.is_none() => {
+                    info.audio_codec = codec_id.map(|codec_id| Self::matroska_codec_name(&codec_id));
+                    for (audio_id, audio_data) in ebml_elements(audio.unwrap_or_default()) {
+                        match audio_id {
+                            0xB5 => info.audio_sample_rate = read_ebml_float(audio_data).map(|rate| rate as i32),
+                            0x9F => info.audio_channels = read_ebml_uint(audio_data).map(|channels| channels as i32),
+                            _ => {}
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+
+    fn parse_matroska_video(video: &[u8], info: &mut MediaInfo) {
+        for (video_id, video_data) in ebml_elements(video) {
+            match video_id {
+                0xB0 => info.width = read_ebml_uint(video_data).map(|value| value as i32),
+                0xBA => info.height = read_ebml_uint(video_data).map(|value| value as i32),
+                0x55B0 => {
+                    // Colour, with code points from ISO/IEC 23091-4
+                    for (colour_id, colour_data) in ebml_elements(video_data) {
+                        let Some(value) = read_ebml_uint(colour_data) else {
+                            continue;
+                        };
+                        match colour_id {
+                            0x55B2 if value > 0 => info.bit_depth = Some(value as i32),
+                            0x55BA => info.hdr = Some(matches!(value, 16 | 18)),
+                            0x55BB => info.color_primaries = color_primaries_name(value),
+                            _ => {}
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+
+    // Map a Matroska codec ID to the codec names FFprobe reports
+    fn matroska_codec_name(codec_id: &str) -> String {
+        match codec_id {
+            "V_MPEG4/ISO/AVC" => "h264".to_string(),
+            "V_MPEGH/ISO/HEVC" => "hevc".to_string(),
+            "V_AV1" => "av1".to_string(),
+            "V_VP9" => "vp9".to_string(),
+            "V_VP8" => "vp8".to_string(),
+            "V_PRORES" => "prores".to_string(),
+            "V_MJPEG" => "mjpeg".to_string(),
+            "A_AAC" => "aac".to_string(),
+            "A_OPUS" => "opus".to_string(),
+            "A_VORBIS" => "vorbis".to_string(),
+            "A_AC3" => "ac3".to_string(),
+            "A_EAC3" => "eac3".to_string(),
+            "A_FLAC" => "flac".to_string(),
+            "A_PCM/INT/LIT" | "A_PCM/INT/BIG" | "A_PCM/FLOAT/IEEE" => "pcm".to_string(),
+            _ => codec_id
+                .trim_start_matches("V_")
+                .trim_start_matches("A_")
+                .split('/')
+                .next()
+                .unwrap_or_default()
+                .to_lowercase(),
+        }
+    }
+}
+
+// Profile and bit depth from an avcC, hvcC or av1C codec configuration record, named the way FFprobe does
+fn parse_codec_configuration(box_type: &[u8], config: &[u8], info: &mut MediaInfo) {
+    match box_type {
+        b"avcC" => {
+            let Some(&profile_idc) = config.get(1) else {
+                return;
+            };
+            let constrained = config.get(2).is_some_and(|constraints| constraints & 0x40 != 0);
+            info.video_profile = match profile_idc {
+                66 if constrained => Some("Constrained Baseline"),
+                66 => Some("Baseline"),
+                77 => Some("Main"),
+                88 => Some("Extended"),
+                100 => Some("High"),
+                110 => Some("High 10"),
+                122 => Some("High 4:2:2"),
+                244 => Some("High 4:4:4 Predictive"),
+                _ => None,
+            }.map(String::from);
+
+            // High profiles record the bit depth after the parameter sets, the others are always 8 bit
+            info.bit_depth = if matches!(profile_idc, 100 | 110 | 122 | 244) {
+                avc_high_bit_depth(config)
+            } else {
+                Some(8)
+            };
+        },
+        b"hvcC" => {
+            info.video_profile = match config.get(1).map(|profile| profile & 0x1F) {
+                Some(1) => Some("Main"),
+                Some(2) => Some("Main 10"),
+                Some(3) => Some("Main Still Picture"),
+                Some(4) => Some("Rext"),
+                _ => None,
+            }.map(String::from);
+            info.bit_depth = config.get(17).map(|bits| (bits & 0x07) as i32 + 8);
+        },
+        b"av1C" => {
+            info.video_profile = match config.get(1).map(|profile| profile >> 5) {
+                Some(0) => Some("Main"),
+                Some(1) => Some("High"),
+                Some(2) => Some("Professional"),
+                _ => None,
+            }.map(String::from);
+            info.bit_depth = config.get(2).map(|flags| match (flags & 0x40 != 0, flags & 0x20 != 0) {
+                (true, true) => 12,
+                (true, false) => 10,
+                _ => 8,
+            });
+        },
+        _ => {}
+    }
+}
+
+// Bit depth at the end of a High profile avcC, after the SPS and PPS lists
+fn avc_high_bit_depth(config: &[u8]) -> Option<i32> {
+    let mut position = 6;
+    let sps_count = config.get(5)? & 0x1F;
+    for _ in 0..sps_count {
+        position += 2 + read_u16(config, position)? as usize;
+    }
+    let pps_count = *config.get(position)?;
+    position += 1;
+    for _ in 0..pps_count {
+        position += 2 + read_u16(config, position)? as usize;
+    }
+
+    // Chroma format, then the luma bit depth minus 8
+    config.get(position + 1).map(|bits| (bits & 0x07) as i32 + 8)
+}
+
+// Color primaries code point from ISO/IEC 23091-4, named the way FFprobe does
+fn color_primaries_name(value: u64) -> Option<String> {
+    match value {
+        1 => Some("bt709"),
+        5 => Some("bt470bg"),
+        6 => Some("smpte170m"),
+        9 => Some("bt2020"),
+        11 => Some("smpte431"),
+        12 => Some("smpte432"),
+        _ => None,
+    }.map(String::from)
+}
+
+// Iterate over the child boxes of an ISO-BMFF box payload
+fn iso_boxes(data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    let mut position = 0usize;
+    std::iter::from_fn(move || {
+        let size = read_u32(data, position)? as usize;
+        let box_type = data.get(position + 4..position + 8)?;
+
+        let (header_size, box_size) = match size {
+            0 => (8, data.len() - position),
+            1 => (16, usize::try_from(read_u64(data, position + 8)?).ok()?),
+            _ => (8, size),
+        };
+
+        if box_size < header_size {
+            return None;
+        }
+
+        let payload = data.get(position + header_size..position.checked_add(box_size)?)?;
+        position += box_size;
+        Some((box_type, payload))
+    })
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4).map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    data.get(offset..offset + 8).map(|bytes| {
+        let mut buffer = [0u8; 8];
+        buffer.copy_from_slice(bytes);
+        u64::from_be_bytes(buffer)
+    })
+}
+
+// Decode an EBML variable-length integer, returning its value and length.
+// Element IDs keep their length marker bits, sizes don't.
+fn decode_vint(data: &[u8], keep_marker: bool) -> Option<(u64, usize, bool)> {
+    let first = *data.first()?;
+    let length = first.leading_zeros() as usize + 1;
+    if length > 8 || data.len() < length {
+        return None;
+    }
+
+    let mut value = if keep_marker { first as u64 } else { (first as u64) & ((1u64 << (8 - length)) - 1) };
+    for byte in &data[1..length] {
+        value = (value << 8) | *byte as u64;
+    }
+
+    // A size with all value bits set means "unknown"
+    let unknown = !keep_marker && value == (1u64 << (7 * length)) - 1;
+    Some((value, length, unknown))
+}
+
+// Read an element ID and size from a stream, with `None` as the size for unknown-size elements
+fn read_element_header<R: Read>(reader: &mut R) -> io::Result<Option<(u64, Option<u64>)>> {
+    let Some(id) = read_stream_vint(reader, true)? else {
+        return Ok(None);
+    };
+    let Some(size) = read_stream_vint(reader, false)? else {
+        return Ok(None);
+    };
+
+    Ok(Some((id.0, (!size.1).then_some(size.0))))
+}
+
+fn read_stream_vint<R: Read>(reader: &mut R, keep_marker: bool) -> io::Result<Option<(u64, bool)>> {
+    let mut buffer = [0u8; 8];
+    if reader.read(&mut buffer[..1])? == 0 {
+        return Ok(None);
+    }
+
+    let length = buffer[0].leading_zeros() as usize + 1;
+    if length > 8 {
+        return Ok(None);
+    }
+    reader.read_exact(&mut buffer[1..length])?;
+
+    Ok(decode_vint(&buffer[..length], keep_marker).map(|(value, _, unknown)| (value, unknown)))
+}
+
+// Iterate over the child elements of an in-memory EBML element
+fn ebml_elements(data: &[u8]) -> impl Iterator<Item = (u64, &[u8])> {
+    let mut position = 0usize;
+    std::iter::from_fn(move || {
+        let (id, id_length, _) = decode_vint(data.get(position..)?, true)?;
+        let (size, size_length, unknown) = decode_vint(data.get(position + id_length..)?, false)?;
+        if unknown {
+            return None;
+        }
+
+        let start = position + id_length + size_length;
+        let end = start.checked_add(usize::try_from(size).ok()?)?;
+        let payload = data.get(start..end)?;
+        position = end;
+        Some((id, payload))
+    })
+}
+
+fn read_ebml_uint(data: &[u8]) -> Option<u64> {
+    if data.is_empty() || data.len() > 8 {
+        return None;
+    }
+    Some(data.iter().fold(0u64, |value, byte| (value << 8) | *byte as u64))
+}
+
+fn read_ebml_float(data: &[u8]) -> Option<f64> {
+    match data.len() {
+        4 => Some(f32::from_be_bytes([data[0], data[1], data[2], data[3]]) as f64),
+        8 => read_u64(data, 0).map(f64::from_bits),
+        _ => None,
+    }
+}
+
+/// ISO-BMFF files built in memory for tests of the probes
+#[cfg(test)]
+pub(crate) mod fixtures {
+    pub fn iso_box(box_type: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        [((payload.len() + 8) as u32).to_be_bytes().to_vec(), box_type.to_vec(), payload.to_vec()].concat()
+    }
+
+    pub fn be32(values: &[u32]) -> Vec<u8> {
+        values.iter().flat_map(|value| value.to_be_bytes()).collect()
+    }
+
+    // A visual sample entry with zeroed fixed fields followed by child boxes
+    pub fn video_sample_entry(fourcc: &[u8; 4], children: &[u8]) -> Vec<u8> {
+        iso_box(fourcc, &[vec![0; 78], children.to_vec()].concat())
+    }
+
+    // An 8 bit High profile avcC without parameter sets
+    pub fn avc_config() -> Vec<u8> {
+        iso_box(b"avcC", &[1, 100, 0, 40, 0xFF, 0xE0, 0, 0xFD, 0xF8, 0xF8, 0])
+    }
+
+    // A Main 10 hvcC without parameter arrays
+    pub fn hevc_config() -> Vec<u8> {
+        iso_box(b"hvcC", &[1, 0x02, 0x20, 0, 0, 0, 0x90, 0, 0, 0, 0, 0, 153, 0xF0, 0, 0xFC, 0xFD, 0xFA, 0xFA, 0, 0, 0x0F, 0])
+    }
+
+    // An nclx colr box with color primaries, transfer characteristics and matrix coefficients
+    pub fn nclx(primaries: u16, transfer: u16, matrix: u16) -> Vec<u8> {
+        let values: Vec<u8> = [primaries, transfer, matrix].iter().flat_map(|value| value.to_be_bytes()).collect();
+        iso_box(b"colr", &[b"nclx".to_vec(), values, vec![0]].concat())
+    }
+
+    // A 10 second 1920x1080 8 bit H.264 movie at 29.97 fps, with no media data and optionally more boxes in moov
+    pub fn minimal_mp4(extra_moov: &[u8]) -> Vec<u8> {
+        mp4_with_sample_entry(&video_sample_entry(b"avc1", &avc_config()), extra_moov)
+    }
+
+    // The movie of `minimal_mp4` with another video sample entry
+    pub fn mp4_with_sample_entry(sample_entry: &[u8], extra_moov: &[u8]) -> Vec<u8> {
+        let mut mvhd = be32(&[0, 3_700_000_000, 3_700_000_000, 1000, 10_000]);
+        mvhd.resize(100, 0);
+
+        let mut tkhd = vec![0u8; 40];
+        tkhd.extend(be32(&[0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000, 1920 << 16, 1080 << 16]));
+
+        let hdlr = [be32(&[0, 0]), b"vide".to_vec(), vec![0; 12]].concat();
+        let mdhd = be32(&[0, 0, 0, 30_000, 300_300, 0]);
+        let stsd = [be32(&[0, 1]), sample_entry.to_vec()].concat();
+        let stts = be32(&[0, 1, 300, 1001]);
+        let stbl = iso_box(b"stbl", &[iso_box(b"stsd", &stsd), iso_box(b"stts", &stts)].concat());
+        let mdia = iso_box(b"mdia", &[iso_box(b"mdhd", &mdhd), iso_box(b"hdlr", &hdlr), iso_box(b"minf", &stbl)].concat());
+        let trak = iso_box(b"trak", &[iso_box(b"tkhd", &tkhd), mdia].concat());
+        let moov = iso_box(b"moov", &[iso_box(b"mvhd", &mvhd), trak, extra_moov.to_vec()].concat());
+
+        [iso_box(b"ftyp", b"isom\0\0\0\0isom"), moov].concat()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::{avc_config, hevc_config, iso_box, minimal_mp4, mp4_with_sample_entry, nclx, video_sample_entry};
+    use super::*;
+    use std::io::Cursor;
+
+    // Name, data and what's expected of it
+    type Case<T> = (&'static str, Vec<u8>, T);
+
+    // Value, length and whether the size is unknown
+    type Vint = (u64, usize, bool);
+
+    // Codec, profile, bit depth, HDR and color primaries
+    type VideoFormat = (&'static str, Option<&'static str>, Option<i32>, Option<bool>, Option<&'static str>);
+
+    // An EBML element with an 8 byte size
+    fn element(id: &[u8], size: u64, payload: &[u8]) -> Vec<u8> {
+        let mut size_bytes = size.to_be_bytes();
+        size_bytes[0] = 0x01;
+        [id, &size_bytes, payload].concat()
+    }
+
+    fn sized_element(id: &[u8], payload: &[u8]) -> Vec<u8> {
+        element(id, payload.len() as u64, payload)
+    }
+
+    const EBML_ID: [u8; 4] = [0x1A, 0x45, 0xDF, 0xA3];
+    const SEGMENT_ID: [u8; 4] = [0x18, 0x53, 0x80, 0x67];
+    const INFO_ID: [u8; 4] = [0x15, 0x49, 0xA9, 0x66];
+
+    fn ebml_header() -> Vec<u8> {
+        sized_element(&EBML_ID, &sized_element(&[0x42, 0x86], &[1]))
+    }
+
+    // Info payload of a 10 second file in milliseconds
+    fn info_payload() -> Vec<u8> {
+        [sized_element(&[0x2A, 0xD7, 0xB1], &[0x0F, 0x42, 0x40]), sized_element(&[0x44, 0x89], &10_000f64.to_be_bytes())].concat()
+    }
+
+    fn tracks() -> Vec<u8> {
+        let track = sized_element(&[0xAE], &[sized_element(&[0x83], &[1]), sized_element(&[0x86], b"V_MPEG4/ISO/AVC")].concat());
+        sized_element(&[0x16, 0x54, 0xAE, 0x6B], &track)
+    }
+
+    #[test]
+    fn iso_bmff_box_sizes() {
+        let mp4 = minimal_mp4(&[]);
+        let ftyp = iso_box(b"ftyp", b"isom\0\0\0\0isom");
+        let moov_to_end = {
+            let mut moov = minimal_mp4(&[])[ftyp.len()..].to_vec();
+            moov[..4].copy_from_slice(&[0; 4]);
+            [ftyp.clone(), moov].concat()
+        };
+        let oversized_moov = {
+            let mut mp4 = minimal_mp4(&[]);
+            mp4[ftyp.len()..ftyp.len() + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+            mp4
+        };
+        let oversized_large_size = [
+            ftyp.clone(),
+            [&1u32.to_be_bytes()[..], b"free", &u64::MAX.to_be_bytes()].concat(),
+            minimal_mp4(&[])[ftyp.len()..].to_vec(),
+        ]
+        .concat();
+
+        let cases: Vec<Case<Option<Option<i64>>>> = vec![
+            ("complete", mp4.clone(), Some(Some(10_000))),
+            ("moov running to the end of the file", moov_to_end, Some(Some(10_000))),
+            ("truncated moov", mp4[..mp4.len() - 10].to_vec(), None),
+            ("moov larger than the file", oversized_moov, None),
+            ("large size past the end of the file", oversized_large_size, None),
+            ("cut off large size", [ftyp.clone(), [&1u32.to_be_bytes()[..], b"free", &[0, 0]].concat()].concat(), None),
+            ("box smaller than its header", [ftyp.clone(), [&4u32.to_be_bytes()[..], b"free"].concat()].concat(), None),
+            ("no moov", ftyp, None),
+        ];
+
+        for (name, data, expected) in cases {
+            let info = NativeProbe::probe_iso_bmff(&mut Cursor::new(&data), data.len() as u64).unwrap();
+            assert_eq!(info.map(|info| info.duration_ms), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn reads_video_formats_from_sample_entries() {
+        let hlg = iso_box(b"colr", &[b"nclc".to_vec(), vec![0, 9, 0, 18, 0, 9]].concat());
+        let dolby_vision = iso_box(b"dvcC", &[1, 0, 0x10, 0x35, 0, 0, 0, 0]);
+        let baseline = iso_box(b"avcC", &[1, 66, 0xC0, 30, 0xFF, 0xE0, 0]);
+        let high_10 = iso_box(b"avcC", &[1, 110, 0, 51, 0xFF, 0xE1, 0, 2, 0x67, 0x6E, 1, 0, 1, 0x68, 0xFD, 0xFA, 0xFA, 0]);
+
+        let cases: Vec<Case<VideoFormat>> = vec![
+            ("8 bit H.264", video_sample_entry(b"avc1", &avc_config()), ("h264", Some("High"), Some(8), Some(false), None)),
+            ("Baseline H.264", video_sample_entry(b"avc1", &baseline), ("h264", Some("Constrained Baseline"), Some(8), Some(false), None)),
+            ("10 bit H.264 with parameter sets", video_sample_entry(b"avc1", &high_10), ("h264", Some("High 10"), Some(10), Some(false), None)),
+            (
+                "HDR10 HEVC",
+                video_sample_entry(b"hvc1", &[hevc_config(), nclx(9, 16, 9)].concat()),
+                ("hevc", Some("Main 10"), Some(10), Some(true), Some("bt2020")),
+            ),
+            ("HLG HEVC from QuickTime", video_sample_entry(b"hvc1", &[hevc_config(), hlg].concat()), ("hevc", Some("Main 10"), Some(10), Some(true), Some("bt2020"))),
+            (
+                "Dolby Vision with SDR transfer",
+                video_sample_entry(b"dvh1", &[hevc_config(), dolby_vision, nclx(9, 1, 9)].concat()),
+                ("hevc", Some("Main 10"), Some(10), Some(true), Some("bt2020")),
+            ),
+            ("SDR HEVC", video_sample_entry(b"hev1", &[hevc_config(), nclx(1, 1, 1)].concat()), ("hevc", Some("Main 10"), Some(10), Some(false), Some("bt709"))),
+            ("10 bit AV1", video_sample_entry(b"av01", &iso_box(b"av1C", &[0x81, 0x08, 0x4C, 0])), ("av1", Some("Main"), Some(10), Some(false), None)),
+            (
+                "HDR VP9",
+                video_sample_entry(b"vp09", &iso_box(b"vpcC", &[1, 0, 0, 0, 2, 31, 0xA2, 9, 16, 9, 0, 0])),
+                ("vp9", Some("Profile 2"), Some(10), Some(true), Some("bt2020")),
+            ),
+            ("ProRes", video_sample_entry(b"apch", &[]), ("prores", None, None, Some(false), None)),
+            ("cut off entry", iso_box(b"avc1", &[0; 20]), ("h264", None, None, None, None)),
+        ];
+
+        for (name, sample_entry, (codec, profile, bit_depth, hdr, primaries)) in cases {
+            let data = mp4_with_sample_entry(&sample_entry, &[]);
+            let info = NativeProbe::probe_iso_bmff(&mut Cursor::new(&data), data.len() as u64).unwrap().unwrap();
+            assert_eq!(info.video_codec.as_deref(), Some(codec), "{name}");
+            assert_eq!(info.video_profile.as_deref(), profile, "{name}");
+            assert_eq!(info.bit_depth, bit_depth, "{name}");
+            assert_eq!(info.hdr, hdr, "{name}");
+            assert_eq!(info.color_primaries.as_deref(), primaries, "{name}");
+        }
+    }
+
+    #[test]
+    fn reads_matroska_codec_private() {
+        let hevc = hevc_config();
+        let track = sized_element(
+            &[0xAE],
+            &[sized_element(&[0x83], &[1]), sized_element(&[0x86], b"V_MPEGH/ISO/HEVC"), sized_element(&[0x63, 0xA2], &hevc[8..])].concat(),
+        );
+        let body = [sized_element(&INFO_ID, &info_payload()), sized_element(&[0x16, 0x54, 0xAE, 0x6B], &track)].concat();
+        let mkv = [ebml_header(), sized_element(&SEGMENT_ID, &body)].concat();
+
+        let info = NativeProbe::probe_matroska(&mut Cursor::new(&mkv), mkv.len() as u64).unwrap().unwrap();
+        assert_eq!(info.video_profile.as_deref(), Some("Main 10"));
+        assert_eq!(info.bit_depth, Some(10));
+        assert_eq!(info.hdr, Some(false));
+    }
+
+    #[test]
+    fn iso_boxes_stop_at_boxes_past_their_parent() {
+        let cases: Vec<Case<Vec<&[u8]>>> = vec![
+            ("two boxes", [iso_box(b"mvhd", &[0; 4]), iso_box(b"trak", &[])].concat(), vec![b"mvhd", b"trak"]),
+            ("oversized second box", [iso_box(b"mvhd", &[]), [&100u32.to_be_bytes()[..], b"trak"].concat()].concat(), vec![b"mvhd"]),
+            ("oversized large size", [&1u32.to_be_bytes()[..], b"trak", &u64::MAX.to_be_bytes()].concat(), vec![]),
+            ("box smaller than its header", [&3u32.to_be_bytes()[..], b"trak"].concat(), vec![]),
+            ("size running to the end", [&0u32.to_be_bytes()[..], b"trak", &[1, 2]].concat(), vec![b"trak"]),
+            ("cut off header", vec![0, 0, 0, 8, b't'], vec![]),
+        ];
+
+        for (name, data, expected) in cases {
+            let box_types: Vec<&[u8]> = iso_boxes(&data).map(|(box_type, _)| box_type).collect();
+            assert_eq!(box_types, expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn matroska_element_sizes() {
+        let body = [sized_element(&INFO_ID, &info_payload()), tracks()].concat();
+        let mkv = [ebml_header(), sized_element(&SEGMENT_ID, &body)].concat();
+        let unknown_size = [&SEGMENT_ID[..], &[0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF], &body].concat();
+        let oversized_info = [element(&INFO_ID, 0x00FF_FFFF_FFFF_FFFE, &info_payload()), tracks()].concat();
+
+        let cases: Vec<Case<Option<Option<i64>>>> = vec![
+            ("complete", mkv.clone(), Some(Some(10_000))),
+            ("unknown segment size", [ebml_header(), unknown_size].concat(), Some(Some(10_000))),
+            ("segment larger than the file", [ebml_header(), element(&SEGMENT_ID, u64::from(u32::MAX), &body)].concat(), Some(Some(10_000))),
+            ("truncated info", mkv[..ebml_header().len() + 12 + 20].to_vec(), None),
+            ("info larger than the file", [ebml_header(), sized_element(&SEGMENT_ID, &oversized_info)].concat(), None),
+            ("header larger than the file", [element(&EBML_ID, u64::from(u32::MAX), &[]), sized_element(&SEGMENT_ID, &body)].concat(), None),
+            ("no segment", ebml_header(), None),
+        ];
+
+        for (name, data, expected) in cases {
+            let info = NativeProbe::probe_matroska(&mut Cursor::new(&data), data.len() as u64).unwrap();
+            assert_eq!(info.map(|info| info.duration_ms), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn ebml_variable_length_integers() {
+        let cases: Vec<(&[u8], bool, Option<Vint>)> = vec![
+            (&[0x81], false, Some((1, 1, false))),
+            (&[0x40, 0x02], false, Some((2, 2, false))),
+            (&[0xFF], false, Some((127, 1, true))),
+            (&[0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF], false, Some(((1 << 56) - 1, 8, true))),
+            (&[0x1A, 0x45, 0xDF, 0xA3], true, Some((0x1A45_DFA3, 4, false))),
+            (&[0x40], false, None),
+            (&[0x00, 0x01], false, None),
+            (&[], false, None),
+        ];
+
+        for (data, keep_marker, expected) in cases {
+            assert_eq!(decode_vint(data, keep_marker), expected, "{data:02X?}");
+        }
+    }
+
+    #[test]
+    fn ebml_elements_stop_at_elements_past_their_parent() {
+        let cases: Vec<Case<Vec<u64>>> = vec![
+            ("two elements", [vec![0x83, 0x81, 1], vec![0x86, 0x82, b'V', b'_']].concat(), vec![0x83, 0x86]),
+            ("oversized second element", [vec![0x83, 0x81, 1], vec![0x86, 0x88, b'V']].concat(), vec![0x83]),
+            ("unknown size", vec![0x83, 0xFF, 1], vec![]),
+            ("cut off size", vec![0x2A, 0xD7, 0xB1], vec![]),
+        ];
+
+        for (name, data, expected) in cases {
+            let ids: Vec<u64> = ebml_elements(&data).map(|(id, _)| id).collect();
+            assert_eq!(ids, expected, "{name}");
+        }
+    }
+}
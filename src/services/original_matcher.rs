@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::services::probe::ProbeService;
+use crate::services::scanner::ScannerService;
+
+/// Largest difference in duration, in milliseconds, for an original to still match a proxy
+const DURATION_TOLERANCE_MS: i64 = 1000;
+
+/// An original file found in a source's original directory
+#[derive(Clone, Debug)]
+pub struct OriginalFile {
+    pub path: PathBuf,
+    /// Original directory the file was found in
+    pub root: PathBuf,
+    /// Directory of the file relative to `root`
+    pub relative_dir: PathBuf,
+    pub size: u64,
+}
+
+/// Original files keyed by file stem. Several files can share a stem, e.g. the same
+/// clip name on different camera cards.
+#[derive(Debug, Default)]
+pub struct OriginalIndex {
+    files: HashMap<String, Vec<OriginalFile>>,
+}
+
+impl OriginalIndex {
+    pub fn insert(&mut self, root: &Path, path: &Path) {
+        let Some(stem) = path.file_stem().map(|stem| stem.to_string_lossy().to_string()) else {
+            return;
+        };
+
+        let relative_dir = path
+            .parent()
+            .and_then(|parent| parent.strip_prefix(root).ok())
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let size = std::fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0);
+
+        let entries = self.files.entry(stem).or_default();
+        entries.retain(|existing| existing.path != path);
+        entries.push(OriginalFile {
+            path: path.to_path_buf(),
+            root: root.to_path_buf(),
+            relative_dir,
+            size,
+        });
+    }
+
+    pub fn merge(&mut self, other: OriginalIndex) {
+        for (stem, files) in other.files {
+            let entries = self.files.entry(stem).or_default();
+            for file in files {
+                if !entries.iter().any(|existing| existing.path == file.path) {
+                    entries.push(file);
+                }
+            }
+        }
+    }
+
+    /// Original files with the given stem under an original directory
+    pub fn candidates(&self, stem: &str, root: &Path) -> Vec<OriginalFile> {
+        self.files
+            .get(stem)
+            .map(|files| files.iter().filter(|file| file.root == root).cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Outcome of looking for a video's original file
+#[derive(Debug)]
+pub enum OriginalMatch {
+    Matched(OriginalFile),
+    Ambiguous(Vec<OriginalFile>),
+    NotFound,
+}
+
+/// What is known about the proxy a match is made for
+pub struct MatchTarget<'a> {
+    pub file_path: &'a Path,
+    /// Source directory the proxy was found in
+    pub media_root: &'a Path,
+    pub size: u64,
+    pub duration_ms: Option<i64>,
+    pub exif_data: Option<&'a serde_json::Value>,
+}
+
+pub struct OriginalMatcher;
+
+impl OriginalMatcher {
+    /// Pick the original for a proxy among the files sharing its stem, narrowing the candidates by
+    /// relative directory, identical size, reel and timecode, and duration until one is left
+    pub async fn find(candidates: Vec<OriginalFile>, target: &MatchTarget<'_>, probe_service: &ProbeService) -> OriginalMatch {
+        let mut candidates = candidates;
+        if candidates.len() <= 1 {
+            return candidates.pop().map(OriginalMatch::Matched).unwrap_or(OriginalMatch::NotFound);
+        }
+
+        info!("{} original candidates for {}, narrowing down", candidates.len(), target.file_path.display());
+
+        // Prefer originals laid out in the same folders as the proxy, e.g. Day1/CardA/
+        let mut proxy_dir = target.file_path
+            .parent()
+            .and_then(|parent| parent.strip_prefix(target.media_root).ok())
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        // Cameras often write proxies to a "Proxy" folder next to the originals
+        if proxy_dir
+            .file_name()
+            .is_some_and(|name| matches!(name.to_string_lossy().to_lowercase().as_str(), "proxy" | "proxies"))
+        {
+            proxy_dir.pop();
+        }
+        let best_score = candidates
+            .iter()
+            .map(|candidate| common_suffix_len(&proxy_dir, &candidate.relative_dir))
+            .max()
+            .unwrap_or(0);
+        if best_score > 0 {
+            candidates.retain(|candidate| common_suffix_len(&proxy_dir, &candidate.relative_dir) == best_score);
+        }
+        if candidates.len() == 1 {
+            return OriginalMatch::Matched(candidates.remove(0));
+        }
+
+        // An original with the same size is a straight copy of the proxy
+        candidates = narrow(candidates, |candidate| candidate.size == target.size);
+        if candidates.len() == 1 {
+            return OriginalMatch::Matched(candidates.remove(0));
+        }
+
+        // Compare reel names and start timecodes recorded by the camera
+        let (proxy_reel, proxy_timecode) = target.exif_data.map(reel_and_timecode).unwrap_or_default();
+        if proxy_reel.is_some() || proxy_timecode.is_some() {
+            let mut flags = Vec::new();
+            for candidate in &candidates {
+                let exif_data = ScannerService::get_exif_data(&candidate.path.to_string_lossy()).await;
+                let (reel, timecode) = exif_data.as_ref().map(reel_and_timecode).unwrap_or_default();
+
+                let compared = (proxy_reel.is_some() && reel.is_some()) || (proxy_timecode.is_some() && timecode.is_some());
+                let conflicts = (proxy_reel.is_some() && reel.is_some() && proxy_reel != reel)
+                    || (proxy_timecode.is_some() && timecode.is_some() && proxy_timecode != timecode);
+                flags.push(compared && !conflicts);
+            }
+
+            candidates = keep_flagged(candidates, flags);
+            if candidates.len() == 1 {
+                return OriginalMatch::Matched(candidates.remove(0));
+            }
+        }
+
+        // Finally compare durations
+        if let Some(duration_ms) = target.duration_ms {
+            let mut flags = Vec::new();
+            for candidate in &candidates {
+                let duration = probe_service.probe(&candidate.path).await.ok().and_then(|info| info.duration_ms);
+                flags.push(duration.is_some_and(|duration| (duration - duration_ms).abs() <= DURATION_TOLERANCE_MS));
+            }
+
+            candidates = keep_flagged(candidates, flags);
+            if candidates.len() == 1 {
+                return OriginalMatch::Matched(candidates.remove(0));
+            }
+        }
+
+        OriginalMatch::Ambiguous(candidates)
+    }
+}
+
+// Keep the candidates matching a predicate, unless none do
+fn narrow(candidates: Vec<OriginalFile>, predicate: impl Fn(&OriginalFile) -> bool) -> Vec<OriginalFile> {
+    let flags = candidates.iter().map(predicate).collect();
+    keep_flagged(candidates, flags)
+}
+
+// Keep the candidates whose flag is set, unless no flag is set
+fn keep_flagged(candidates: Vec<OriginalFile>, flags: Vec<bool>) -> Vec<OriginalFile> {
+    if !flags.contains(&true) {
+        return candidates;
+    }
+
+    candidates
+        .into_iter()
+        .zip(flags)
+        .filter_map(|(candidate, keep)| keep.then_some(candidate))
+        .collect()
+}
+
+// Number of trailing directory names two relative paths have in common
+fn common_suffix_len(a: &Path, b: &Path) -> usize {
+    a.components()
+        .rev()
+        .zip(b.components().rev())
+        .take_while(|(a, b)| a == b)
+        .count()
+}
+
+// Find the reel name and start timecode in exiftool output, whichever group they are in
+fn reel_and_timecode(exif_data: &serde_json::Value) -> (Option<String>, Option<String>) {
+    let mut reel = None;
+    let mut timecode = None;
+
+    if let Some(fields) = exif_data.as_object() {
+        for (key, value) in fields {
+            let tag = key.rsplit(':').next().unwrap_or(key).to_lowercase();
+            let value = match value {
+                serde_json::Value::String(value) => value.trim().to_string(),
+                serde_json::Value::Number(value) => value.to_string(),
+                _ => continue,
+            };
+            if value.is_empty() {
+                continue;
+            }
+
+            match tag.as_str() {
+                "reelname" | "reel" | "tapename" => {
+                    reel.get_or_insert(value);
+                },
+                "timecode" | "starttimecode" | "timecodestart" => {
+                    timecode.get_or_insert(value);
+                },
+                _ => {}
+            }
+        }
+    }
+
+    (reel, timecode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::native_probe::fixtures::minimal_mp4;
+
+    type Case = (&'static str, Vec<OriginalFile>, Option<i64>, (&'static str, Vec<PathBuf>));
+
+    // Whether a match was found, with the files it settled on
+    fn outcome(original_match: OriginalMatch) -> (&'static str, Vec<PathBuf>) {
+        match original_match {
+            OriginalMatch::Matched(file) => ("matched", vec![file.path]),
+            OriginalMatch::Ambiguous(files) => ("ambiguous", files.into_iter().map(|file| file.path).collect()),
+            OriginalMatch::NotFound => ("not found", Vec::new()),
+        }
+    }
+
+    #[tokio::test]
+    async fn narrows_candidates_down_to_one() {
+        let root = std::env::temp_dir().join(format!("shoebox-originals-{}", uuid::Uuid::new_v4()));
+        let original = |relative_dir: &str, size: u64| OriginalFile {
+            path: root.join(relative_dir).join("A001.braw"),
+            root: root.clone(),
+            relative_dir: PathBuf::from(relative_dir),
+            size,
+        };
+
+        // Only these originals are 10 second movies that can be probed; the others are missing or unreadable
+        let movies = [("Day2/CardA", minimal_mp4(&[])), ("Day2/CardB", minimal_mp4(&[])), ("Day3/CardB", minimal_mp4(&[]))];
+        for (relative_dir, contents) in movies.into_iter().chain([("Day4/CardB", b"not a movie".to_vec())]) {
+            std::fs::create_dir_all(root.join(relative_dir)).unwrap();
+            std::fs::write(root.join(relative_dir).join("A001.braw"), contents).unwrap();
+        }
+
+        let cases: Vec<Case> = vec![
+            ("no candidates", vec![], None, ("not found", vec![])),
+            ("single candidate", vec![original("Day9", 1)], None, ("matched", vec![original("Day9", 1).path])),
+            (
+                "same folders as the proxy",
+                vec![original("Day2/CardA", 1), original("Day1/CardA", 1), original("Day1/CardB", 1)],
+                None,
+                ("matched", vec![original("Day1/CardA", 1).path]),
+            ),
+            (
+                "same card folder on different days, then same size",
+                vec![original("Day2/CardA", 1), original("Day3/CardA", 500), original("Day1/CardB", 500)],
+                None,
+                ("matched", vec![original("Day3/CardA", 500).path]),
+            ),
+            (
+                "no folders in common, then same size",
+                vec![original("Day2/CardB", 500), original("Day3/CardC", 1)],
+                None,
+                ("matched", vec![original("Day2/CardB", 500).path]),
+            ),
+            (
+                "same size, then duration",
+                vec![original("Day3/CardA", 500), original("Day2/CardA", 500)],
+                Some(10_400),
+                ("matched", vec![original("Day2/CardA", 500).path]),
+            ),
+            (
+                "tied without a duration",
+                vec![original("Day2/CardA", 1), original("Day3/CardA", 1)],
+                None,
+                ("ambiguous", vec![original("Day2/CardA", 1).path, original("Day3/CardA", 1).path]),
+            ),
+            (
+                "tied on duration",
+                vec![original("Day2/CardB", 1), original("Day3/CardB", 1)],
+                Some(10_000),
+                ("ambiguous", vec![original("Day2/CardB", 1).path, original("Day3/CardB", 1).path]),
+            ),
+            (
+                "no duration within the tolerance",
+                vec![original("Day2/CardB", 1), original("Day4/CardB", 1)],
+                Some(12_000),
+                ("ambiguous", vec![original("Day2/CardB", 1).path, original("Day4/CardB", 1).path]),
+            ),
+        ];
+
+        let media_root = Path::new("/media");
+        let file_path = Path::new("/media/Day1/CardA/Proxy/A001.mov");
+        let probe_service = ProbeService::default();
+
+        for (name, candidates, duration_ms, expected) in cases {
+            let target = MatchTarget { file_path, media_root, size: 500, duration_ms, exif_data: None };
+            let found = OriginalMatcher::find(candidates, &target, &probe_service).await;
+            assert_eq!(outcome(found), expected, "{name}");
+        }
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,179 @@
+use std::path::Path;
+use tracing::info;
+
+use crate::config::MediaPathConfig;
+use crate::error::Result;
+use crate::models::{PathRuleAssignment, PathRulesResult, UpdateVideoDto};
+use crate::services::video::VideoService;
+use crate::utils::PathTemplate;
+
+/// Metadata derived from where a file sits in its source
+#[derive(Clone, Debug, Default)]
+pub struct PathMetadata {
+    pub event: Option<String>,
+    pub location: Option<String>,
+    pub people: Vec<String>,
+    pub tags: Vec<String>,
+    pub shoeboxes: Vec<String>,
+}
+
+pub struct PathRuleService;
+
+impl PathRuleService {
+    /// Metadata from the first of the source's path rules that matches the file.
+    /// Captures named event, location, person, tag and shoebox are assigned; other names only match.
+    pub fn derive(path_config: &MediaPathConfig, file_path: &Path) -> Option<PathMetadata> {
+        let relative_path = file_path.strip_prefix(path_config.root()).ok()?;
+
+        let captures = path_config
+            .path_rules
+            .iter()
+            .filter_map(|rule| PathTemplate::parse(rule))
+            .find_map(|template| template.captures(relative_path))?;
+
+        let mut metadata = PathMetadata::default();
+        for (name, value) in captures {
+            let value = value.trim().to_string();
+            if value.is_empty() {
+                continue;
+            }
+
+            match name.as_str() {
+                "event" => metadata.event = Some(value),
+                "location" => metadata.location = Some(value),
+                "person" => push_unique(&mut metadata.people, value),
+                "tag" => push_unique(&mut metadata.tags, value),
+                "shoebox" => push_unique(&mut metadata.shoeboxes, value),
+                _ => {},
+            }
+        }
+
+        Some(metadata)
+    }
+
+    /// Work out what the path rules assign to the existing videos of some sources, and store it
+    /// unless this is a dry run. Rules fill in missing events and locations and add people, tags
+    /// and shoeboxes; they never overwrite or remove anything.
+    pub async fn apply_to_existing(
+        video_service: &VideoService,
+        source_paths: &[MediaPathConfig],
+        dry_run: bool,
+    ) -> Result<PathRulesResult> {
+        let mut matched = 0;
+        let mut assignments = Vec::new();
+
+        for path_config in source_paths.iter().filter(|source| !source.path_rules.is_empty()) {
+            for video in video_service.find_by_path_prefix(&path_config.path).await? {
+                let Some(derived) = Self::derive(path_config, Path::new(&video.file_path)) else {
+                    continue;
+                };
+                matched += 1;
+
+                let current = video_service.find_with_metadata(&video.id).await?;
+                let assignment = PathRuleAssignment {
+                    video_id: video.id.clone(),
+                    file_path: video.file_path.clone(),
+                    event: derived.event.filter(|_| current.video.event.is_none()),
+                    location: derived.location.filter(|_| current.video.location.is_none()),
+                    add_people: missing(derived.people, &current.people),
+                    add_tags: missing(derived.tags, &current.tags),
+                    add_shoeboxes: missing(derived.shoeboxes, &current.shoeboxes),
+                };
+
+                let changed = assignment.event.is_some()
+                    || assignment.location.is_some()
+                    || !assignment.add_people.is_empty()
+                    || !assignment.add_tags.is_empty()
+                    || !assignment.add_shoeboxes.is_empty();
+                if !changed {
+                    continue;
+                }
+
+                if !dry_run {
+                    let with_added = |current: &[String], added: &[String]| {
+                        (!added.is_empty()).then(|| current.iter().chain(added).cloned().collect())
+                    };
+
+                    video_service.update(&video.id, UpdateVideoDto {
+                        title: None,
+                        description: None,
+                        rating: None,
+                        location: assignment.location.clone(),
+                        event: assignment.event.clone(),
+                        tags: with_added(&current.tags, &assignment.add_tags),
+                        people: with_added(&current.people, &assignment.add_people),
+                        shoeboxes: with_added(&current.shoeboxes, &assignment.add_shoeboxes),
+                    }).await?;
+                }
+
+                assignments.push(assignment);
+            }
+        }
+
+        info!(
+            "Path rules matched {} videos and {} {}",
+            matched,
+            if dry_run { "would change" } else { "changed" },
+            assignments.len()
+        );
+
+        Ok(PathRulesResult {
+            dry_run,
+            matched,
+            assignments,
+        })
+    }
+}
+
+fn push_unique(values: &mut Vec<String>, value: String) {
+    if !values.contains(&value) {
+        values.push(value);
+    }
+}
+
+// Values not already present
+fn missing(values: Vec<String>, existing: &[String]) -> Vec<String> {
+    values.into_iter().filter(|value| !existing.contains(value)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(path_rules: &[&str]) -> MediaPathConfig {
+        MediaPathConfig {
+            path: "/media".to_string(),
+            path_rules: path_rules.iter().map(|rule| rule.to_string()).collect(),
+            ..MediaPathConfig::default()
+        }
+    }
+
+    #[test]
+    fn derives_metadata_from_the_first_matching_rule() {
+        let source = source(&["{year}/{event}/{person}/*", "{year}/{event}/*", "{shoebox}/**/*"]);
+
+        let derived = PathRuleService::derive(&source, Path::new("/media/2024/Wedding/Anna/clip.mov")).unwrap();
+        assert_eq!(derived.event.as_deref(), Some("Wedding"));
+        assert_eq!(derived.people, ["Anna"]);
+
+        // Files with a missing person fall through to the next rule
+        let derived = PathRuleService::derive(&source, Path::new("/media/2024/Wedding/clip.mov")).unwrap();
+        assert_eq!(derived.event.as_deref(), Some("Wedding"));
+        assert!(derived.people.is_empty());
+
+        let derived = PathRuleService::derive(&source, Path::new("/media/clip.mov"));
+        assert!(derived.is_none());
+
+        assert!(PathRuleService::derive(&source, Path::new("/elsewhere/2024/Wedding/clip.mov")).is_none());
+    }
+
+    #[test]
+    fn skips_blank_and_unknown_captures() {
+        let source = source(&["{year}/{event}/{tag}-{tag}/*"]);
+
+        let derived = PathRuleService::derive(&source, Path::new("/media/2024/ /beach-beach/clip.mov")).unwrap();
+        assert_eq!(derived.event, None);
+        assert_eq!(derived.tags, ["beach"]);
+        assert_eq!(derived.location, None);
+    }
+}
@@ -0,0 +1,459 @@
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use tokio::process::Command;
+use tracing::{info, warn};
+
+use crate::error::{AppError, Result};
+use crate::models::CreationTime;
+use crate::services::native_probe::NativeProbe;
+
+/// Container-level metadata read from a media file
+#[derive(Clone, Debug, Default)]
+pub struct MediaInfo {
+    pub duration_ms: Option<i64>,
+    pub creation_time: Option<CreationTime>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub video_codec: Option<String>,
+    pub video_profile: Option<String>,
+    /// Frames per second
+    pub frame_rate: Option<f64>,
+    /// Overall bit rate of the file in bits per second
+    pub bit_rate: Option<i64>,
+    /// Bits per color channel
+    pub bit_depth: Option<i32>,
+    pub color_primaries: Option<String>,
+    /// Whether the video uses an HDR transfer function (PQ or HLG)
+    pub hdr: Option<bool>,
+    /// Clockwise rotation in degrees needed to show the video upright
+    pub rotation: Option<i32>,
+    pub audio_codec: Option<String>,
+    pub audio_channels: Option<i32>,
+    pub audio_sample_rate: Option<i32>,
+}
+
+impl MediaInfo {
+    // Whether every field a scan needs has been found. Bit depth and HDR are needed by the search
+    // filters; profile, audio and color primaries are best-effort, as not every file has them.
+    fn is_complete(&self) -> bool {
+        self.duration_ms.is_some()
+            && self.creation_time.is_some()
+            && self.width.is_some()
+            && self.height.is_some()
+            && self.video_codec.is_some()
+            && self.bit_depth.is_some()
+            && self.hdr.is_some()
+    }
+
+    // Fill in the fields that are still missing from another probe's result
+    fn merge(&mut self, other: MediaInfo) {
+        self.duration_ms = self.duration_ms.or(other.duration_ms);
+        // The native probe's creation time is plain UTC; keep the camera's UTC offset when another probe found it
+        self.creation_time = match (self.creation_time, other.creation_time) {
+            (Some(current), Some(other)) if current.offset_minutes.is_none() && other.offset_minutes.is_some() => Some(other),
+            (current, other) => current.or(other),
+        };
+        self.width = self.width.or(other.width);
+        self.height = self.height.or(other.height);
+        self.video_codec = self.video_codec.take().or(other.video_codec);
+        self.video_profile = self.video_profile.take().or(other.video_profile);
+        self.frame_rate = self.frame_rate.or(other.frame_rate);
+        self.bit_rate = self.bit_rate.or(other.bit_rate);
+        self.bit_depth = self.bit_depth.or(other.bit_depth);
+        self.color_primaries = self.color_primaries.take().or(other.color_primaries);
+        self.hdr = self.hdr.or(other.hdr);
+        self.rotation = self.rotation.or(other.rotation);
+        self.audio_codec = self.audio_codec.take().or(other.audio_codec);
+        self.audio_channels = self.audio_channels.or(other.audio_channels);
+        self.audio_sample_rate = self.audio_sample_rate.or(other.audio_sample_rate);
+    }
+}
+
+pub type ProbeFuture<'a> = Pin<Box<dyn Future<Output = Result<MediaInfo>> + Send + 'a>>;
+
+/// A way of reading metadata from media files
+pub trait MediaProbe: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn probe<'a>(&'a self, path: &'a Path) -> ProbeFuture<'a>;
+}
+
+/// Reads metadata by running the system's FFprobe binary
+pub struct FfprobeProbe;
+
+impl MediaProbe for FfprobeProbe {
+    fn name(&self) -> &'static str {
+        "ffprobe"
+    }
+
+    fn probe<'a>(&'a self, path: &'a Path) -> ProbeFuture<'a> {
+        Box::pin(async move {
+            let output = Command::new("ffprobe")
+                .arg("-v")
+                .arg("error")
+                .arg("-show_format")
+                .arg("-show_streams")
+                .arg("-of")
+                .arg("json")
+                .arg(path)
+                .kill_on_drop(true)
+                .output()
+                .await?;
+
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                return Err(AppError::FFmpeg(format!("FFprobe error: {}", stderr.trim())));
+            }
+
+            let json: serde_json::Value = serde_json::from_slice(&output.stdout)
+                .map_err(|e| AppError::FFmpeg(format!("Failed to parse FFprobe output: {e}")))?;
+
+            Ok(Self::parse_output(&json))
+        })
+    }
+}
+
+impl FfprobeProbe {
+    fn parse_output(json: &serde_json::Value) -> MediaInfo {
+        let mut info = MediaInfo::default();
+        let format = &json["format"];
+
+        info.duration_ms = format["duration"]
+            .as_str()
+            .and_then(|duration| duration.parse::<f64>().ok())
+            .map(|seconds| (seconds * 1000.0) as i64);
+
+        // Different video formats store the creation date in different tags
+        let possible_tags = [
+            "creation_time",                    // Common in MP4
+            "com.apple.quicktime.creationdate", // Common in MOV
+            "date",                             // Generic date tag
+            "com.apple.quicktime.createdate",   // Alternative MOV tag
+        ];
+        let creation_times: Vec<CreationTime> = possible_tags
+            .iter()
+            .filter_map(|tag| format["tags"][tag].as_str())
+            .filter_map(parse_creation_time)
+            .collect();
+
+        // Prefer a tag that says which UTC offset the camera was in
+        info.creation_time = creation_times
+            .iter()
+            .find(|time| time.offset_minutes.is_some())
+            .or(creation_times.first())
+            .copied();
+
+        info.bit_rate = format["bit_rate"].as_str().and_then(|bit_rate| bit_rate.parse().ok());
+
+        let streams = json["streams"].as_array().map(Vec::as_slice).unwrap_or_default();
+
+        if let Some(stream) = streams.iter().find(|stream| stream["codec_type"] == "video") {
+            info.width = stream["width"].as_i64().map(|width| width as i32);
+            info.height = stream["height"].as_i64().map(|height| height as i32);
+            info.video_codec = stream["codec_name"].as_str().map(|codec| codec.to_string());
+            info.video_profile = stream["profile"].as_str().map(|profile| profile.to_string());
+            info.frame_rate = [&stream["avg_frame_rate"], &stream["r_frame_rate"]]
+                .iter()
+                .filter_map(|rate| rate.as_str())
+                .find_map(parse_frame_rate);
+            info.bit_depth = stream["bits_per_raw_sample"]
+                .as_str()
+                .and_then(|bits| bits.parse().ok())
+                .or_else(|| stream["pix_fmt"].as_str().map(pixel_format_bit_depth));
+            info.color_primaries = stream["color_primaries"]
+                .as_str()
+                .filter(|primaries| *primaries != "unknown")
+                .map(|primaries| primaries.to_string());
+
+            // PQ (HDR10, Dolby Vision) and HLG transfer functions
+            let side_data = stream["side_data_list"].as_array().map(Vec::as_slice).unwrap_or_default();
+            let dolby_vision = side_data.iter().any(|data| data["side_data_type"] == "DOVI configuration record");
+            info.hdr = Some(dolby_vision || matches!(stream["color_transfer"].as_str(), Some("smpte2084" | "arib-std-b67")));
+
+            // The display matrix gives the counterclockwise rotation, the older rotate tag the clockwise one
+            info.rotation = side_data
+                .iter()
+                .find_map(|data| data["rotation"].as_f64())
+                .map(|rotation| -rotation)
+                .or_else(|| stream["tags"]["rotate"].as_str().and_then(|rotate| rotate.parse().ok()))
+                .map(|rotation: f64| (rotation.round() as i32).rem_euclid(360));
+        }
+
+        if let Some(stream) = streams.iter().find(|stream| stream["codec_type"] == "audio") {
+            info.audio_codec = stream["codec_name"].as_str().map(|codec| codec.to_string());
+            info.audio_channels = stream["channels"].as_i64().map(|channels| channels as i32);
+            info.audio_sample_rate = stream["sample_rate"].as_str().and_then(|rate| rate.parse().ok());
+        }
+
+        info
+    }
+}
+
+// Parse a frame rate such as "30000/1001"
+fn parse_frame_rate(value: &str) -> Option<f64> {
+    let (numerator, denominator) = value.split_once('/')?;
+    let numerator: f64 = numerator.parse().ok()?;
+    let denominator: f64 = denominator.parse().ok()?;
+    (numerator > 0.0 && denominator > 0.0)
+        .then(|| numerator / denominator)
+        .filter(|rate| rate.is_finite())
+}
+
+// Bits per channel of a pixel format such as "yuv420p10le" or "p010le"; formats without a number are 8 bit
+fn pixel_format_bit_depth(pixel_format: &str) -> i32 {
+    let format = pixel_format.trim_end_matches("le").trim_end_matches("be");
+    let digits_start = format.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    let (prefix, digits) = format.split_at(digits_start);
+
+    match digits.parse::<i32>() {
+        Ok(bits) if prefix.ends_with('p') && (9..=16).contains(&bits) => bits,
+        _ => 8,
+    }
+}
+
+/// Parse a creation date in one of the formats found in media metadata. Dates without a UTC
+/// offset are taken as UTC, which keeps their wall-clock time.
+pub fn parse_creation_time(value: &str) -> Option<CreationTime> {
+    let value = value.trim();
+
+    // Formats that record the offset the camera was in
+    let offset_formats = [
+        "%Y-%m-%dT%H:%M:%S%.f%z",  // ISO 8601 with timezone
+        "%Y:%m:%d %H:%M:%S%.f%z",  // EXIF date with timezone (QuickTime, or EXIF with OffsetTime)
+    ];
+
+    for format in offset_formats.iter() {
+        if let Ok(dt) = chrono::DateTime::parse_from_str(value, format) {
+            return Some(CreationTime::from_local(dt));
+        }
+    }
+
+    let date_formats = [
+        "%Y-%m-%dT%H:%M:%S%.fZ",  // ISO 8601 in UTC
+        "%Y-%m-%dT%H:%M:%S%.f",   // ISO 8601 without timezone
+        "%Y-%m-%d %H:%M:%S%.f",   // Simple date time format
+        "%Y:%m:%d %H:%M:%S%.f",   // EXIF date format (common in photos/videos)
+    ];
+
+    for format in date_formats.iter() {
+        if let Ok(dt) = chrono::NaiveDateTime::parse_from_str(value, format) {
+            return Some(CreationTime::from_utc(dt.and_utc()));
+        }
+    }
+
+    // A bare date has no time to parse as a NaiveDateTime
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return date.and_hms_opt(0, 0, 0).map(|dt| CreationTime::from_utc(dt.and_utc()));
+    }
+
+    warn!("Couldn't parse creation date value: {}", value);
+    None
+}
+
+/// Runs media probes in order, using the fast native probe first and FFprobe to fill in the gaps
+pub struct ProbeService {
+    probes: Vec<Box<dyn MediaProbe>>,
+}
+
+impl Default for ProbeService {
+    fn default() -> Self {
+        Self::new(vec![Box::new(NativeProbe), Box::new(FfprobeProbe)])
+    }
+}
+
+impl ProbeService {
+    pub fn new(probes: Vec<Box<dyn MediaProbe>>) -> Self {
+        Self { probes }
+    }
+
+    /// Probe a file with each probe until all metadata is known.
+    /// Fails only if no probe could read the file.
+    pub async fn probe(&self, path: &Path) -> Result<MediaInfo> {
+        let mut info = MediaInfo::default();
+        let mut succeeded = false;
+        let mut last_error = None;
+
+        for probe in &self.probes {
+            match probe.probe(path).await {
+                Ok(result) => {
+                    info!("Probed {} with {}", path.display(), probe.name());
+                    info.merge(result);
+                    succeeded = true;
+
+                    if info.is_complete() {
+                        break;
+                    }
+                },
+                Err(AppError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound && path.exists() => {
+                    // The probe's binary isn't installed
+                    warn!("{} is not available: {}", probe.name(), e);
+                },
+                Err(e) => {
+                    info!("{} could not read {}: {}", probe.name(), path.display(), e);
+                    last_error = Some(e);
+                },
+            }
+        }
+
+        match (succeeded, last_error) {
+            (false, Some(e)) => Err(e),
+            _ => Ok(info),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::native_probe::fixtures::{be32, hevc_config, iso_box, minimal_mp4, mp4_with_sample_entry, nclx, video_sample_entry};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    // A probe that only records whether it was asked
+    struct FallbackProbe(Arc<AtomicBool>);
+
+    impl MediaProbe for FallbackProbe {
+        fn name(&self) -> &'static str {
+            "fallback"
+        }
+
+        fn probe<'a>(&'a self, _path: &'a Path) -> ProbeFuture<'a> {
+            self.0.store(true, Ordering::SeqCst);
+            Box::pin(async { Ok(MediaInfo::default()) })
+        }
+    }
+
+    #[test]
+    fn parses_frame_rates() {
+        let cases = [
+            ("30000/1001", Some(29.97)),
+            ("24000/1001", Some(23.976)),
+            ("25/1", Some(25.0)),
+            ("0/0", None),
+            ("0/1", None),
+            ("30/0", None),
+            ("-30/1", None),
+            ("30", None),
+            ("", None),
+            ("a/b", None),
+            ("inf/1", None),
+            ("NaN/1", None),
+        ];
+
+        for (value, expected) in cases {
+            let rate = parse_frame_rate(value);
+            assert_eq!(rate.is_some(), expected.is_some(), "{value}");
+            if let (Some(rate), Some(expected)) = (rate, expected) {
+                assert!((rate - expected).abs() < 0.001, "{value}: {rate}");
+            }
+        }
+    }
+
+    #[test]
+    fn reads_bit_depth_from_pixel_formats() {
+        let cases = [
+            ("yuv420p", 8),
+            ("yuvj420p", 8),
+            ("yuv420p10le", 10),
+            ("yuv422p10be", 10),
+            ("yuv444p12le", 12),
+            ("yuva444p16le", 16),
+            ("p010le", 10),
+            ("nv12", 8),
+            ("gray", 8),
+            ("yuv420p7", 8),
+            ("", 8),
+        ];
+
+        for (pixel_format, expected) in cases {
+            assert_eq!(pixel_format_bit_depth(pixel_format), expected, "{pixel_format}");
+        }
+    }
+
+    #[test]
+    fn merge_keeps_creation_time_with_offset() {
+        let utc = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let local = chrono::DateTime::parse_from_rfc3339("2023-11-14T12:13:20-10:00").unwrap();
+
+        let mut native = MediaInfo { creation_time: Some(CreationTime::from_utc(utc)), ..MediaInfo::default() };
+        native.merge(MediaInfo { creation_time: Some(CreationTime::from_local(local)), ..MediaInfo::default() });
+        assert_eq!(native.creation_time.unwrap().offset_minutes, Some(-600));
+
+        let mut with_offset = MediaInfo { creation_time: Some(CreationTime::from_local(local)), ..MediaInfo::default() };
+        with_offset.merge(MediaInfo { creation_time: Some(CreationTime::from_utc(utc)), ..MediaInfo::default() });
+        assert_eq!(with_offset.creation_time.unwrap().offset_minutes, Some(-600));
+    }
+
+    #[tokio::test]
+    async fn native_probe_reads_quicktime_creation_date() {
+        let hdlr = [be32(&[0, 0]), b"mdta".to_vec(), vec![0; 12]].concat();
+        let key = [be32(&[40]), b"mdta".to_vec(), b"com.apple.quicktime.creationdate".to_vec()].concat();
+        let keys = [be32(&[0, 1]), key].concat();
+        let data = [be32(&[1, 0]), b"2023-11-14T12:13:20-1000".to_vec()].concat();
+        let ilst = iso_box(&1u32.to_be_bytes(), &iso_box(b"data", &data));
+        let meta = iso_box(b"meta", &[iso_box(b"hdlr", &hdlr), iso_box(b"keys", &keys), iso_box(b"ilst", &ilst)].concat());
+
+        let path = std::env::temp_dir().join(format!("shoebox-probe-{}.mov", uuid::Uuid::new_v4()));
+        std::fs::write(&path, minimal_mp4(&meta)).unwrap();
+        let info = NativeProbe.probe(&path).await;
+        std::fs::remove_file(&path).unwrap();
+
+        let creation_time = info.unwrap().creation_time.unwrap();
+        assert_eq!(creation_time.offset_minutes, Some(-600));
+        assert_eq!(creation_time.utc.timestamp(), 1_700_000_000);
+    }
+
+    #[tokio::test]
+    async fn native_probe_of_mp4_skips_fallback() {
+        let path = std::env::temp_dir().join(format!("shoebox-probe-{}.mp4", uuid::Uuid::new_v4()));
+        std::fs::write(&path, minimal_mp4(&[])).unwrap();
+
+        let fallback_called = Arc::new(AtomicBool::new(false));
+        let service = ProbeService::new(vec![Box::new(NativeProbe), Box::new(FallbackProbe(fallback_called.clone()))]);
+        let info = service.probe(&path).await;
+        std::fs::remove_file(&path).unwrap();
+
+        let info = info.unwrap();
+        assert_eq!(info.duration_ms, Some(10_000));
+        assert_eq!((info.width, info.height), (Some(1920), Some(1080)));
+        assert_eq!(info.video_codec.as_deref(), Some("h264"));
+        assert!(info.creation_time.is_some());
+        assert!((info.frame_rate.unwrap() - 29.97).abs() < 0.01);
+        assert!(!fallback_called.load(Ordering::SeqCst), "FFprobe fallback ran for a complete MP4");
+    }
+
+    // Probe a file with the native probe and a fallback, returning what was found and whether the fallback ran
+    async fn probe_with_fallback(data: &[u8], extension: &str) -> (MediaInfo, bool) {
+        let path = std::env::temp_dir().join(format!("shoebox-probe-{}.{extension}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, data).unwrap();
+
+        let fallback_called = Arc::new(AtomicBool::new(false));
+        let service = ProbeService::new(vec![Box::new(NativeProbe), Box::new(FallbackProbe(fallback_called.clone()))]);
+        let info = service.probe(&path).await;
+        std::fs::remove_file(&path).unwrap();
+
+        (info.unwrap(), fallback_called.load(Ordering::SeqCst))
+    }
+
+    #[tokio::test]
+    async fn native_probe_reads_10_bit_hdr_hevc() {
+        let sample_entry = video_sample_entry(b"hvc1", &[hevc_config(), nclx(9, 16, 9)].concat());
+        let (info, fallback_called) = probe_with_fallback(&mp4_with_sample_entry(&sample_entry, &[]), "mov").await;
+
+        assert_eq!(info.video_codec.as_deref(), Some("hevc"));
+        assert_eq!(info.video_profile.as_deref(), Some("Main 10"));
+        assert_eq!(info.bit_depth, Some(10));
+        assert_eq!(info.hdr, Some(true));
+        assert_eq!(info.color_primaries.as_deref(), Some("bt2020"));
+        assert!(!fallback_called, "FFprobe fallback ran for an HEVC movie the native probe read");
+    }
+
+    #[tokio::test]
+    async fn fallback_fills_formats_the_native_probe_cant_read() {
+        let (info, fallback_called) = probe_with_fallback(&mp4_with_sample_entry(&video_sample_entry(b"apch", &[]), &[]), "mov").await;
+
+        assert_eq!(info.video_codec.as_deref(), Some("prores"));
+        assert!(fallback_called, "FFprobe fallback didn't run for a ProRes movie without a bit depth");
+    }
+}
@@ -0,0 +1,165 @@
+use sqlx::{Pool, Postgres};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::error::{AppError, Result};
+use crate::models::{
+    ScanJob, ScanJobError, ScanJobWithErrors, SCAN_JOB_INTERRUPTED, SCAN_JOB_RUNNING,
+};
+use crate::services::scanner::{ScanCounts, ScanFileError};
+
+pub struct ScanJobService {
+    db: Pool<Postgres>,
+}
+
+impl ScanJobService {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        Self { db }
+    }
+
+    pub async fn find_all(&self, limit: i64, offset: i64) -> Result<Vec<ScanJob>> {
+        let jobs = sqlx::query_as::<_, ScanJob>(
+            "SELECT * FROM scan_jobs ORDER BY started_at DESC LIMIT $1 OFFSET $2"
+        )
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.db)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(jobs)
+    }
+
+    pub async fn find_by_id(&self, id: &str) -> Result<ScanJob> {
+        let job = sqlx::query_as::<_, ScanJob>("SELECT * FROM scan_jobs WHERE id = $1")
+            .bind(id)
+            .fetch_one(&self.db)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::RowNotFound => AppError::NotFound(format!("Scan job not found: {id}")),
+                _ => AppError::Database(e),
+            })?;
+
+        Ok(job)
+    }
+
+    pub async fn find_with_errors(&self, id: &str) -> Result<ScanJobWithErrors> {
+        let job = self.find_by_id(id).await?;
+
+        let errors = sqlx::query_as::<_, ScanJobError>(
+            "SELECT * FROM scan_job_errors WHERE scan_job_id = $1 ORDER BY created_at, file_path"
+        )
+        .bind(id)
+        .fetch_all(&self.db)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(ScanJobWithErrors { job, errors })
+    }
+
+    // Record the start of a scan over the given sources
+    pub async fn create(&self, sources: &[String], force_full_rescan: bool) -> Result<ScanJob> {
+        let id = Uuid::new_v4().to_string();
+        let now = chrono::Utc::now().naive_utc();
+
+        let job = sqlx::query_as::<_, ScanJob>(
+            "INSERT INTO scan_jobs (id, status, sources, force_full_rescan, started_at)
+             VALUES ($1, $2, $3, $4, $5)
+             RETURNING *"
+        )
+        .bind(&id)
+        .bind(SCAN_JOB_RUNNING)
+        .bind(sources)
+        .bind(force_full_rescan)
+        .bind(now)
+        .fetch_one(&self.db)
+        .await
+        .map_err(AppError::Database)?;
+
+        info!("Started scan job {}", job.id);
+        Ok(job)
+    }
+
+    pub async fn update_counts(&self, id: &str, counts: &ScanCounts) -> Result<()> {
+        sqlx::query(
+            "UPDATE scan_jobs
+             SET new_count = $1, updated_count = $2, skipped_count = $3, failed_count = $4
+             WHERE id = $5"
+        )
+        .bind(counts.new as i32)
+        .bind(counts.updated as i32)
+        .bind(counts.skipped as i32)
+        .bind(counts.failed as i32)
+        .bind(id)
+        .execute(&self.db)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    pub async fn add_errors(&self, id: &str, errors: &[ScanFileError]) -> Result<()> {
+        if errors.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = self.db.begin().await.map_err(AppError::Database)?;
+        let now = chrono::Utc::now().naive_utc();
+
+        for error in errors {
+            sqlx::query(
+                "INSERT INTO scan_job_errors (id, scan_job_id, file_path, stage, message, created_at)
+                 VALUES ($1, $2, $3, $4, $5, $6)"
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(id)
+            .bind(&error.file_path)
+            .bind(&error.stage)
+            .bind(&error.message)
+            .bind(now)
+            .execute(&mut *tx)
+            .await
+            .map_err(AppError::Database)?;
+        }
+
+        tx.commit().await.map_err(AppError::Database)?;
+        Ok(())
+    }
+
+    // Store the final counts and status of a scan
+    pub async fn finish(&self, id: &str, status: &str, counts: &ScanCounts) -> Result<()> {
+        self.update_counts(id, counts).await?;
+
+        let now = chrono::Utc::now().naive_utc();
+        sqlx::query("UPDATE scan_jobs SET status = $1, finished_at = $2 WHERE id = $3")
+            .bind(status)
+            .bind(now)
+            .bind(id)
+            .execute(&self.db)
+            .await
+            .map_err(AppError::Database)?;
+
+        info!("Scan job {} finished with status {}", id, status);
+        Ok(())
+    }
+
+    // Jobs still marked as running when the server starts were cut short by a restart
+    pub async fn mark_interrupted(&self) -> Result<u64> {
+        let now = chrono::Utc::now().naive_utc();
+        let result = sqlx::query(
+            "UPDATE scan_jobs SET status = $1, finished_at = $2 WHERE status = $3"
+        )
+        .bind(SCAN_JOB_INTERRUPTED)
+        .bind(now)
+        .bind(SCAN_JOB_RUNNING)
+        .execute(&self.db)
+        .await
+        .map_err(AppError::Database)?;
+
+        if result.rows_affected() > 0 {
+            warn!("Marked {} unfinished scan jobs as interrupted", result.rows_affected());
+        }
+
+        Ok(result.rows_affected())
+    }
+}
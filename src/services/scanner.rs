@@ -1,26 +1,145 @@
-use std::path::{Path, PathBuf};
-use std::time::SystemTime;
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::{Duration, Instant};
 use tokio::fs;
 use walkdir::WalkDir;
 use tracing::{info, warn, error};
 use anyhow::Result;
-use std::io::{Read, Seek, SeekFrom};
-use std::process::Command;
 use tokio::task;
 use tokio::process::Command as TokioCommand;
 use std::sync::Arc;
-use tokio::sync::Semaphore;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::{RwLock, Semaphore};
+use tokio_util::sync::CancellationToken;
 
+use crate::config::{GeocodeMode, MediaPathConfig};
 use crate::error::AppError;
-use crate::models::{Video, CreateVideoDto};
+use crate::models::{Video, CreateVideoDto, CreationTime, GpsPosition, ScanJob, ORIGINAL_MATCH_AMBIGUOUS, ORIGINAL_MATCH_MANUAL, ORIGINAL_MATCH_MATCHED, MEDIA_TYPE_PHOTO, MEDIA_TYPE_VIDEO, SCAN_JOB_CANCELLED, SCAN_JOB_COMPLETED, SCAN_JOB_FAILED};
 use crate::services::video::VideoService;
 use crate::services::thumbnail::ThumbnailService;
+use crate::services::probe::{parse_creation_time, MediaInfo, ProbeService};
+use crate::services::original_matcher::{MatchTarget, OriginalIndex, OriginalMatch, OriginalMatcher};
+use crate::services::sidecar::{SidecarMetadata, SidecarService};
+use crate::services::geocoder::GeocoderService;
+use crate::services::{AppState, PathRuleService, PersonService, ScanStatus, ScanJobService, ShoeboxService, TagService};
+use crate::utils::{compute_content_hash, compute_partial_hash, needs_proxy, FileFingerprint};
+
+/// How often the live scan status is refreshed while a scan runs
+const STATUS_UPDATE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often a running scan's counts and errors are written to its job
+const JOB_UPDATE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Options controlling how a scan treats files that are already in the database
+#[derive(Clone, Debug, Default)]
+pub struct ScanOptions {
+    /// Re-probe every file, even if its fingerprint hasn't changed since the last scan
+    pub force_full_rescan: bool,
+}
+
+/// Shared results of a running scan, filled in by the per-file tasks
+#[derive(Clone, Default)]
+pub struct ScanProgress {
+    pub new_videos: Arc<tokio::sync::Mutex<Vec<Video>>>,
+    pub updated_videos: Arc<tokio::sync::Mutex<Vec<Video>>>,
+    pub skipped_count: Arc<AtomicUsize>,
+    pub errors: Arc<tokio::sync::Mutex<Vec<ScanFileError>>>,
+    pub failed_files: Arc<tokio::sync::Mutex<HashSet<String>>>,
+    /// Media files found in the scanned sources
+    pub total_files: Arc<AtomicUsize>,
+    /// Files whose processing has finished
+    pub processed_files: Arc<AtomicUsize>,
+    /// File most recently picked up for processing
+    pub current_file: Arc<tokio::sync::Mutex<Option<String>>>,
+}
+
+/// A problem encountered while processing a single file
+#[derive(Clone, Debug)]
+pub struct ScanFileError {
+    pub file_path: String,
+    /// Processing step that failed, e.g. "ffprobe" or "thumbnail"
+    pub stage: String,
+    pub message: String,
+}
+
+/// Snapshot of the per-file counters of a scan
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ScanCounts {
+    pub new: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+impl ScanProgress {
+    pub async fn new_count(&self) -> usize {
+        self.new_videos.lock().await.len()
+    }
+
+    pub async fn updated_count(&self) -> usize {
+        self.updated_videos.lock().await.len()
+    }
+
+    pub fn skipped_count(&self) -> usize {
+        self.skipped_count.load(Ordering::SeqCst)
+    }
+
+    /// Number of files that hit at least one error
+    pub async fn failed_count(&self) -> usize {
+        self.failed_files.lock().await.len()
+    }
+
+    pub async fn counts(&self) -> ScanCounts {
+        ScanCounts {
+            new: self.new_count().await,
+            updated: self.updated_count().await,
+            skipped: self.skipped_count(),
+            failed: self.failed_count().await,
+        }
+    }
+
+    /// Log and remember an error for a file
+    pub async fn record_error(&self, file_path: &str, stage: &str, message: impl std::fmt::Display) {
+        let message = message.to_string();
+        error!("Error during {} for {}: {}", stage, file_path, message);
+
+        self.failed_files.lock().await.insert(file_path.to_string());
+        self.errors.lock().await.push(ScanFileError {
+            file_path: file_path.to_string(),
+            stage: stage.to_string(),
+            message,
+        });
+    }
+
+    /// Take the errors recorded since the last call
+    pub async fn take_errors(&self) -> Vec<ScanFileError> {
+        std::mem::take(&mut *self.errors.lock().await)
+    }
+}
+
+/// Index of the original files of all scanned sources
+pub type OriginalFiles = Arc<tokio::sync::Mutex<OriginalIndex>>;
+
+/// Shared state used while processing individual files, both by scans and the watcher
+#[derive(Clone)]
+pub struct ScanContext {
+    pub video_service: Arc<VideoService>,
+    pub thumbnail_service: Arc<ThumbnailService>,
+    pub probe_service: Arc<ProbeService>,
+    /// Place names for GPS positions, if any source uses reverse geocoding
+    pub geocoder: Option<Arc<GeocoderService>>,
+    pub original_files: OriginalFiles,
+    pub progress: ScanProgress,
+    pub force_full_rescan: bool,
+    pub partial_hash: bool,
+    pub content_hash: bool,
+}
 
 pub struct ScannerService;
 
 impl ScannerService {
     // Extract EXIF data from file using exiftool
-    async fn get_exif_data(path: &str) -> Option<serde_json::Value> {
+    pub async fn get_exif_data(path: &str) -> Option<serde_json::Value> {
         // Check if this is a BRAW file
         let is_braw = path.to_lowercase().ends_with(".braw");
 
@@ -39,7 +158,8 @@ impl ScannerService {
                    .arg("-U"); // Extract unsafe tags
         }
 
-        command.arg(path);
+        command.arg(path)
+               .kill_on_drop(true); // Don't leave exiftool running if the scan is cancelled
 
         // Execute the command
         let output = match command.output().await {
@@ -76,297 +196,11 @@ impl ScannerService {
         }
     }
 
-    // Extract creation date from video file using FFprobe (part of FFmpeg suite)
-    async fn get_video_creation_date(path: &str) -> Option<String> {
-        info!("Attempting to extract creation date from video metadata for: {}", path);
-
-        // Try multiple metadata tags that might contain creation date information
-        // Different video formats store this information in different tags
-        let possible_tags = [
-            "creation_time",           // Common in MP4
-            "com.apple.quicktime.creationdate", // Common in MOV
-            "date",                    // Generic date tag
-            "com.apple.quicktime.createdate", // Alternative MOV tag
-        ];
-
-        for tag in possible_tags.iter() {
-            // Use ffprobe instead of ffmpeg for metadata extraction
-            let output = match TokioCommand::new("ffprobe")
-                .arg("-v")
-                .arg("error")
-                .arg("-show_entries")
-                .arg(format!("format_tags={tag}"))
-                .arg("-of")
-                .arg("default=noprint_wrappers=1:nokey=1")
-                .arg(path)
-                .output()
-                .await {
-                    Ok(output) => output,
-                    Err(e) => {
-                        error!("Failed to execute FFprobe for metadata extraction (tag: {}): {}", tag, e);
-                        continue; // Try next tag
-                    }
-                };
-
-            if !output.status.success() {
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                if !stderr.is_empty() {
-                    error!("FFprobe metadata extraction error (tag: {}): {}", tag, stderr);
-                }
-                continue; // Try next tag
-            }
-
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let creation_time = stdout.trim();
-
-            if creation_time.is_empty() {
-                info!("No {} found in video metadata", tag);
-                continue; // Try next tag
-            }
-
-            info!("Found {} tag with value: {}", tag, creation_time);
-
-            // Try multiple date formats
-            let date_formats = [
-                "%Y-%m-%dT%H:%M:%S%.fZ",  // ISO 8601 with fractional seconds
-                "%Y-%m-%d %H:%M:%S",      // Simple date time format
-                "%Y:%m:%d %H:%M:%S",      // EXIF date format (common in photos/videos)
-                "%Y-%m-%dT%H:%M:%S%z",    // ISO 8601 with timezone
-                "%Y-%m-%d",               // Just date
-            ];
-
-            for format in date_formats.iter() {
-                match chrono::NaiveDateTime::parse_from_str(creation_time, format) {
-                    Ok(dt) => {
-                        let datetime = chrono::DateTime::<chrono::Utc>::from_naive_utc_and_offset(dt, chrono::Utc);
-                        info!("Extracted creation date from video metadata (tag: {}, format: {}): {}",
-                              tag, format, datetime.to_rfc3339());
-                        return Some(datetime.to_rfc3339());
-                    },
-                    Err(_) => {
-                        // Try next format
-                    }
-                }
-
-                // Also try parsing as DateTime which handles timezone information
-                match chrono::DateTime::parse_from_str(creation_time, format) {
-                    Ok(dt) => {
-                        let utc_time = dt.with_timezone(&chrono::Utc);
-                        info!("Extracted creation date from video metadata (tag: {}, format: {}): {}",
-                              tag, format, utc_time.to_rfc3339());
-                        return Some(utc_time.to_rfc3339());
-                    },
-                    Err(_) => {
-                        // Try next format
-                    }
-                }
-            }
-
-            // If we got here, we found a tag but couldn't parse the date
-            error!("Found {} tag but couldn't parse date value: {}", tag, creation_time);
-        }
-
-        // If we got here, we couldn't find any usable creation date
-        info!("No usable creation date found in video metadata");
-        None
-    }
-
-    // Check if a file extension is a supported video format
-    fn is_supported_video_format(ext: &str) -> bool {
-        // List of supported video formats
-        ["mp4", "mov", "mkv", "avi", "wmv", "flv", "webm", "braw"].contains(&ext)
-    }
-
-    // Get video duration using FFprobe
-    async fn get_video_duration(path: &str) -> Option<i64> {
-        info!("Attempting to extract duration from video: {}", path);
-
-        let output = match TokioCommand::new("ffprobe")
-            .arg("-v")
-            .arg("error")
-            .arg("-show_entries")
-            .arg("format=duration")
-            .arg("-of")
-            .arg("default=noprint_wrappers=1:nokey=1")
-            .arg(path)
-            .output()
-            .await {
-                Ok(output) => output,
-                Err(e) => {
-                    error!("Failed to execute FFprobe for duration extraction: {}", e);
-                    return None;
-                }
-            };
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            if !stderr.is_empty() {
-                error!("FFprobe duration extraction error: {}", stderr);
-            }
-            return None;
-        }
-
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let duration_str = stdout.trim();
-
-        if duration_str.is_empty() {
-            info!("No duration found in video metadata");
-            return None;
-        }
-
-        // Parse the duration string to a float and convert to milliseconds
-        match duration_str.parse::<f64>() {
-            Ok(duration_seconds) => {
-                let duration_ms = (duration_seconds * 1000.0) as i64;
-                info!("Extracted duration from video: {} seconds ({} ms)", duration_seconds, duration_ms);
-                Some(duration_ms)
-            },
-            Err(e) => {
-                error!("Failed to parse duration '{}': {}", duration_str, e);
-                None
-            }
-        }
-    }
-
-    // Function to get the duration of an MP4 file (if possible)
-    // This is a fallback method for MP4 files when ffprobe fails
-    fn get_mp4_duration(path: &PathBuf) -> Option<f64> {
-        // Try to open the file in blocking mode for quick analysis
-        let file = match std::fs::File::open(path) {
-            Ok(file) => file,
-            Err(_) => return None, // If we can't open the file, return None
-        };
-
-        // Read the file to find the moov atom and extract duration
-        let mut reader = std::io::BufReader::new(file);
-        let mut buffer = [0u8; 8]; // 8 bytes for atom size (4) and type (4)
-        let mut position = 0;
-
-        // We'll search the entire file for the moov atom
-        loop {
-            // Read atom header
-            match reader.read_exact(&mut buffer) {
-                Ok(_) => {},
-                Err(_) => break, // End of file or error
-            }
-
-            // Parse atom size (big-endian)
-            let size = ((buffer[0] as u32) << 2) |
-                      ((buffer[1] as u32) << 16) |
-                      ((buffer[2] as u32) << 8) |
-                      (buffer[3] as u32);
-
-            // Check if this is the moov atom
-            if &buffer[4..8] == b"moov" {
-                // Found moov atom, now look for mvhd atom inside it
-                let mut mvhd_buffer = vec![0u8; size as usize - 8];
-                if reader.read_exact(&mut mvhd_buffer).is_err() {
-                    break;
-                }
-
-                // Search for mvhd atom inside moov
-                let mut i = 0;
-                while i + 8 <= mvhd_buffer.len() {
-                    let atom_size = ((mvhd_buffer[i] as u32) << 24) |
-                                   ((mvhd_buffer[i+1] as u32) << 16) |
-                                   ((mvhd_buffer[i+2] as u32) << 8) |
-                                   (mvhd_buffer[i+3] as u32);
-
-                    if &mvhd_buffer[i+4..i+8] == b"mvhd" {
-                        // Found mvhd atom, extract duration
-                        // The format depends on the version (first byte after atom header)
-                        let version = mvhd_buffer[i+8];
-
-                        if version == 0 && i + 20 + 12 < mvhd_buffer.len() {
-                            // Version 0: 32-bit duration at offset 20
-                            let time_scale = ((mvhd_buffer[i+16] as u32) << 24) |
-                                            ((mvhd_buffer[i+17] as u32) << 16) |
-                                            ((mvhd_buffer[i+18] as u32) << 8) |
-                                            (mvhd_buffer[i+19] as u32);
-
-                            let duration = ((mvhd_buffer[i+20] as u32) << 24) |
-                                          ((mvhd_buffer[i+21] as u32) << 16) |
-                                          ((mvhd_buffer[i+22] as u32) << 8) |
-                                          (mvhd_buffer[i+23] as u32);
-
-                            if time_scale > 0 {
-                                return Some(duration as f64 / time_scale as f64);
-                            }
-                        } else if version == 1 && i + 28 + 12 < mvhd_buffer.len() {
-                            // Version 1: 64-bit duration at offset 28
-                            let time_scale = ((mvhd_buffer[i+24] as u32) << 24) |
-                                            ((mvhd_buffer[i+25] as u32) << 16) |
-                                            ((mvhd_buffer[i+26] as u32) << 8) |
-                                            (mvhd_buffer[i+27] as u32);
-
-                            let duration = ((mvhd_buffer[i+28] as u64) << 56) |
-                                          ((mvhd_buffer[i+29] as u64) << 48) |
-                                          ((mvhd_buffer[i+30] as u64) << 40) |
-                                          ((mvhd_buffer[i+31] as u64) << 32) |
-                                          ((mvhd_buffer[i+32] as u64) << 24) |
-                                          ((mvhd_buffer[i+33] as u64) << 16) |
-                                          ((mvhd_buffer[i+34] as u64) << 8) |
-                                          (mvhd_buffer[i+35] as u64);
-
-                            if time_scale > 0 {
-                                return Some(duration as f64 / time_scale as f64);
-                            }
-                        }
-
-                        break;
-                    }
-
-                    // Move to the next atom
-                    if atom_size > 0 {
-                        i += atom_size as usize;
-                    } else {
-                        break;
-                    }
-                }
-
-                break;
-            }
-
-            // Skip to the next atom
-            if size > 8 {
-                // Skip the rest of this atom (size - 8 bytes we already read)
-                let to_skip = size as u64 - 8;
-                match reader.seek(SeekFrom::Current(to_skip as i64)) {
-                    Ok(new_pos) => position = new_pos,
-                    Err(_) => break, // Error seeking
-                }
-            } else if size == 0 {
-                // Size 0 means the rest of the file, so we're done
-                break;
-            } else if size < 8 {
-                // Invalid size, something is wrong
-                break;
-            }
-        }
-
-        // If we get here, we couldn't find the duration
-        None
-    }
-
-    pub async fn scan_directories(
-        path_configs: &[crate::config::MediaPathConfig],
-        video_service: VideoService,
-        thumbnail_service: ThumbnailService,
-        config: &crate::config::Config,
-    ) -> Result<(Arc<tokio::sync::Mutex<Vec<Video>>>, Arc<tokio::sync::Mutex<Vec<Video>>>, Vec<tokio::task::JoinHandle<()>>), AppError> {
-        // Wrap services in Arc for sharing across tasks
-        let video_service = Arc::new(video_service);
-        let thumbnail_service = Arc::new(thumbnail_service);
-
-        // Create a semaphore to limit concurrent tasks
-        // This prevents resource exhaustion when scanning large directories
-        let max_concurrent_tasks = config.media.max_concurrent_tasks;
-        info!("Limiting concurrent processing tasks to {}", max_concurrent_tasks);
-        let semaphore = Arc::new(Semaphore::new(max_concurrent_tasks));
-
-        // Create a single map to store all original file paths
+    /// Pre-scan the original directories of the given sources, indexing original files by stem
+    pub async fn prescan_original_files(path_configs: &[MediaPathConfig]) -> OriginalFiles {
+        // Create a single index of all original files
         // This allows us to find original files regardless of which subdirectory they're in
-        let all_original_files_arc = Arc::new(tokio::sync::Mutex::new(std::collections::HashMap::new()));
+        let all_original_files_arc = Arc::new(tokio::sync::Mutex::new(OriginalIndex::default()));
 
         // Pre-scan all original directories to build the map (in parallel)
         let mut prescan_tasks = Vec::new();
@@ -375,7 +209,7 @@ impl ScannerService {
             if let Some(original_path) = &path_config.original_path {
                 info!("Pre-scanning original directory: {}", original_path);
                 let original_path_str = original_path.clone();
-                let original_extension = path_config.original_extension.clone();
+                let path_config = path_config.clone();
                 let all_original_files = all_original_files_arc.clone();
 
                 // Spawn a task for each original directory
@@ -387,43 +221,30 @@ impl ScannerService {
                     }
 
                     let mut files_count = 0;
-                    let mut local_files = std::collections::HashMap::new();
+                    let mut local_files = OriginalIndex::default();
 
                     // Walk through all files in the original directory and its subdirectories
-                    for entry in WalkDir::new(original_path_obj).follow_links(true).into_iter().filter_map(|e| e.ok()) {
+                    let entries = WalkDir::new(original_path_obj)
+                        .follow_links(true)
+                        .into_iter()
+                        .filter_entry(|entry| !path_config.is_excluded(entry.path()))
+                        .filter_map(|e| e.ok());
+
+                    for entry in entries {
                         let path = entry.path();
 
                         if path.is_file() {
                             // Check if we should filter by extension
-                            let should_include = if let Some(ref original_extension) = original_extension {
-                                if let Some(ext) = path.extension() {
-                                    ext.to_string_lossy().to_lowercase() == original_extension.to_lowercase()
-                                } else {
-                                    false
-                                }
-                            } else {
-                                // If no extension specified, include all files
-                                true
-                            };
-
-                            if should_include {
-                                if let Some(file_stem) = path.file_stem() {
-                                    let file_stem_str = file_stem.to_string_lossy().to_string();
-                                    let full_path = path.to_string_lossy().to_string();
-
-                                    // Store the full path with the file stem as the key
-                                    local_files.insert(file_stem_str, full_path);
-                                    files_count += 1;
-                                }
+                            if Self::matches_original_extension(path, path_config.original_extension.as_deref()) {
+                                // Index the file under its stem, keeping files that share a stem
+                                local_files.insert(original_path_obj, path);
+                                files_count += 1;
                             }
                         }
                     }
 
-                    // Update the shared map with our findings
-                    let mut all_files_guard = all_original_files.lock().await;
-                    for (key, value) in local_files {
-                        all_files_guard.insert(key, value);
-                    }
+                    // Update the shared index with our findings
+                    all_original_files.lock().await.merge(local_files);
 
                     info!("Found {} original files in {} and its subdirectories", files_count, original_path_str);
                 });
@@ -437,10 +258,102 @@ impl ScannerService {
             let _ = task.await;
         }
 
-        // Create vectors to hold all the tasks and results
+        all_original_files_arc
+    }
+
+    /// Check if a file in an original directory has the configured original extension
+    pub fn matches_original_extension(path: &Path, original_extension: Option<&str>) -> bool {
+        match original_extension {
+            Some(original_extension) => path
+                .extension()
+                .is_some_and(|ext| ext.to_string_lossy().to_lowercase() == original_extension.to_lowercase()),
+            // If no extension specified, include all files
+            None => true,
+        }
+    }
+
+    /// Resolve a list of source names or sub-paths to the source configurations to scan.
+    /// Sub-paths may be absolute or relative to a source root and keep the options of their source.
+    pub fn resolve_scan_targets(
+        source_paths: &[MediaPathConfig],
+        targets: &[String],
+    ) -> Result<Vec<MediaPathConfig>, AppError> {
+        let mut resolved: Vec<MediaPathConfig> = Vec::new();
+
+        for target in targets {
+            let path_config = if let Some(source) = source_paths.iter().find(|source| source.name.as_deref() == Some(target.as_str())) {
+                source.clone()
+            } else {
+                let target_path = Path::new(target);
+                if target_path.components().any(|c| matches!(c, std::path::Component::ParentDir)) {
+                    return Err(AppError::BadRequest(format!("Invalid scan path: {target}")));
+                }
+
+                source_paths
+                    .iter()
+                    .find_map(|source| {
+                        let root = Path::new(&source.path);
+                        let path = if target_path.is_absolute() {
+                            target_path.to_path_buf()
+                        } else {
+                            root.join(target_path)
+                        };
+
+                        if path.starts_with(root) && path.is_dir() {
+                            Some(MediaPathConfig {
+                                path: path.to_string_lossy().to_string(),
+                                source_root: Some(source.source_root.clone().unwrap_or_else(|| source.path.clone())),
+                                ..source.clone()
+                            })
+                        } else {
+                            None
+                        }
+                    })
+                    .ok_or_else(|| AppError::BadRequest(format!("Unknown source or path: {target}")))?
+            };
+
+            if !resolved.iter().any(|existing| existing.path == path_config.path) {
+                resolved.push(path_config);
+            }
+        }
+
+        Ok(resolved)
+    }
+
+    pub async fn scan_directories(
+        path_configs: &[MediaPathConfig],
+        video_service: VideoService,
+        thumbnail_service: ThumbnailService,
+        config: &crate::config::Config,
+        options: ScanOptions,
+        semaphore: Arc<Semaphore>,
+    ) -> Result<(ScanProgress, Vec<tokio::task::JoinHandle<()>>), AppError> {
+        // The semaphore is shared with every other media task to limit how many run at once.
+        // This prevents resource exhaustion when scanning large directories
+        info!("Limiting concurrent processing tasks to {}", config.media.max_concurrent_tasks.max(1));
+
+        // Build the map of original files before processing any video
+        let original_files = Self::prescan_original_files(path_configs).await;
+
+        if options.force_full_rescan {
+            info!("Forcing a full rescan of all files");
+        }
+
+        // Shared state for all per-file tasks
+        let context = ScanContext {
+            video_service: Arc::new(video_service),
+            thumbnail_service: Arc::new(thumbnail_service),
+            probe_service: Arc::new(ProbeService::default()),
+            geocoder: GeocoderService::load_for_sources(config, path_configs).await,
+            original_files,
+            progress: ScanProgress::default(),
+            force_full_rescan: options.force_full_rescan,
+            partial_hash: config.media.partial_hash,
+            content_hash: config.media.content_hash,
+        };
+
+        // Create a vector to hold all the tasks
         let mut tasks = Vec::new();
-        let new_videos_arc = Arc::new(tokio::sync::Mutex::new(Vec::new()));
-        let updated_videos_arc = Arc::new(tokio::sync::Mutex::new(Vec::new()));
 
         // Collect all video files from all directories first
         let mut all_entries = Vec::new();
@@ -454,7 +367,7 @@ impl ScannerService {
                 continue;
             }
 
-            match Self::get_video_files(path) {
+            match Self::get_media_files(path_config) {
                 Ok(entries) => {
                     for entry in entries {
                         all_entries.push((entry, path_config.clone()));
@@ -466,19 +379,33 @@ impl ScannerService {
             }
         }
 
-        info!("Found {} video files to process", all_entries.len());
+        info!("Found {} media files to process", all_entries.len());
+        context.progress.total_files.store(all_entries.len(), Ordering::SeqCst);
+
+        // Mark videos whose files vanished as offline before processing, so moved files can be relinked
+        for path_config in path_configs {
+            if !Path::new(&path_config.path).exists() {
+                // An unmounted source shouldn't take all of its videos offline
+                continue;
+            }
+
+            let present_paths: HashSet<String> = all_entries
+                .iter()
+                .filter(|(_, entry_config)| entry_config.path == path_config.path)
+                .map(|(entry, _)| entry.path().to_string_lossy().to_string())
+                .collect();
+
+            if let Err(e) = context.video_service.mark_missing_offline(&path_config.path, &present_paths).await {
+                error!("Error marking missing videos under {} as offline: {}", path_config.path, e);
+            }
+        }
 
         // Process files in parallel with semaphore to limit concurrency
         for (entry, path_config) in all_entries {
             let file_path = entry.path().to_string_lossy().to_string();
-            let file_name = entry.file_name().to_string_lossy().to_string();
-
-            // Clone Arc pointers for the async task
-            let video_service = video_service.clone();
-            let thumbnail_service = thumbnail_service.clone();
-            let all_original_files = all_original_files_arc.clone();
-            let new_videos = new_videos_arc.clone();
-            let updated_videos = updated_videos_arc.clone();
+
+            // Clone shared state for the async task
+            let context = context.clone();
             let semaphore = semaphore.clone();
 
             // Spawn a task to process this file
@@ -487,206 +414,799 @@ impl ScannerService {
                 // This will block if we've reached the maximum number of concurrent tasks
                 let _permit = semaphore.acquire().await.unwrap();
 
-                // Get file metadata
-                let metadata = match fs::metadata(&file_path).await {
-                    Ok(m) => m,
-                    Err(e) => {
-                        error!("Error getting metadata for {}: {}", file_path, e);
-                        return;
-                    }
-                };
+                *context.progress.current_file.lock().await = Some(file_path.clone());
+                Self::process_file(&context, file_path, &path_config).await;
+                context.progress.processed_files.fetch_add(1, Ordering::SeqCst);
 
-                // Get created date - only use video metadata as filesystem metadata is not accurate
-                info!("Extracting creation date from video metadata");
-                let created_date = Self::get_video_creation_date(&file_path).await;
+                // The permit is automatically released when it goes out of scope
+            });
 
-                // Generate thumbnail
-                let thumbnail_path = match thumbnail_service.generate_thumbnail(&file_path).await {
-                    Ok(path) => Some(path),
-                    Err(e) => {
-                        error!("Error generating thumbnail for {}: {}", file_path, e);
-                        None
-                    }
+            tasks.push(task);
+        }
+
+        // Return the shared results and tasks without waiting
+        info!("Scan started with {} tasks", tasks.len());
+        Ok((context.progress, tasks))
+    }
+
+    /// Probe a single media file and create or update its video record
+    pub async fn process_file(context: &ScanContext, file_path: String, path_config: &MediaPathConfig) {
+        let file_name = Path::new(&file_path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        // Get file metadata
+        let metadata = match fs::metadata(&file_path).await {
+            Ok(m) => m,
+            Err(e) => {
+                context.progress.record_error(&file_path, "metadata", e).await;
+                return;
+            }
+        };
+
+        let mut fingerprint = FileFingerprint::from_metadata(&metadata);
+        let mut existing_video = context.video_service.find_by_path(&file_path).await.ok();
+
+        // A file that reappears at its old path is no longer offline
+        if let Some(existing) = &existing_video {
+            if existing.offline_since.is_some() {
+                info!("Video is back online: {}", file_path);
+                if let Err(e) = context.video_service.mark_online(&existing.id).await {
+                    error!("Error marking video {} as online: {}", existing.id, e);
+                }
+            }
+        } else {
+            // A new path may be a moved file whose old record went offline
+            existing_video = Self::relink_moved_video(context, &file_path, &file_name, &mut fingerprint).await;
+        }
+
+        let sidecars = SidecarService::discover(Path::new(&file_path), path_config).await;
+
+        // Skip files that haven't changed since they were last scanned, unless a sidecar was edited since
+        if let Some(existing) = &existing_video {
+            let sidecar_changed = sidecars.iter().any(|sidecar| {
+                sidecar.modified.is_some_and(|modified| chrono::DateTime::<chrono::Utc>::from(modified).naive_utc() > existing.updated_at)
+            });
+
+            if !context.force_full_rescan && !sidecar_changed && fingerprint.matches(existing.file_size, existing.file_mtime, existing.file_inode) {
+                // Videos scanned before content hashing was enabled still need a hash
+                if context.content_hash && existing.content_hash.is_none() {
+                    Self::backfill_content_hash(context, existing, &file_path, &mut fingerprint).await;
+                }
+
+                info!("Skipping unchanged video: {}", file_path);
+                context.progress.skipped_count.fetch_add(1, Ordering::SeqCst);
+                return;
+            }
+        }
+
+        if context.partial_hash && fingerprint.partial_hash.is_none() {
+            match compute_partial_hash(Path::new(&file_path)).await {
+                Ok(hash) => fingerprint.partial_hash = Some(hash),
+                Err(e) => context.progress.record_error(&file_path, "hash", e).await,
+            }
+        }
+
+        if context.content_hash {
+            match compute_content_hash(Path::new(&file_path)).await {
+                Ok(hash) => fingerprint.content_hash = Some(hash),
+                Err(e) => context.progress.record_error(&file_path, "hash", e).await,
+            }
+        }
+
+        let file_media_type = path_config.classify(Path::new(&file_path)).unwrap_or(MEDIA_TYPE_VIDEO);
+        let is_photo = file_media_type == MEDIA_TYPE_PHOTO;
+
+        // FFmpeg can't decode some camera originals, such as BRAW. When the file is the original of a
+        // scanned proxy, it takes its duration, thumbnail and playback from the proxy instead.
+        let proxy = if !is_photo && needs_proxy(Path::new(&file_path)) {
+            match context.video_service.find_proxy_for(&file_path).await {
+                Ok(proxy) => proxy,
+                Err(e) => {
+                    context.progress.record_error(&file_path, "database", e).await;
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        // Read duration and creation date from the container - filesystem dates are not accurate.
+        // Photos have no container; their date and size come from the EXIF data below.
+        // Media details stay as they are when a video can't be probed.
+        let mut media_info = if is_photo {
+            Some(MediaInfo::default())
+        } else if proxy.is_some() {
+            None
+        } else {
+            match context.probe_service.probe(Path::new(&file_path)).await {
+                Ok(media_info) => {
+                    info!("Probed {}: {:?}x{:?} {:?}", file_path, media_info.width, media_info.height, media_info.video_codec);
+                    Some(media_info)
+                },
+                Err(e) => {
+                    context.progress.record_error(&file_path, "probe", e).await;
+                    None
+                }
+            }
+        };
+        let mut created_date = media_info.as_ref().and_then(|media_info| media_info.creation_time);
+        let duration = media_info
+            .as_ref()
+            .and_then(|media_info| media_info.duration_ms)
+            .or_else(|| proxy.as_ref().and_then(|proxy| proxy.duration));
+
+        // Extract EXIF data from the main file
+        let mut exif_data = Self::get_exif_data(&file_path).await;
+
+        // Fall back to the date the camera recorded when the container has none, and take the
+        // camera's UTC offset from it when the container only has the UTC time
+        let exif_date = exif_data.as_ref().and_then(exif_creation_time);
+        created_date = match (created_date, exif_date) {
+            (Some(container), Some(exif)) if container.offset_minutes.is_none() => Some(CreationTime {
+                offset_minutes: exif.offset_minutes,
+                ..container
+            }),
+            (container, exif) => container.or(exif),
+        };
+
+        if is_photo {
+            if let (Some(media_info), Some((width, height))) = (media_info.as_mut(), exif_data.as_ref().and_then(exif_image_size)) {
+                media_info.width = Some(width);
+                media_info.height = Some(height);
+            }
+        }
+
+        // Keep originals that were set by hand, otherwise look for the original among the files sharing the stem
+        let manual_original = existing_video
+            .as_ref()
+            .filter(|video| video.original_match_status.as_deref() == Some(ORIGINAL_MATCH_MANUAL))
+            .map(|video| video.original_file_path.clone());
+
+        let original_match = match (&manual_original, &path_config.original_path) {
+            (None, Some(original_path)) => {
+                let stem = Path::new(&file_name)
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let candidates = context.original_files.lock().await.candidates(&stem, Path::new(original_path));
+                let target = MatchTarget {
+                    file_path: Path::new(&file_path),
+                    media_root: Path::new(&path_config.path),
+                    size: metadata.len(),
+                    duration_ms: duration,
+                    exif_data: exif_data.as_ref(),
                 };
 
-                // Extract duration for video files
-                let duration = if let Some(ext) = std::path::Path::new(&file_path).extension() {
-                    let ext = ext.to_string_lossy().to_lowercase();
-                    if Self::is_supported_video_format(&ext) {
-                        // Use ffprobe to get duration for all supported video formats
-                        let ffprobe_duration = Self::get_video_duration(&file_path).await;
-
-                        // Fallback to MP4 specific method for MP4 files if ffprobe failed
-                        if ffprobe_duration.is_none() && ext == "mp4" {
-                            Self::get_mp4_duration(&std::path::PathBuf::from(&file_path))
-                                .map(|d| d as i64)
+                let original_match = OriginalMatcher::find(candidates, &target, &context.probe_service).await;
+                match &original_match {
+                    OriginalMatch::Matched(original) => info!("Found original file: {}", original.path.display()),
+                    OriginalMatch::Ambiguous(candidates) => warn!(
+                        "Ambiguous original for {}: {} candidates in {}",
+                        file_path, candidates.len(), original_path
+                    ),
+                    OriginalMatch::NotFound => {
+                        let extension_info = if let Some(ext) = &path_config.original_extension {
+                            format!(" with extension '{ext}'")
                         } else {
-                            ffprobe_duration
+                            "".to_string()
+                        };
+                        info!("Original file not found for stem: '{}'{} in path: '{}'",
+                              stem, extension_info, original_path);
+                    },
+                }
+                Some(original_match)
+            },
+            _ => None,
+        };
+
+        let original_file_path = match (&manual_original, &original_match) {
+            (Some(manual_path), _) => manual_path.clone(),
+            (None, Some(OriginalMatch::Matched(original))) => Some(original.path.to_string_lossy().to_string()),
+            _ => None,
+        };
+
+        // Originals FFmpeg can't decode that were scanned before this proxy get its thumbnail once it has one
+        let undecodable_original = original_file_path.clone().filter(|original| needs_proxy(Path::new(original)));
+
+        // If there is an original file, extract its EXIF data as well
+        if let Some(original_file) = &original_file_path {
+            if let Some(original_exif_data) = Self::get_exif_data(original_file).await {
+                info!("Extracted EXIF data from original file: {}", original_file);
+
+                // Merge original file EXIF data with main file EXIF data
+                if let Some(ref mut main_exif) = exif_data {
+                    // If we have EXIF data from both files, merge them
+                    if let serde_json::Value::Object(main_map) = main_exif {
+                        if let serde_json::Value::Object(original_map) = original_exif_data {
+                            // Add a prefix to original file EXIF data keys to distinguish them
+                            for (key, value) in original_map {
+                                main_map.insert(format!("Original_{key}"), value);
+                            }
+                            info!("Merged EXIF data from original file with main file EXIF data");
                         }
-                    } else {
-                        None
                     }
                 } else {
-                    None
-                };
+                    // If we only have EXIF data from the original file, use that
+                    exif_data = Some(original_exif_data);
+                    info!("Using EXIF data from original file only");
+                }
+            }
+        }
 
-                // Extract EXIF data from the main file
-                let mut exif_data = Self::get_exif_data(&file_path).await;
-
-                // Check for original file if original_path is specified
-                let original_file_path = if let Some(original_path) = &path_config.original_path {
-                    // Get the file name without extension
-                    let file_stem = std::path::Path::new(&file_name)
-                        .file_stem()
-                        .map(|s| s.to_string_lossy().to_string());
-
-                    if let Some(stem) = file_stem {
-                        // Look up the file stem in our pre-built map
-                        let all_files_guard = all_original_files.lock().await;
-                        if let Some(original_file) = all_files_guard.get(&stem) {
-                            info!("Found original file: {}", original_file);
-
-                            // If configured, extract EXIF data from the original file as well
-                            if let Some(original_exif_data) = Self::get_exif_data(original_file).await {
-                                info!("Extracted EXIF data from original file: {}", original_file);
-
-                                // Merge original file EXIF data with main file EXIF data
-                                if let Some(ref mut main_exif) = exif_data {
-                                    // If we have EXIF data from both files, merge them
-                                    if let serde_json::Value::Object(main_map) = main_exif {
-                                        if let serde_json::Value::Object(original_map) = original_exif_data {
-                                            // Add a prefix to original file EXIF data keys to distinguish them
-                                            for (key, value) in original_map {
-                                                main_map.insert(format!("Original_{key}"), value);
-                                            }
-                                            info!("Merged EXIF data from original file with main file EXIF data");
-                                        }
-                                    }
-                                } else {
-                                    // If we only have EXIF data from the original file, use that
-                                    exif_data = Some(original_exif_data);
-                                    info!("Using EXIF data from original file only");
+        // Keep sidecar metadata that has no column of its own with the EXIF data
+        let sidecar_metadata = SidecarService::read(&sidecars).await;
+        if !sidecar_metadata.extra.is_empty() {
+            let exif_map = exif_data.get_or_insert_with(|| serde_json::Value::Object(serde_json::Map::new()));
+            if let serde_json::Value::Object(exif_map) = exif_map {
+                exif_map.extend(sidecar_metadata.extra.clone());
+            }
+        }
+
+        // Where the file was recorded, preferring a sidecar's position over the file's own GPS tags
+        let position = sidecar_metadata.position.or_else(|| exif_data.as_ref().and_then(exif_gps_position));
+
+        // Check if video already exists in database
+        match existing_video {
+            Some(existing_video) => {
+                // Update existing video with new metadata
+                info!("Updating existing video: {}", file_path);
+                let file_changed = !fingerprint.matches(existing_video.file_size, existing_video.file_mtime, existing_video.file_inode);
+                // We already have the EXIF data from both the main file and original file (if available)
+                // No need to extract it again
+
+                match context.video_service.update_technical_metadata(
+                    &existing_video.id,
+                    Some(metadata.len() as i64),
+                    duration,
+                    created_date,
+                    None,
+                    original_file_path,
+                    exif_data
+                ).await {
+                    Ok(updated_video) => {
+                        if let Some(original_match) = &original_match {
+                            Self::store_original_match(context, &updated_video.id, original_match, &file_path).await;
+                        }
+
+                        Self::store_sidecar_metadata(context, &updated_video.id, &sidecar_metadata, &file_path).await;
+                        Self::store_position(context, path_config, &updated_video.id, position, &file_path).await;
+                        Self::store_media_details(context, &updated_video.id, media_info.as_ref(), &file_path).await;
+
+                        match &proxy {
+                            Some(proxy) => Self::store_proxy(context, &existing_video, proxy, &file_path).await,
+                            None => {
+                                // Keep thumbnails chosen by hand
+                                if existing_video.thumbnail_time.is_none() {
+                                    Self::store_thumbnail(context, path_config, &existing_video, is_photo, duration, &file_path).await;
                                 }
-                            }
+                                if !is_photo {
+                                    Self::store_sprite_sheet(context, &updated_video.id, duration, &file_path).await;
+                                    Self::store_preview(context, &updated_video.id, duration, &file_path).await;
+                                }
+                            },
+                        }
+                        if let Some(original) = &undecodable_original {
+                            Self::share_with_original(context, original, &file_path).await;
+                        }
 
-                            Some(original_file.clone())
-                        } else {
-                            // If not found in the map, we couldn't find the original file
-                            let extension_info = if let Some(ext) = &path_config.original_extension {
-                                format!(" with extension '{ext}'")
+                        // Remember the fingerprint so the next scan can skip this file
+                        if let Err(e) = context.video_service.update_file_fingerprint(&updated_video.id, &fingerprint, file_changed).await {
+                            context.progress.record_error(&file_path, "database", e).await;
+                        }
+
+                        // Check if there's a default shoebox for this path config
+                        if let Some(default_shoebox) = &path_config.default_shoebox {
+                            info!("Adding video to default shoebox: {}", default_shoebox);
+
+                            // Add the video to the default shoebox using the new method
+                            if let Err(e) = context.video_service.add_to_default_shoebox(&updated_video.id, default_shoebox).await {
+                                error!("Error adding video to default shoebox: {}", e);
                             } else {
-                                "".to_string()
-                            };
-                            info!("Original file not found for stem: '{}'{} in path: '{}'",
-                                  stem, extension_info, original_path);
-                            None
+                                info!("Added video to default shoebox: {}", default_shoebox);
+                            }
                         }
-                    } else {
-                        None
+
+                        let mut updated_videos_guard = context.progress.updated_videos.lock().await;
+                        updated_videos_guard.push(updated_video);
+                    },
+                    Err(e) => {
+                        context.progress.record_error(&file_path, "database", e).await;
                     }
-                } else {
-                    None
-                };
+                }
+                return;
+            },
+            None => {
+                // Video doesn't exist, continue with creation
+            }
+        };
 
-                // Check if video already exists in database
-                match video_service.find_by_path(&file_path).await {
-                    Ok(existing_video) => {
-                        // Update existing video with new metadata
-                        info!("Updating existing video: {}", file_path);
-                        // We already have the EXIF data from both the main file and original file (if available)
-                        // No need to extract it again
-
-                        match video_service.update_technical_metadata(
-                            &existing_video.id,
-                            Some(metadata.len() as i64),
-                            duration,
-                            created_date,
-                            thumbnail_path,
-                            original_file_path,
-                            exif_data
-                        ).await {
-                            Ok(updated_video) => {
-                                // Check if there's a default shoebox for this path config
-                                if let Some(default_shoebox) = &path_config.default_shoebox {
-                                    info!("Adding video to default shoebox: {}", default_shoebox);
-
-                                    // Add the video to the default shoebox using the new method
-                                    if let Err(e) = video_service.add_to_default_shoebox(&updated_video.id, default_shoebox).await {
-                                        error!("Error adding video to default shoebox: {}", e);
-                                    } else {
-                                        info!("Added video to default shoebox: {}", default_shoebox);
+        info!("Found new video: {}", file_path);
+
+        // Create video record
+        let file_name_clone = file_name.clone();
+
+        // Initialize shoeboxes with default_shoebox if it exists
+        let mut shoeboxes = Vec::new();
+        if let Some(default_shoebox) = &path_config.default_shoebox {
+            shoeboxes.push(default_shoebox.clone());
+        }
+
+        // Fill in metadata encoded in the folder structure
+        let path_metadata = PathRuleService::derive(path_config, Path::new(&file_path)).unwrap_or_default();
+        for shoebox in path_metadata.shoeboxes {
+            if !shoeboxes.contains(&shoebox) {
+                shoeboxes.push(shoebox);
+            }
+        }
+
+        let create_dto = CreateVideoDto {
+            file_path: file_path.clone(),
+            file_name,
+            title: Some(file_name_clone),
+            description: None,
+            created_date: created_date.map(|date| date.utc),
+            created_date_offset: created_date.and_then(|date| date.offset_minutes),
+            file_size: Some(metadata.len() as i64),
+            thumbnail_path: None,
+            rating: None,
+            duration,
+            tags: path_metadata.tags,
+            people: path_metadata.people,
+            shoeboxes,
+            original_file_path,
+            exif_data,
+            location: path_metadata.location,
+            event: path_metadata.event,
+            file_mtime: fingerprint.mtime,
+            file_inode: fingerprint.inode,
+            partial_hash: fingerprint.partial_hash,
+            content_hash: fingerprint.content_hash,
+            media_type: file_media_type.to_string(),
+        };
+
+        match context.video_service.create(create_dto).await {
+            Ok(video) => {
+                if let Some(original_match) = &original_match {
+                    Self::store_original_match(context, &video.id, original_match, &file_path).await;
+                }
+
+                Self::store_sidecar_metadata(context, &video.id, &sidecar_metadata, &file_path).await;
+                Self::store_position(context, path_config, &video.id, position, &file_path).await;
+                Self::store_media_details(context, &video.id, media_info.as_ref(), &file_path).await;
+                match &proxy {
+                    Some(proxy) => Self::store_proxy(context, &video, proxy, &file_path).await,
+                    None => {
+                        Self::store_thumbnail(context, path_config, &video, is_photo, duration, &file_path).await;
+                        if !is_photo {
+                            Self::store_sprite_sheet(context, &video.id, duration, &file_path).await;
+                            Self::store_preview(context, &video.id, duration, &file_path).await;
+                        }
+                    },
+                }
+                if let Some(original) = &undecodable_original {
+                    Self::share_with_original(context, original, &file_path).await;
+                }
+
+                let mut new_videos_guard = context.progress.new_videos.lock().await;
+                new_videos_guard.push(video);
+            },
+            Err(e) => {
+                context.progress.record_error(&file_path, "database", e).await;
+            }
+        }
+    }
+
+    // Record which original file a video was matched to, or the candidates if the match is ambiguous
+    async fn store_original_match(context: &ScanContext, video_id: &str, original_match: &OriginalMatch, file_path: &str) {
+        let (original_file_path, status, candidates) = match original_match {
+            OriginalMatch::Matched(original) => {
+                (Some(original.path.to_string_lossy().to_string()), Some(ORIGINAL_MATCH_MATCHED), None)
+            },
+            OriginalMatch::Ambiguous(candidates) => {
+                let paths: Vec<String> = candidates
+                    .iter()
+                    .map(|candidate| candidate.path.to_string_lossy().to_string())
+                    .collect();
+                (None, Some(ORIGINAL_MATCH_AMBIGUOUS), Some(serde_json::json!(paths)))
+            },
+            OriginalMatch::NotFound => (None, None, None),
+        };
+
+        if let Err(e) = context.video_service.set_original_match(video_id, original_file_path.as_deref(), status, candidates).await {
+            context.progress.record_error(file_path, "database", e).await;
+        }
+    }
+
+    // Store ratings and keywords read from sidecars
+    async fn store_sidecar_metadata(context: &ScanContext, video_id: &str, metadata: &SidecarMetadata, file_path: &str) {
+        if !metadata.has_video_fields() {
+            return;
+        }
+
+        if let Err(e) = context.video_service.apply_sidecar_metadata(video_id, metadata).await {
+            context.progress.record_error(file_path, "sidecar", e).await;
+        }
+    }
+
+    // Store the technical details found by probing the file
+    async fn store_media_details(context: &ScanContext, video_id: &str, media_info: Option<&MediaInfo>, file_path: &str) {
+        let Some(media_info) = media_info else {
+            return;
+        };
+
+        if let Err(e) = context.video_service.update_media_details(video_id, media_info).await {
+            context.progress.record_error(file_path, "database", e).await;
+        }
+    }
+
+    // Generate the thumbnail, which is named after the video so it needs its id
+    async fn store_thumbnail(context: &ScanContext, path_config: &MediaPathConfig, video: &Video, is_photo: bool, duration: Option<i64>, file_path: &str) {
+        let thumbnail = if is_photo {
+            context.thumbnail_service.generate_photo_thumbnail(&video.id, file_path).await
+        } else {
+            context.thumbnail_service.generate_thumbnail(&video.id, file_path, duration, path_config.thumbnail).await
+        };
+
+        let thumbnail_path = match thumbnail {
+            Ok(thumbnail_path) => thumbnail_path,
+            Err(e) => {
+                context.progress.record_error(file_path, "thumbnail", e).await;
+                return;
+            }
+        };
+
+        if let Err(e) = context.video_service.update_thumbnail_path(&video.id, video.thumbnail_path.as_deref(), &thumbnail_path).await {
+            context.progress.record_error(file_path, "database", e).await;
+        }
+    }
+
+    // Have an original FFmpeg can't decode take its duration and thumbnail from its proxy
+    async fn store_proxy(context: &ScanContext, video: &Video, proxy: &Video, file_path: &str) {
+        info!("Using proxy {} for {}", proxy.file_path, file_path);
+        if let Err(e) = context.video_service.link_proxy(&video.id, video.thumbnail_path.as_deref(), proxy).await {
+            context.progress.record_error(file_path, "proxy", e).await;
+        }
+    }
+
+    // Link the video of a proxy's original, if it was scanned first, now that the proxy has its thumbnail
+    async fn share_with_original(context: &ScanContext, original_file_path: &str, file_path: &str) {
+        let Ok(original) = context.video_service.find_by_path(original_file_path).await else {
+            return;
+        };
+
+        match context.video_service.find_proxy_for(original_file_path).await {
+            Ok(Some(proxy)) => Self::store_proxy(context, &original, &proxy, original_file_path).await,
+            Ok(None) => {},
+            Err(e) => context.progress.record_error(file_path, "database", e).await,
+        }
+    }
+
+    // Generate the seek preview sprite sheet, which is named after the video so it needs its id
+    async fn store_sprite_sheet(context: &ScanContext, video_id: &str, duration: Option<i64>, file_path: &str) {
+        if let Err(e) = context.thumbnail_service.generate_sprite_sheet(video_id, file_path, duration).await {
+            context.progress.record_error(file_path, "sprite", e).await;
+        }
+    }
+
+    // Generate the hover preview when previews are turned on. This runs within the file's scan permit,
+    // so previews are encoded no more than MAX_CONCURRENT_TASKS at a time.
+    async fn store_preview(context: &ScanContext, video_id: &str, duration: Option<i64>, file_path: &str) {
+        if let Err(e) = context.thumbnail_service.generate_preview(video_id, file_path, duration).await {
+            context.progress.record_error(file_path, "preview", e).await;
+        }
+    }
+
+    // Store where a file was recorded, with the nearest place if the source uses reverse geocoding
+    async fn store_position(context: &ScanContext, path_config: &MediaPathConfig, video_id: &str, position: Option<GpsPosition>, file_path: &str) {
+        let Some(position) = position else {
+            return;
+        };
+
+        let suggested_location = match (&context.geocoder, path_config.geocode) {
+            (Some(geocoder), GeocodeMode::Suggest | GeocodeMode::Fill) => geocoder.nearest(&position),
+            _ => None,
+        };
+
+        let fill_location = path_config.geocode == GeocodeMode::Fill;
+        if let Err(e) = context.video_service.set_position(video_id, &position, suggested_location.as_deref(), fill_location).await {
+            context.progress.record_error(file_path, "location", e).await;
+        }
+    }
+
+    // Compute and store the content hash of an unchanged video that doesn't have one yet
+    async fn backfill_content_hash(context: &ScanContext, video: &Video, file_path: &str, fingerprint: &mut FileFingerprint) {
+        match compute_content_hash(Path::new(file_path)).await {
+            Ok(hash) => {
+                fingerprint.content_hash = Some(hash);
+                if let Err(e) = context.video_service.update_file_fingerprint(&video.id, fingerprint, false).await {
+                    error!("Error storing content hash for {}: {}", file_path, e);
+                }
+            },
+            Err(e) => warn!("Error computing content hash for {}: {}", file_path, e),
+        }
+    }
+
+    // Find an offline video with the same content as a newly discovered file and move it to the new path
+    async fn relink_moved_video(
+        context: &ScanContext,
+        file_path: &str,
+        file_name: &str,
+        fingerprint: &mut FileFingerprint,
+    ) -> Option<Video> {
+        let candidates = match context.video_service.find_offline_by_size(fingerprint.size).await {
+            Ok(candidates) if !candidates.is_empty() => candidates,
+            Ok(_) => return None,
+            Err(e) => {
+                error!("Error looking up offline videos for {}: {}", file_path, e);
+                return None;
+            }
+        };
+
+        // The partial hash is needed to compare contents, even if hashing is otherwise disabled
+        if fingerprint.partial_hash.is_none() {
+            match compute_partial_hash(Path::new(file_path)).await {
+                Ok(hash) => fingerprint.partial_hash = Some(hash),
+                Err(e) => warn!("Error computing partial hash for {}: {}", file_path, e),
+            }
+        }
+
+        let by_hash = candidates
+            .iter()
+            .find(|video| video.partial_hash.is_some() && video.partial_hash == fingerprint.partial_hash);
+
+        // Records scanned before hashes were stored can only be matched by name and size
+        let by_name = || {
+            let mut matches = candidates
+                .iter()
+                .filter(|video| video.partial_hash.is_none() && video.file_name == file_name);
+            match (matches.next(), matches.next()) {
+                (Some(video), None) => Some(video),
+                _ => None,
+            }
+        };
+
+        let moved_video = by_hash.or_else(by_name)?;
+        info!("Found moved video {} ({} -> {})", moved_video.id, moved_video.file_path, file_path);
+
+        match context.video_service.relink(&moved_video.id, file_path, file_name).await {
+            Ok(relinked) => relinked,
+            Err(e) => {
+                error!("Error relinking video {} to {}: {}", moved_video.id, file_path, e);
+                None
+            }
+        }
+    }
+
+    /// Start a scan of the given sources in the background, recording it as a scan job.
+    /// Returns `None` if another scan is already in progress.
+    pub async fn start_scan(
+        state: &AppState,
+        source_paths: Vec<MediaPathConfig>,
+        sources: Vec<String>,
+        options: ScanOptions,
+    ) -> Result<Option<ScanJob>, AppError> {
+        // Check if a scan is already in progress and claim the scan slot
+        let cancel_token = CancellationToken::new();
+        {
+            let mut status = state.scan_status.write().await;
+            if status.in_progress {
+                return Ok(None);
+            }
+
+            // Mark scan as in progress
+            status.in_progress = true;
+            status.new_videos_count = 0;
+            status.updated_videos_count = 0;
+            status.skipped_videos_count = 0;
+            status.failed_videos_count = 0;
+            status.total_files = 0;
+            status.processed_files = 0;
+            status.current_file = None;
+            status.started_at = Some(Instant::now());
+            status.job_id = None;
+            status.cancel_token = Some(cancel_token.clone());
+            status.publish();
+        }
+
+        // Clone what we need for the background task
+        let db = state.db.clone();
+        let config = state.config.clone();
+        let scan_status = state.scan_status.clone();
+        let task_permits = state.task_permits.clone();
+
+        // Record the scan in the job history
+        let job = match ScanJobService::new(db.clone()).create(&sources, options.force_full_rescan).await {
+            Ok(job) => job,
+            Err(e) => {
+                let mut status = scan_status.write().await;
+                status.in_progress = false;
+                status.cancel_token = None;
+                status.publish();
+                return Err(e);
+            }
+        };
+        let job_id = job.id.clone();
+        {
+            let mut status = scan_status.write().await;
+            status.job_id = Some(job.id.clone());
+            status.publish();
+        }
+
+        // Spawn a background task to perform the scan
+        tokio::spawn(async move {
+            let scan_job_service = ScanJobService::new(db.clone());
+            let video_service = VideoService::new(
+                db.clone(),
+                TagService::new(db.clone()),
+                PersonService::new(db.clone()),
+                ThumbnailService::new(&config),
+                ShoeboxService::new(db.clone()),
+            );
+
+            let thumbnail_service = ThumbnailService::new(&config);
+
+            // Start the scan but don't wait for it to complete, giving up early if it is cancelled
+            let scan = tokio::select! {
+                result = Self::scan_directories(
+                    &source_paths,
+                    video_service,
+                    thumbnail_service,
+                    &config,
+                    options,
+                    task_permits,
+                ) => result,
+                _ = cancel_token.cancelled() => {
+                    info!("Scan job {} cancelled before processing started", job_id);
+                    if let Err(e) = scan_job_service.finish(&job_id, SCAN_JOB_CANCELLED, &Default::default()).await {
+                        error!("Error finishing scan job {}: {}", job_id, e);
+                    }
+
+                    let mut status = scan_status.write().await;
+                    status.in_progress = false;
+                    status.cancel_token = None;
+                    status.publish();
+                    return;
+                }
+            };
+
+            match scan {
+                Ok((progress, tasks)) => {
+                    // Spawn another task to wait for all processing tasks to complete
+                    // This ensures the main scan task returns quickly
+                    tokio::spawn(async move {
+                        // Update the live scan status often, and the job history less often
+                        let mut status_interval = tokio::time::interval(STATUS_UPDATE_INTERVAL);
+                        let mut job_interval = tokio::time::interval(JOB_UPDATE_INTERVAL);
+
+                        // Track tasks that are still running
+                        let mut remaining_tasks = tasks;
+
+                        while !remaining_tasks.is_empty() {
+                            tokio::select! {
+                                _ = status_interval.tick() => {
+                                    Self::update_scan_status(&scan_status, &progress).await;
+                                },
+                                _ = job_interval.tick() => {
+                                    Self::record_job_progress(&scan_job_service, &job_id, &progress).await;
+                                },
+                                _ = cancel_token.cancelled() => {
+                                    // Aborting a task drops its FFmpeg and FFprobe children, which kills them
+                                    info!("Cancelling scan job {}, aborting {} tasks", job_id, remaining_tasks.len());
+                                    for task in &remaining_tasks {
+                                        task.abort();
                                     }
+                                    break;
+                                }
+                            }
+
+                            // Check which tasks have completed
+                            remaining_tasks.retain(|task| !task.is_finished());
+                        }
+
+                        // Wait for any aborted tasks to wind down, then collect final results
+                        let job_status = if cancel_token.is_cancelled() {
+                            SCAN_JOB_CANCELLED
+                        } else {
+                            SCAN_JOB_COMPLETED
+                        };
+
+                        match Self::collect_scan_results(
+                            progress.clone(),
+                            remaining_tasks
+                        ).await {
+                            Ok((new_videos, updated_videos)) => {
+                                let counts = Self::record_job_progress(&scan_job_service, &job_id, &progress).await;
+                                if let Err(e) = scan_job_service.finish(&job_id, job_status, &counts).await {
+                                    error!("Error finishing scan job {}: {}", job_id, e);
                                 }
 
-                                let mut updated_videos_guard = updated_videos.lock().await;
-                                updated_videos_guard.push(updated_video);
+                                // Update scan status with final results
+                                Self::update_scan_status(&scan_status, &progress).await;
+                                let mut status = scan_status.write().await;
+                                status.in_progress = false;
+                                status.cancel_token = None;
+                                status.current_file = None;
+                                status.new_videos_count = new_videos.len();
+                                status.updated_videos_count = updated_videos.len();
+                                status.skipped_videos_count = counts.skipped;
+                                status.failed_videos_count = counts.failed;
+                                status.publish();
                             },
                             Err(e) => {
-                                error!("Error updating video metadata: {}", e);
+                                error!("Error collecting scan results: {}", e);
+                                let counts = Self::record_job_progress(&scan_job_service, &job_id, &progress).await;
+                                if let Err(e) = scan_job_service.finish(&job_id, SCAN_JOB_FAILED, &counts).await {
+                                    error!("Error finishing scan job {}: {}", job_id, e);
+                                }
+
+                                // Mark scan as not in progress even if it failed
+                                let mut status = scan_status.write().await;
+                                status.in_progress = false;
+                                status.cancel_token = None;
+                                status.current_file = None;
+                                status.publish();
                             }
                         }
-                        return;
-                    },
-                    Err(_) => {
-                        // Video doesn't exist, continue with creation
+                    });
+                },
+                Err(e) => {
+                    error!("Error starting scan: {}", e);
+                    if let Err(e) = scan_job_service.finish(&job_id, SCAN_JOB_FAILED, &Default::default()).await {
+                        error!("Error finishing scan job {}: {}", job_id, e);
                     }
-                };
 
-                info!("Found new video: {}", file_path);
-
-                // Create video record
-                let file_name_clone = file_name.clone();
-
-                // Initialize shoeboxes with default_shoebox if it exists
-                let mut shoeboxes = Vec::new();
-                if let Some(default_shoebox) = &path_config.default_shoebox {
-                    shoeboxes.push(default_shoebox.clone());
+                    // Mark scan as not in progress if it failed to start
+                    let mut status = scan_status.write().await;
+                    status.in_progress = false;
+                    status.cancel_token = None;
+                    status.publish();
                 }
+            }
+        });
 
-                let create_dto = CreateVideoDto {
-                    file_path,
-                    file_name,
-                    title: Some(file_name_clone),
-                    description: None,
-                    created_date,
-                    file_size: Some(metadata.len() as i64),
-                    thumbnail_path,
-                    rating: None,
-                    duration,
-                    tags: Vec::new(),
-                    people: Vec::new(),
-                    shoeboxes,
-                    original_file_path,
-                    exif_data,
-                    location: None,
-                    event: None,
-                };
+        Ok(Some(job))
+    }
 
-                match video_service.create(create_dto).await {
-                    Ok(video) => {
-                        let mut new_videos_guard = new_videos.lock().await;
-                        new_videos_guard.push(video);
-                    },
-                    Err(e) => {
-                        error!("Error creating video record: {}", e);
-                    }
-                }
+    // Copy the progress of a running scan into the scan status and notify listeners
+    async fn update_scan_status(scan_status: &RwLock<ScanStatus>, progress: &ScanProgress) {
+        let counts = progress.counts().await;
+        let current_file = progress.current_file.lock().await.clone();
+
+        let mut status = scan_status.write().await;
+        status.new_videos_count = counts.new;
+        status.updated_videos_count = counts.updated;
+        status.skipped_videos_count = counts.skipped;
+        status.failed_videos_count = counts.failed;
+        status.total_files = progress.total_files.load(Ordering::SeqCst);
+        status.processed_files = progress.processed_files.load(Ordering::SeqCst);
+        status.current_file = current_file;
+        status.publish();
+    }
 
-                // The permit is automatically released when it goes out of scope
-            });
+    // Persist the counts and any new per-file errors of a running scan
+    pub(crate) async fn record_job_progress(
+        scan_job_service: &ScanJobService,
+        job_id: &str,
+        progress: &ScanProgress,
+    ) -> ScanCounts {
+        let errors = progress.take_errors().await;
+        if let Err(e) = scan_job_service.add_errors(job_id, &errors).await {
+            error!("Error recording scan errors for job {}: {}", job_id, e);
+        }
 
-            tasks.push(task);
+        let counts = progress.counts().await;
+        if let Err(e) = scan_job_service.update_counts(job_id, &counts).await {
+            error!("Error updating scan job {}: {}", job_id, e);
         }
 
-        // Return the shared results and tasks without waiting
-        info!("Scan started with {} tasks", tasks.len());
-        Ok((new_videos_arc, updated_videos_arc, tasks))
+        counts
     }
 
     // Helper method to collect results from a scan
     pub async fn collect_scan_results(
-        new_videos_arc: Arc<tokio::sync::Mutex<Vec<Video>>>,
-        updated_videos_arc: Arc<tokio::sync::Mutex<Vec<Video>>>,
+        progress: ScanProgress,
         tasks: Vec<tokio::task::JoinHandle<()>>
     ) -> Result<(Vec<Video>, Vec<Video>), AppError> {
         // Wait for all tasks to complete
@@ -696,48 +1216,233 @@ impl ScannerService {
 
         // Collect results
         let new_videos = {
-            let guard = new_videos_arc.lock().await;
+            let guard = progress.new_videos.lock().await;
             guard.clone()
         };
 
         let updated_videos = {
-            let guard = updated_videos_arc.lock().await;
+            let guard = progress.updated_videos.lock().await;
             guard.clone()
         };
 
-        info!("Scan complete. Found {} new videos and updated {} existing videos", new_videos.len(), updated_videos.len());
+        info!("Scan complete. Found {} new videos, updated {} existing videos and skipped {} unchanged videos",
+              new_videos.len(), updated_videos.len(), progress.skipped_count());
         Ok((new_videos, updated_videos))
     }
 
-    fn get_video_files(dir: &Path) -> Result<Vec<walkdir::DirEntry>> {
-        let mut video_files = Vec::new();
+    fn get_media_files(path_config: &MediaPathConfig) -> Result<Vec<walkdir::DirEntry>> {
+        let mut media_files = Vec::new();
+
+        // Don't descend into excluded directories
+        let entries = WalkDir::new(&path_config.path)
+            .follow_links(true)
+            .into_iter()
+            .filter_entry(|entry| !path_config.is_excluded(entry.path()))
+            .filter_map(|e| e.ok());
 
-        for entry in WalkDir::new(dir).follow_links(true).into_iter().filter_map(|e| e.ok()) {
+        for entry in entries {
             let path = entry.path();
 
-            if path.is_file() {
-                if let Some(ext) = path.extension() {
-                    let ext = ext.to_string_lossy().to_lowercase();
-                    if ["mp4", "mov", "mkv", "braw"].contains(&ext.as_str()) {
-                        video_files.push(entry);
-                    }
-                }
+            if path.is_file() && path_config.classify(path).is_some() {
+                media_files.push(entry);
             }
         }
 
-        Ok(video_files)
+        Ok(media_files)
     }
+}
 
-    pub fn is_video_file(path: &Path) -> bool {
-        if let Some(ext) = path.extension() {
-            let ext = ext.to_string_lossy().to_lowercase();
-            return ["mp4", "mov", "mkv", "braw"].contains(&ext.as_str());
+// Creation date recorded by the camera, in the first EXIF date tag that parses
+fn exif_creation_time(exif_data: &serde_json::Value) -> Option<CreationTime> {
+    // Date tags, with the tag EXIF keeps their UTC offset in
+    let tags = [
+        ("EXIF:DateTimeOriginal", Some("EXIF:OffsetTimeOriginal")),
+        ("EXIF:CreateDate", Some("EXIF:OffsetTimeDigitized")),
+        ("QuickTime:CreationDate", None),
+        ("XMP:DateCreated", None),
+        ("QuickTime:CreateDate", None),
+    ];
+    tags.iter().find_map(|(tag, offset_tag)| {
+        let date = exif_data[tag].as_str()?;
+        match offset_tag.and_then(|offset_tag| exif_data[offset_tag].as_str()) {
+            Some(offset) => parse_creation_time(&format!("{}{}", date.trim(), offset.trim())),
+            None => parse_creation_time(date),
         }
-        false
+    })
+}
+
+// Pixel size of a photo, such as "4032x3024" in Composite:ImageSize
+fn exif_image_size(exif_data: &serde_json::Value) -> Option<(i32, i32)> {
+    let size = exif_data["Composite:ImageSize"].as_str()?;
+    let (width, height) = size.split_once(['x', ' '])?;
+    Some((width.trim().parse().ok()?, height.trim().parse().ok()?))
+}
+
+// Position from the GPS tags exiftool reports. Composite tags cover EXIF, XMP and most QuickTime
+// files; some videos only have a combined coordinates tag.
+fn exif_gps_position(exif_data: &serde_json::Value) -> Option<GpsPosition> {
+    let tag = |name: &str| match &exif_data[name] {
+        serde_json::Value::String(value) => Some(value.clone()),
+        serde_json::Value::Number(value) => Some(value.to_string()),
+        _ => None,
+    };
+
+    let (latitude, longitude, altitude) = match (tag("Composite:GPSLatitude"), tag("Composite:GPSLongitude")) {
+        (Some(latitude), Some(longitude)) => (latitude, longitude, tag("Composite:GPSAltitude")),
+        _ => {
+            let coordinates = ["QuickTime:GPSCoordinates", "Keys:GPSCoordinates", "UserData:GPSCoordinates"]
+                .iter()
+                .find_map(|name| tag(name))?;
+            let mut parts = coordinates.split(',').map(|part| part.trim().to_string());
+            (parts.next()?, parts.next()?, parts.next())
+        },
+    };
+
+    let latitude = parse_gps_coordinate(&latitude).filter(|latitude| latitude.abs() <= 90.0)?;
+    let longitude = parse_gps_coordinate(&longitude).filter(|longitude| longitude.abs() <= 180.0)?;
+
+    // Cameras without a fix often write 0,0
+    if latitude == 0.0 && longitude == 0.0 {
+        return None;
     }
+
+    Some(GpsPosition {
+        latitude,
+        longitude,
+        altitude: altitude.as_deref().and_then(parse_gps_altitude),
+    })
+}
+
+// Parse a coordinate such as `37 deg 46' 30.00" N`, `37.775 N` or `-122.42` into decimal degrees
+fn parse_gps_coordinate(value: &str) -> Option<f64> {
+    let numbers: Vec<f64> = value
+        .split(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+        .filter_map(|number| number.parse().ok())
+        .collect();
+    let degrees = *numbers.first()?;
+    let minutes = numbers.get(1).copied().unwrap_or(0.0);
+    let seconds = numbers.get(2).copied().unwrap_or(0.0);
+    let magnitude = degrees.abs() + minutes / 60.0 + seconds / 3600.0;
+
+    let southern_or_western = value
+        .split_whitespace()
+        .last()
+        .is_some_and(|reference| reference.starts_with(['S', 's', 'W', 'w']));
+
+    Some(if degrees.is_sign_negative() || southern_or_western { -magnitude } else { magnitude })
 }
 
-// Utility function for Path to String conversion
-pub fn path_to_string(path: &Path) -> String {
-    path.to_string_lossy().to_string()
+// Parse an altitude such as `12.3 m`, `12.3 m Above Sea Level` or `5 m Below Sea Level`
+fn parse_gps_altitude(value: &str) -> Option<f64> {
+    let altitude: f64 = value.split_whitespace().next()?.parse().ok()?;
+    Some(if value.contains("Below") { -altitude.abs() } else { altitude })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_gps_coordinates() {
+        let cases = [
+            (r#"37 deg 46' 30.00" N"#, Some(37.775)),
+            (r#"37 deg 46' 30.00" S"#, Some(-37.775)),
+            (r#"122 deg 25' 4.80" W"#, Some(-122.418)),
+            (r#"122 deg 25' 4.80" E"#, Some(122.418)),
+            (r#"0 deg 30' 0.00" S"#, Some(-0.5)),
+            (r#"-0 deg 30' 0.00""#, Some(-0.5)),
+            ("37.775 s", Some(-37.775)),
+            ("37.775 South", Some(-37.775)),
+            ("-122.418", Some(-122.418)),
+            ("122.418", Some(122.418)),
+            ("", None),
+            ("N", None),
+        ];
+
+        for (value, expected) in cases {
+            let parsed = parse_gps_coordinate(value);
+            assert_eq!(parsed.is_some(), expected.is_some(), "{value}");
+            if let (Some(parsed), Some(expected)) = (parsed, expected) {
+                assert!((parsed - expected).abs() < 1e-9, "{value}: {parsed} != {expected}");
+            }
+        }
+    }
+
+    #[test]
+    fn reads_gps_positions_from_exif() {
+        let cases = [
+            (
+                serde_json::json!({
+                    "Composite:GPSLatitude": r#"33 deg 51' 54.00" S"#,
+                    "Composite:GPSLongitude": r#"151 deg 12' 36.00" E"#,
+                    "Composite:GPSAltitude": "5 m Below Sea Level",
+                }),
+                Some((-33.865, 151.21, Some(-5.0))),
+            ),
+            (
+                serde_json::json!({
+                    "QuickTime:GPSCoordinates": r#"37 deg 46' 30.00" N, 122 deg 25' 4.80" W, 12.3 m Above Sea Level"#,
+                }),
+                Some((37.775, -122.418, Some(12.3))),
+            ),
+            (serde_json::json!({"Keys:GPSCoordinates": "-22.9068 -43.1729"}), None),
+            (serde_json::json!({"Keys:GPSCoordinates": "-22.9068, -43.1729"}), Some((-22.9068, -43.1729, None))),
+            (serde_json::json!({"Composite:GPSLatitude": -22.9068, "Composite:GPSLongitude": -43.1729}), Some((-22.9068, -43.1729, None))),
+            (serde_json::json!({"Composite:GPSLatitude": "0 deg 0' 0.00\" N", "Composite:GPSLongitude": "0 deg 0' 0.00\" E"}), None),
+            (serde_json::json!({"Composite:GPSLatitude": "91.0", "Composite:GPSLongitude": "8.5"}), None),
+            (serde_json::json!({"Composite:GPSLatitude": "47.1"}), None),
+            (serde_json::json!({}), None),
+        ];
+
+        for (exif_data, expected) in cases {
+            let position = exif_gps_position(&exif_data);
+            assert_eq!(position.is_some(), expected.is_some(), "{exif_data}");
+            if let (Some(position), Some((latitude, longitude, altitude))) = (position, expected) {
+                assert!((position.latitude - latitude).abs() < 1e-9, "{exif_data}");
+                assert!((position.longitude - longitude).abs() < 1e-9, "{exif_data}");
+                assert_eq!(position.altitude, altitude, "{exif_data}");
+            }
+        }
+    }
+    #[test]
+    fn resolves_scan_targets() {
+        let root = std::env::temp_dir().join(format!("shoebox-targets-{}", uuid::Uuid::new_v4()));
+        for dir in ["camera/2024/trip", "camera/clips", "phone/clips", "phone/camera"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        let path = |dir: &str| root.join(dir).to_string_lossy().to_string();
+
+        let sources = [
+            MediaPathConfig { name: Some("camera".to_string()), path: path("camera"), ..MediaPathConfig::default() },
+            MediaPathConfig { path: path("phone"), ..MediaPathConfig::default() },
+        ];
+        let target = |dir: &str, root: Option<&str>| (path(dir), root.map(path));
+
+        let cases = [
+            ("name before a sub-path of the same name", vec!["camera".to_string()], Some(vec![target("camera", None)])),
+            ("relative sub-path", vec!["2024".to_string()], Some(vec![target("camera/2024", Some("camera"))])),
+            ("absolute sub-path", vec![path("camera/2024/trip")], Some(vec![target("camera/2024/trip", Some("camera"))])),
+            ("first source with the sub-path", vec!["clips".to_string()], Some(vec![target("camera/clips", Some("camera"))])),
+            ("unnamed source", vec![path("phone/clips")], Some(vec![target("phone/clips", Some("phone"))])),
+            ("duplicates", vec!["camera".to_string(), path("camera")], Some(vec![target("camera", None)])),
+            ("parent directory", vec!["2024/../..".to_string()], None),
+            ("absolute parent directory", vec![format!("{}/../phone", path("camera"))], None),
+            ("outside every source", vec![root.to_string_lossy().to_string()], None),
+            ("missing directory", vec!["2025".to_string()], None),
+            ("unknown name", vec!["tablet".to_string()], None),
+        ];
+
+        for (name, targets, expected) in cases {
+            let resolved = ScannerService::resolve_scan_targets(&sources, &targets);
+            match expected {
+                Some(expected) => {
+                    let resolved: Vec<_> = resolved.unwrap().into_iter().map(|source| (source.path, source.source_root)).collect();
+                    assert_eq!(resolved, expected, "{name}");
+                },
+                None => assert!(matches!(resolved, Err(AppError::BadRequest(_))), "{name}"),
+            }
+        }
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
 }
@@ -0,0 +1,100 @@
+use std::time::Duration;
+use tracing::{info, warn, error};
+
+use crate::config::{MediaPathConfig, ScanSchedule};
+use crate::services::{AppState, ScanOptions, ScannerService};
+
+pub struct SchedulerService;
+
+impl SchedulerService {
+    /// Start a background task for every source with a rescan schedule
+    pub fn start(state: AppState) {
+        let sources: Vec<MediaPathConfig> = state.config.media.source_paths
+            .iter()
+            .filter(|source| source.schedule.is_some())
+            .cloned()
+            .collect();
+
+        if sources.is_empty() {
+            info!("No media sources have a scan schedule");
+            return;
+        }
+
+        for source in sources {
+            let state = state.clone();
+            tokio::spawn(async move {
+                Self::run(state, source).await;
+            });
+        }
+    }
+
+    async fn run(state: AppState, source: MediaPathConfig) {
+        let source_name = source.name.clone().unwrap_or_else(|| source.path.clone());
+        info!("Scheduled scans enabled for {}: {:?}", source_name, source.schedule);
+
+        loop {
+            let Some(delay) = source.schedule.as_ref().and_then(Self::next_delay) else {
+                error!("Could not determine the next scheduled scan for {}", source_name);
+                return;
+            };
+
+            tokio::time::sleep(delay).await;
+
+            info!("Starting scheduled scan of {}", source_name);
+            match ScannerService::start_scan(
+                &state,
+                vec![source.clone()],
+                vec![source_name.clone()],
+                ScanOptions::default(),
+            ).await {
+                Ok(Some(job)) => info!("Scheduled scan of {} started as job {}", source_name, job.id),
+                Ok(None) => warn!("Skipping scheduled scan of {}: a scan is already in progress", source_name),
+                Err(e) => error!("Error starting scheduled scan of {}: {}", source_name, e),
+            }
+        }
+    }
+
+    // Time to wait until the schedule next fires
+    fn next_delay(schedule: &ScanSchedule) -> Option<Duration> {
+        Self::delay_after(schedule, &chrono::Local::now())
+    }
+
+    // Time from `now` until the schedule next fires, with cron expressions read in the time zone of `now`
+    fn delay_after<Tz: chrono::TimeZone>(schedule: &ScanSchedule, now: &chrono::DateTime<Tz>) -> Option<Duration> {
+        match schedule {
+            ScanSchedule::Interval(seconds) => Some(Duration::from_secs(*seconds)),
+            ScanSchedule::Cron(expression) => {
+                let cron = croner::Cron::new(expression).parse().ok()?;
+                let next = cron.find_next_occurrence(now, false).ok()?;
+                (next - now.clone()).to_std().ok()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn delays_until_the_next_run() {
+        // A Saturday
+        let now = chrono::Utc.with_ymd_and_hms(2025, 6, 14, 10, 30, 0).unwrap();
+        let cron = |expression: &str| ScanSchedule::Cron(expression.to_string());
+        let cases = [
+            ("interval", ScanSchedule::Interval(3_600), Some(3_600)),
+            ("daily at 3", cron("0 3 * * *"), Some(16 * 3_600 + 30 * 60)),
+            ("every quarter hour", cron("*/15 * * * *"), Some(15 * 60)),
+            ("not the current minute", cron("30 10 * * *"), Some(24 * 3_600)),
+            ("weekdays", cron("0 9 * * 1-5"), Some(46 * 3_600 + 30 * 60)),
+            ("next year", cron("0 0 1 1 *"), Some(200 * 24 * 3_600 + 13 * 3_600 + 30 * 60)),
+            ("invalid", cron("not a cron"), None),
+            ("never", cron("0 0 31 2 *"), None),
+        ];
+
+        for (name, schedule, expected) in cases {
+            assert_eq!(SchedulerService::delay_after(&schedule, &now), expected.map(Duration::from_secs), "{name}");
+        }
+    }
+}
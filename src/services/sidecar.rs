@@ -0,0 +1,484 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tokio::fs;
+use tracing::{info, warn};
+
+use crate::config::{MediaPathConfig, SidecarKind};
+use crate::error::{AppError, Result};
+use crate::models::GpsPosition;
+
+/// Largest number of telemetry points kept from an SRT track
+const MAX_TRACK_POINTS: usize = 1000;
+
+const XMP_NAMESPACE: &str = "http://ns.adobe.com/xap/1.0/";
+const DC_NAMESPACE: &str = "http://purl.org/dc/elements/1.1/";
+const RDF_NAMESPACE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+
+/// A sidecar file found next to a media file
+#[derive(Clone, Debug)]
+pub struct Sidecar {
+    pub kind: SidecarKind,
+    pub path: PathBuf,
+    pub modified: Option<SystemTime>,
+}
+
+/// Metadata read from the sidecars of a media file
+#[derive(Debug, Default)]
+pub struct SidecarMetadata {
+    pub rating: Option<i32>,
+    pub tags: Vec<String>,
+    pub position: Option<GpsPosition>,
+    /// Everything else, keyed by "Sidecar:<type>", to be stored with the EXIF data
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl SidecarMetadata {
+    // Whether there is a rating or keywords to store; positions are stored with the file's own GPS data
+    pub fn has_video_fields(&self) -> bool {
+        self.rating.is_some() || !self.tags.is_empty()
+    }
+
+    // Keep values already read from another sidecar
+    fn merge(&mut self, other: SidecarMetadata) {
+        self.rating = self.rating.or(other.rating);
+        for tag in other.tags {
+            if !self.tags.contains(&tag) {
+                self.tags.push(tag);
+            }
+        }
+        self.position = self.position.or(other.position);
+        self.extra.extend(other.extra);
+    }
+}
+
+pub struct SidecarService;
+
+impl SidecarService {
+    /// Find the sidecars of a media file that its source reads.
+    /// Sidecars share the file's stem (`clip.srt`) or its full name (`clip.mov.xmp`).
+    pub async fn discover(file_path: &Path, path_config: &MediaPathConfig) -> Vec<Sidecar> {
+        let (Some(parent), Some(stem), Some(file_name)) = (file_path.parent(), file_path.file_stem(), file_path.file_name()) else {
+            return Vec::new();
+        };
+        let stem = stem.to_string_lossy();
+        let file_name = file_name.to_string_lossy();
+
+        let mut sidecars = Vec::new();
+        for kind in SidecarKind::ALL.into_iter().filter(|kind| path_config.reads_sidecar(*kind)) {
+            let extension = kind.extension();
+            let mut names = Vec::new();
+            for base in [stem.as_ref(), file_name.as_ref()] {
+                names.push(format!("{base}.{extension}"));
+                names.push(format!("{base}.{}", extension.to_uppercase()));
+            }
+            // Google Takeout names its metadata after the full file name
+            if kind == SidecarKind::Json {
+                names.push(format!("{file_name}.supplemental-metadata.json"));
+            }
+
+            for name in names {
+                let path = parent.join(&name);
+                if let Ok(metadata) = fs::metadata(&path).await {
+                    if metadata.is_file() {
+                        sidecars.push(Sidecar { kind, path, modified: metadata.modified().ok() });
+                        break;
+                    }
+                }
+            }
+        }
+
+        sidecars
+    }
+
+    /// Read and parse sidecars, skipping the ones that can't be parsed
+    pub async fn read(sidecars: &[Sidecar]) -> SidecarMetadata {
+        let mut metadata = SidecarMetadata::default();
+
+        for sidecar in sidecars {
+            match Self::read_sidecar(sidecar).await {
+                Ok(parsed) => {
+                    info!("Read {:?} sidecar: {}", sidecar.kind, sidecar.path.display());
+                    metadata.merge(parsed);
+                },
+                Err(e) => warn!("Could not read sidecar {}: {}", sidecar.path.display(), e),
+            }
+        }
+
+        metadata
+    }
+
+    async fn read_sidecar(sidecar: &Sidecar) -> Result<SidecarMetadata> {
+        let bytes = fs::read(&sidecar.path).await?;
+        let content = String::from_utf8_lossy(&bytes);
+
+        match sidecar.kind {
+            SidecarKind::Srt => Ok(parse_srt(&content)),
+            SidecarKind::Xmp => parse_xmp(&content),
+            SidecarKind::Json => parse_json(&content),
+        }
+    }
+}
+
+// Parse DJI telemetry subtitles. Newer drones write "[latitude: 47.1] [longitude: 8.5] [rel_alt: 1.2 abs_alt: 400.3]",
+// older ones "GPS(8.5,47.1,19)" with the longitude first.
+fn parse_srt(content: &str) -> SidecarMetadata {
+    let mut metadata = SidecarMetadata::default();
+    let mut track = Vec::new();
+    let mut point_count = 0;
+    let mut max_relative_altitude: Option<f64> = None;
+    let mut current_second = None;
+    let mut last_track_second = None;
+    let mut camera = serde_json::Map::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+
+        if let Some((start, _)) = line.split_once("-->") {
+            current_second = srt_timestamp_seconds(start.trim());
+            continue;
+        }
+
+        let fix = match (labelled_value(line, "latitude"), labelled_value(line, "longitude")) {
+            (Some(latitude), Some(longitude)) => {
+                let altitude = labelled_value(line, "abs_alt").or_else(|| labelled_value(line, "altitude"));
+                Some((latitude, longitude, altitude))
+            },
+            _ => legacy_gps(line),
+        };
+        let Some((latitude, longitude, altitude)) = fix else {
+            continue;
+        };
+
+        // Drones report 0,0 until they have a fix
+        if latitude == 0.0 && longitude == 0.0 {
+            continue;
+        }
+
+        point_count += 1;
+        if let Some(relative_altitude) = labelled_value(line, "rel_alt") {
+            max_relative_altitude = Some(max_relative_altitude.map_or(relative_altitude, |max| max.max(relative_altitude)));
+        }
+
+        if metadata.position.is_none() {
+            metadata.position = Some(GpsPosition { latitude, longitude, altitude });
+
+            for key in ["iso", "shutter", "fnum", "ev", "ct", "focal_len"] {
+                if let Some(value) = labelled_text(line, key) {
+                    camera.insert(key.to_string(), serde_json::Value::String(value));
+                }
+            }
+        }
+
+        // Keep one point per second of footage
+        if track.len() < MAX_TRACK_POINTS && (current_second.is_none() || current_second != last_track_second) {
+            last_track_second = current_second;
+            track.push(serde_json::json!([latitude, longitude, altitude]));
+        }
+    }
+
+    if point_count > 0 {
+        let mut summary = serde_json::json!({
+            "Points": point_count,
+            "Track": track,
+        });
+        if let Some(max_relative_altitude) = max_relative_altitude {
+            summary["MaxRelativeAltitude"] = serde_json::json!(max_relative_altitude);
+        }
+        if !camera.is_empty() {
+            summary["Camera"] = serde_json::Value::Object(camera);
+        }
+        metadata.extra.insert("Sidecar:SRT".to_string(), summary);
+    }
+
+    metadata
+}
+
+// Seconds part of an SRT timestamp such as "00:01:02,500"
+fn srt_timestamp_seconds(timestamp: &str) -> Option<u64> {
+    let time = timestamp.split([',', '.']).next()?;
+    let mut seconds = 0;
+    for part in time.split(':') {
+        seconds = seconds * 60 + part.trim().parse::<u64>().ok()?;
+    }
+    Some(seconds)
+}
+
+// Text following "<label>:" up to the next whitespace or bracket
+fn labelled_text(line: &str, label: &str) -> Option<String> {
+    let mut search_from = 0;
+    while let Some(found) = line[search_from..].find(label) {
+        let start = search_from + found;
+        search_from = start + label.len();
+
+        // Don't match "alt" inside "rel_alt"
+        let preceded_by_word = line[..start].chars().next_back().is_some_and(|c| c.is_alphanumeric() || c == '_');
+        let rest = line[search_from..].trim_start();
+        if preceded_by_word || !rest.starts_with(':') {
+            continue;
+        }
+
+        let value: String = rest[1..]
+            .trim_start()
+            .chars()
+            .take_while(|c| !c.is_whitespace() && *c != ']' && *c != ',')
+            .collect();
+        if !value.is_empty() {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn labelled_value(line: &str, label: &str) -> Option<f64> {
+    labelled_text(line, label)?.parse().ok()
+}
+
+// "GPS(longitude,latitude,altitude)" as written by older DJI drones
+fn legacy_gps(line: &str) -> Option<(f64, f64, Option<f64>)> {
+    let start = line.find("GPS")?;
+    let rest = line[start + 3..].trim_start().strip_prefix('(')?;
+    let values: Vec<f64> = rest[..rest.find(')')?]
+        .split(',')
+        .filter_map(|value| value.trim().parse().ok())
+        .collect();
+
+    match values.as_slice() {
+        [longitude, latitude, altitude, ..] => Some((*latitude, *longitude, Some(*altitude))),
+        [longitude, latitude] => Some((*latitude, *longitude, None)),
+        _ => None,
+    }
+}
+
+// Parse an XMP packet. xmp:Rating becomes the rating, dc:subject the tags,
+// and other simple properties are kept as "prefix:Name" values.
+fn parse_xmp(content: &str) -> Result<SidecarMetadata> {
+    let document = roxmltree::Document::parse(content)
+        .map_err(|e| AppError::Other(anyhow::anyhow!("Invalid XMP: {e}")))?;
+
+    let mut metadata = SidecarMetadata::default();
+    let mut properties = serde_json::Map::new();
+
+    for description in document
+        .descendants()
+        .filter(|node| node.has_tag_name((RDF_NAMESPACE, "Description")))
+    {
+        // Properties can be written as attributes...
+        for attribute in description.attributes() {
+            let Some(namespace) = attribute.namespace() else {
+                continue;
+            };
+            if namespace == RDF_NAMESPACE {
+                continue;
+            }
+            if namespace == XMP_NAMESPACE && attribute.name() == "Rating" {
+                metadata.rating = parse_rating(attribute.value());
+            }
+            properties.insert(property_name(&description, namespace, attribute.name()), attribute.value().into());
+        }
+
+        // ...or as child elements
+        for property in description.children().filter(|node| node.is_element()) {
+            let namespace = property.tag_name().namespace().unwrap_or_default();
+            let name = property.tag_name().name();
+
+            if namespace == DC_NAMESPACE && name == "subject" {
+                for item in property.descendants().filter(|node| node.has_tag_name((RDF_NAMESPACE, "li"))) {
+                    if let Some(tag) = item.text().map(str::trim).filter(|tag| !tag.is_empty()) {
+                        if !metadata.tags.iter().any(|existing| existing == tag) {
+                            metadata.tags.push(tag.to_string());
+                        }
+                    }
+                }
+                continue;
+            }
+
+            // Only simple values; structures and lists other than keywords are skipped
+            if property.children().any(|node| node.is_element()) {
+                continue;
+            }
+            let Some(value) = property.text().map(str::trim).filter(|value| !value.is_empty()) else {
+                continue;
+            };
+            if namespace == XMP_NAMESPACE && name == "Rating" {
+                metadata.rating = parse_rating(value);
+            }
+            properties.insert(property_name(&property, namespace, name), value.into());
+        }
+    }
+
+    if !properties.is_empty() {
+        metadata.extra.insert("Sidecar:XMP".to_string(), serde_json::Value::Object(properties));
+    }
+
+    Ok(metadata)
+}
+
+// Prefix a property with its namespace prefix, e.g. "xmp:Label"
+fn property_name(node: &roxmltree::Node, namespace: &str, name: &str) -> String {
+    match node.lookup_prefix(namespace) {
+        Some(prefix) => format!("{prefix}:{name}"),
+        None => name.to_string(),
+    }
+}
+
+// XMP ratings run from -1 (rejected) to 5; only stars are kept
+fn parse_rating(value: &str) -> Option<i32> {
+    let rating = value.trim().parse::<f64>().ok()?.round() as i32;
+    (1..=5).contains(&rating).then_some(rating)
+}
+
+// Parse Google Takeout metadata. The geoData position is used when set, everything is kept as is.
+fn parse_json(content: &str) -> Result<SidecarMetadata> {
+    let json: serde_json::Value = serde_json::from_str(content)
+        .map_err(|e| AppError::Other(anyhow::anyhow!("Invalid JSON sidecar: {e}")))?;
+    if !json.is_object() {
+        return Err(AppError::Other(anyhow::anyhow!("JSON sidecar is not an object")));
+    }
+
+    let mut metadata = SidecarMetadata::default();
+
+    // Takeout writes 0,0 when the position is unknown
+    for geo_data in [&json["geoData"], &json["geoDataExif"]] {
+        let latitude = geo_data["latitude"].as_f64().unwrap_or(0.0);
+        let longitude = geo_data["longitude"].as_f64().unwrap_or(0.0);
+        if latitude != 0.0 || longitude != 0.0 {
+            metadata.position = Some(GpsPosition {
+                latitude,
+                longitude,
+                altitude: geo_data["altitude"].as_f64().filter(|altitude| *altitude != 0.0),
+            });
+            break;
+        }
+    }
+
+    metadata.extra.insert("Sidecar:JSON".to_string(), json);
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Position = Option<(f64, f64, Option<f64>)>;
+
+    fn position(metadata: &SidecarMetadata) -> Position {
+        metadata.position.as_ref().map(|position| (position.latitude, position.longitude, position.altitude))
+    }
+
+    #[test]
+    fn parses_srt_telemetry() {
+        let cases: [(&str, &str, Position, usize); 7] = [
+            (
+                "labelled",
+                "1\n00:00:00,000 --> 00:00:00,033\n[iso : 100] [latitude: 47.1] [longitude: 8.5] [rel_alt: 1.2 abs_alt: 400.3]\n",
+                Some((47.1, 8.5, Some(400.3))),
+                1,
+            ),
+            (
+                "legacy longitude first",
+                "1\n00:00:00,000 --> 00:00:01,000\nGPS(8.5,47.1,19) BAROMETER:1.2\n",
+                Some((47.1, 8.5, Some(19.0))),
+                1,
+            ),
+            ("legacy without altitude", "GPS(8.5,47.1)\n", Some((47.1, 8.5, None)), 1),
+            ("no fix yet", "[latitude: 0.000000] [longitude: 0.000000]\n[latitude: -33.9] [longitude: 151.2]\n", Some((-33.9, 151.2, None)), 1),
+            ("missing longitude", "[latitude: 47.1] [rel_alt: 1.2]\n", None, 0),
+            ("unparseable values", "[latitude: north] [longitude: east]\nGPS(a,b,c)\n", None, 0),
+            ("empty", "", None, 0),
+        ];
+
+        for (name, content, expected, points) in cases {
+            let metadata = parse_srt(content);
+            assert_eq!(position(&metadata), expected, "{name}");
+            assert_eq!(metadata.extra.get("Sidecar:SRT").map_or(0, |srt| srt["Points"].as_u64().unwrap() as usize), points, "{name}");
+            assert!(!metadata.has_video_fields(), "{name}");
+        }
+    }
+
+    #[test]
+    fn keeps_one_srt_track_point_per_second() {
+        let content = "1\n00:00:00,000 --> 00:00:00,033\n[latitude: 47.1] [longitude: 8.5]\n\n\
+                       2\n00:00:00,033 --> 00:00:00,066\n[latitude: 47.2] [longitude: 8.5]\n\n\
+                       3\n00:00:01,000 --> 00:00:01,033\n[latitude: 47.3] [longitude: 8.5] [rel_alt: 5.0 abs_alt: 400]\n";
+
+        let srt = &parse_srt(content).extra["Sidecar:SRT"];
+        assert_eq!(srt["Points"], 3);
+        assert_eq!(srt["Track"].as_array().unwrap().len(), 2);
+        assert_eq!(srt["MaxRelativeAltitude"], 5.0);
+    }
+
+    #[test]
+    fn parses_xmp_ratings() {
+        let cases = [
+            ("5", Some(5)),
+            ("1", Some(1)),
+            ("3.6", Some(4)),
+            (" 2 ", Some(2)),
+            ("0", None),
+            ("-1", None),
+            ("6", None),
+            ("", None),
+            ("five", None),
+            ("NaN", None),
+        ];
+
+        for (value, expected) in cases {
+            let attribute = format!(
+                r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="{RDF_NAMESPACE}"><rdf:Description xmlns:xmp="{XMP_NAMESPACE}" xmp:Rating="{value}"/></rdf:RDF></x:xmpmeta>"#
+            );
+            let element = format!(
+                r#"<rdf:RDF xmlns:rdf="{RDF_NAMESPACE}"><rdf:Description xmlns:xmp="{XMP_NAMESPACE}"><xmp:Rating>{value}</xmp:Rating></rdf:Description></rdf:RDF>"#
+            );
+
+            assert_eq!(parse_xmp(&attribute).unwrap().rating, expected, "attribute {value:?}");
+            assert_eq!(parse_xmp(&element).unwrap().rating, expected, "element {value:?}");
+        }
+    }
+
+    #[test]
+    fn parses_xmp_keywords_and_properties() {
+        let content = format!(
+            r#"<rdf:RDF xmlns:rdf="{RDF_NAMESPACE}">
+                 <rdf:Description xmlns:dc="{DC_NAMESPACE}" xmlns:xmp="{XMP_NAMESPACE}" xmp:Label="Red">
+                   <dc:subject><rdf:Bag><rdf:li>Beach</rdf:li><rdf:li> </rdf:li><rdf:li>Beach</rdf:li><rdf:li>Sunset</rdf:li></rdf:Bag></dc:subject>
+                   <xmp:CreatorTool> Resolve </xmp:CreatorTool>
+                   <xmp:Empty></xmp:Empty>
+                   <xmp:Nested><rdf:Seq><rdf:li>a</rdf:li></rdf:Seq></xmp:Nested>
+                 </rdf:Description>
+               </rdf:RDF>"#
+        );
+
+        let metadata = parse_xmp(&content).unwrap();
+        assert_eq!(metadata.tags, ["Beach", "Sunset"]);
+        assert_eq!(metadata.rating, None);
+        assert_eq!(metadata.extra["Sidecar:XMP"], serde_json::json!({"xmp:Label": "Red", "xmp:CreatorTool": "Resolve"}));
+
+        assert!(parse_xmp("<rdf:RDF").is_err());
+        assert!(parse_xmp("<empty/>").unwrap().extra.is_empty());
+    }
+
+    #[test]
+    fn parses_takeout_json() {
+        let cases: [(&str, &str, Position); 6] = [
+            ("position", r#"{"geoData": {"latitude": 47.1, "longitude": 8.5, "altitude": 400.0}}"#, Some((47.1, 8.5, Some(400.0)))),
+            ("zero altitude", r#"{"geoData": {"latitude": -33.9, "longitude": 151.2, "altitude": 0.0}}"#, Some((-33.9, 151.2, None))),
+            (
+                "unknown position falls back to EXIF",
+                r#"{"geoData": {"latitude": 0.0, "longitude": 0.0}, "geoDataExif": {"latitude": 47.1, "longitude": 8.5}}"#,
+                Some((47.1, 8.5, None)),
+            ),
+            ("unknown position", r#"{"geoData": {"latitude": 0.0, "longitude": 0.0, "altitude": 0.0}}"#, None),
+            ("text coordinates", r#"{"geoData": {"latitude": "47.1", "longitude": "8.5"}}"#, None),
+            ("no position", r#"{"title": "clip.mp4"}"#, None),
+        ];
+
+        for (name, content, expected) in cases {
+            let metadata = parse_json(content).unwrap();
+            assert_eq!(position(&metadata), expected, "{name}");
+            assert!(metadata.extra.contains_key("Sidecar:JSON"), "{name}");
+        }
+
+        assert!(parse_json("[1, 2]").is_err());
+        assert!(parse_json("{").is_err());
+    }
+}
@@ -1,15 +1,92 @@
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use tokio::process::Command;
 use tokio::fs;
-use uuid::Uuid;
-use tracing::{info, error};
+use tokio::sync::OnceCell;
+use tracing::{debug, info, warn, error};
 
 use crate::error::{AppError, Result};
-use crate::config::Config;
+use crate::config::{Config, PreviewFormat, ThumbnailFormat, ThumbnailPosition, ThumbnailSize};
+use crate::utils::{ensure_dir_exists, get_file_extension};
+
+/// Photo formats FFmpeg can decode directly
+const FFMPEG_PHOTO_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "tif", "tiff", "webp"];
+
+/// Largest width of a photo thumbnail
+const PHOTO_THUMBNAIL_WIDTH: u32 = 1280;
+
+/// Candidate frames scored when picking a thumbnail from across a whole video
+const AUTO_CANDIDATES: usize = 8;
+
+/// Candidate frames scored from a chosen position, one second apart
+const POSITION_CANDIDATES: usize = 5;
+
+/// Size of the grayscale copies of candidate frames that are scored
+const SCORE_WIDTH: usize = 160;
+const SCORE_HEIGHT: usize = 90;
+
+/// Frames darker or brighter than this on average are mostly black or blown out
+const MIN_BRIGHTNESS: f64 = 24.0;
+const MAX_BRIGHTNESS: f64 = 235.0;
+
+/// Frames with less brightness variation than this are flat, such as a lens cap or a wall
+const MIN_CONTRAST: f64 = 12.0;
+
+/// Size of each frame in a sprite sheet. Frames are letterboxed to fit.
+const SPRITE_FRAME_WIDTH: u32 = 160;
+const SPRITE_FRAME_HEIGHT: u32 = 90;
+
+/// Frames per row of a sprite sheet
+const SPRITE_COLUMNS: u32 = 10;
+
+/// Hover previews are stitched from this many segments spread across the video
+const PREVIEW_SEGMENTS: u32 = 4;
+const PREVIEW_SEGMENT_SECONDS: f64 = 1.0;
+
+/// Width and frame rate of hover previews
+const PREVIEW_WIDTH: u32 = 320;
+const PREVIEW_FRAME_RATE: u32 = 24;
+
+/// Size name of the thumbnail as generated, without scaling or converting it
+pub const ORIGINAL_THUMBNAIL_SIZE: &str = "original";
+
+/// Encoders and muxers of the installed FFmpeg, read once
+static FFMPEG_IMAGE_SUPPORT: OnceCell<FfmpegImageSupport> = OnceCell::const_new();
+
+#[derive(Debug, Default)]
+struct FfmpegImageSupport {
+    webp: bool,
+    /// AV1 encoder to write AVIF images with, when FFmpeg has one and can write AVIF files
+    avif_encoder: Option<&'static str>,
+}
+
+impl FfmpegImageSupport {
+    /// The format to write instead of one FFmpeg can't write, which is JPEG
+    fn writable(&self, format: ThumbnailFormat) -> ThumbnailFormat {
+        match format {
+            ThumbnailFormat::Webp if !self.webp => ThumbnailFormat::Jpeg,
+            ThumbnailFormat::Avif if self.avif_encoder.is_none() => ThumbnailFormat::Jpeg,
+            format => format,
+        }
+    }
+}
+
+/// A thumbnail file in the size and format a client asked for
+#[derive(Debug)]
+pub struct ThumbnailVariant {
+    pub path: PathBuf,
+    pub format: ThumbnailFormat,
+    /// Changes whenever the content does, for use as a strong ETag
+    pub tag: String,
+}
 
 pub struct ThumbnailService {
     thumbnail_dir: PathBuf,
     web_path: String,
+    sprite_frames: u32,
+    sizes: Vec<ThumbnailSize>,
+    format: ThumbnailFormat,
+    previews: bool,
+    preview_format: PreviewFormat,
 }
 
 impl ThumbnailService {
@@ -17,39 +94,439 @@ impl ThumbnailService {
         let thumbnail_dir = PathBuf::from(&config.media.thumbnail_path);
         Self {
             thumbnail_dir,
-            web_path: "/app/thumbnails".to_string()
+            web_path: "/app/thumbnails".to_string(),
+            sprite_frames: config.media.sprite_frames,
+            sizes: config.media.thumbnail_sizes.clone(),
+            format: config.media.thumbnail_format,
+            previews: config.media.scan_previews,
+            preview_format: config.media.preview_format,
         }
     }
 
-    pub async fn generate_thumbnail(&self, video_path: &str) -> Result<String> {
-        // Ensure thumbnail directory exists
-        if !self.thumbnail_dir.exists() {
-            fs::create_dir_all(&self.thumbnail_dir).await.map_err(|e| {
-                AppError::Io(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!("Failed to create thumbnail directory: {e}"),
-                ))
-            })?;
+    /// File of a stored thumbnail path, which may be given under the web path as the API returns it
+    pub fn resolve_path(&self, thumbnail_path: &str) -> PathBuf {
+        match Path::new(thumbnail_path).strip_prefix(&self.web_path) {
+            Ok(file_name) => self.thumbnail_dir.join(file_name),
+            Err(_) => PathBuf::from(thumbnail_path),
         }
+    }
 
-        // Generate a unique filename for the thumbnail
-        let thumbnail_filename = format!("{0}.jpg", Uuid::new_v4());
-        let thumbnail_path = self.thumbnail_dir.join(&thumbnail_filename);
-        let thumbnail_path_str = thumbnail_path.to_string_lossy().to_string();
+    /// Directory the thumbnails are written to
+    pub fn directory(&self) -> &Path {
+        &self.thumbnail_dir
+    }
+
+    /// Thumbnail of a video, named after the video so regenerating it replaces the file
+    pub fn thumbnail_path(&self, video_id: &str) -> PathBuf {
+        self.thumbnail_dir.join(format!("{video_id}.jpg"))
+    }
+
+    /// Generate a thumbnail for a video from the best looking frame around `position`
+    pub async fn generate_thumbnail(&self, video_id: &str, video_path: &str, duration_ms: Option<i64>, position: ThumbnailPosition) -> Result<String> {
+        ensure_dir_exists(&self.thumbnail_dir).await?;
 
-        info!("Generating thumbnail for {video_path} at {thumbnail_path_str}");
+        let thumbnail_path = self.thumbnail_path(video_id);
+        info!("Generating thumbnail for {video_path} at {}", thumbnail_path.display());
+
+        let time = Self::pick_frame(video_path, duration_ms, position).await;
+        Self::extract_frame(video_path, time, &thumbnail_path).await?;
+
+        Ok(thumbnail_path.to_string_lossy().to_string())
+    }
 
-        // Use FFmpeg to extract the first keyframe
+    /// Generate a thumbnail for a video from the frame at a time, in seconds
+    pub async fn generate_thumbnail_at(&self, video_id: &str, video_path: &str, time: f64) -> Result<String> {
+        ensure_dir_exists(&self.thumbnail_dir).await?;
+
+        let thumbnail_path = self.thumbnail_path(video_id);
+        info!("Generating thumbnail for {video_path} at {time}s in {}", thumbnail_path.display());
+
+        Self::extract_frame(video_path, Some(time), &thumbnail_path).await?;
+        Ok(thumbnail_path.to_string_lossy().to_string())
+    }
+
+    /// Copy another video's thumbnail to be this video's, for originals that share their proxy's thumbnail
+    pub async fn copy_thumbnail(&self, video_id: &str, source_path: &str) -> Result<String> {
+        ensure_dir_exists(&self.thumbnail_dir).await?;
+
+        let thumbnail_path = self.thumbnail_path(video_id);
+        let partial_path = partial_path(&thumbnail_path);
+        fs::copy(self.resolve_path(source_path), &partial_path).await.map_err(AppError::Io)?;
+        fs::rename(&partial_path, &thumbnail_path).await.map_err(AppError::Io)?;
+
+        Ok(thumbnail_path.to_string_lossy().to_string())
+    }
+
+    // Time of the best scoring candidate frame, or `None` for the first frame
+    async fn pick_frame(video_path: &str, duration_ms: Option<i64>, position: ThumbnailPosition) -> Option<f64> {
+        let mut best: Option<(f64, FrameScore)> = None;
+        let mut best_usable: Option<(f64, FrameScore)> = None;
+
+        for time in candidate_times(duration_ms, position) {
+            let Some(score) = Self::score_frame(video_path, time).await else {
+                continue;
+            };
+
+            let slot = if score.is_usable() { &mut best_usable } else { &mut best };
+            if slot.as_ref().is_none_or(|(_, best)| score.value() > best.value()) {
+                *slot = Some((time, score));
+            }
+        }
+
+        // Fall back to the best of the dark or flat frames when none of them look right
+        let (time, score) = best_usable.or(best)?;
+        debug!("Picked frame at {time:.3}s of {video_path}: {score:?}");
+        Some(time)
+    }
+
+    // Measure a small grayscale copy of the frame at a time, or `None` if it can't be decoded
+    async fn score_frame(video_path: &str, time: f64) -> Option<FrameScore> {
         let output = Command::new("ffmpeg")
+            .arg("-ss")
+            .arg(format!("{time:.3}"))
             .arg("-i")
             .arg(video_path)
+            .arg("-an")
+            .arg("-sn")
             .arg("-vf")
-            .arg("select=eq(n\\,0)")
-            .arg("-vframes")
+            .arg(format!("scale={SCORE_WIDTH}:{SCORE_HEIGHT},format=gray"))
+            .arg("-frames:v")
+            .arg("1")
+            .arg("-f")
+            .arg("rawvideo")
+            .arg("-")
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| warn!("Failed to run FFmpeg for {video_path}: {e}"))
+            .ok()?;
+
+        if !output.status.success() || output.stdout.len() != SCORE_WIDTH * SCORE_HEIGHT {
+            return None;
+        }
+
+        Some(FrameScore::measure(&output.stdout, SCORE_WIDTH))
+    }
+
+    // Write one frame of a video as a JPEG, taking the first frame when there is no time
+    async fn extract_frame(video_path: &str, time: Option<f64>, thumbnail_path: &Path) -> Result<()> {
+        let partial_path = partial_path(thumbnail_path);
+        let mut command = Command::new("ffmpeg");
+        match time {
+            // Seek before opening the input so only the frames after the nearest keyframe are decoded
+            Some(time) => command.arg("-ss").arg(format!("{time:.3}")).arg("-i").arg(video_path),
+            None => command.arg("-i").arg(video_path).arg("-vf").arg("select=eq(n\\,0)"),
+        };
+
+        let output = command
+            .arg("-frames:v")
             .arg("1")
             .arg("-y") // Overwrite output file if it exists
-            .arg(&thumbnail_path_str)
+            .arg(&partial_path)
+            .kill_on_drop(true) // Stop FFmpeg if the scan is cancelled
+            .output()
+            .await
+            .map_err(|e| {
+                error!("FFmpeg command failed: {e}");
+                AppError::FFmpeg(format!("Failed to execute FFmpeg: {e}"))
+            })?;
+
+        finish_partial(&output, &partial_path, thumbnail_path).await
+    }
+
+    /// Generate a thumbnail for a still image. RAW and HEIC files use the preview
+    /// image embedded by the camera, since FFmpeg can't decode them.
+    pub async fn generate_photo_thumbnail(&self, video_id: &str, photo_path: &str) -> Result<String> {
+        ensure_dir_exists(&self.thumbnail_dir).await?;
+
+        let thumbnail_path = self.thumbnail_path(video_id);
+        let partial_path = partial_path(&thumbnail_path);
+        let thumbnail_path_str = thumbnail_path.to_string_lossy().to_string();
+
+        info!("Generating photo thumbnail for {photo_path} at {thumbnail_path_str}");
+
+        let extension = get_file_extension(Path::new(photo_path)).unwrap_or_default();
+        let preview_path = if FFMPEG_PHOTO_EXTENSIONS.contains(&extension.as_str()) {
+            None
+        } else {
+            let preview_path = self.thumbnail_dir.join(format!("{video_id}.preview.jpg"));
+            Self::extract_preview_image(photo_path, &preview_path).await.then_some(preview_path)
+        };
+
+        let source = preview_path
+            .as_ref()
+            .map(|path| path.to_string_lossy().to_string())
+            .unwrap_or_else(|| photo_path.to_string());
+
+        // Scale large photos down to thumbnail size
+        let output = Command::new("ffmpeg")
+            .arg("-i")
+            .arg(&source)
+            .arg("-vf")
+            .arg(format!("scale='min({PHOTO_THUMBNAIL_WIDTH},iw)':-2"))
+            .arg("-frames:v")
+            .arg("1")
+            .arg("-y")
+            .arg(&partial_path)
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| {
+                error!("FFmpeg command failed: {e}");
+                AppError::FFmpeg(format!("Failed to execute FFmpeg: {e}"))
+            });
+
+        if let Some(preview_path) = &preview_path {
+            let _ = fs::remove_file(preview_path).await;
+        }
+
+        finish_partial(&output?, &partial_path, &thumbnail_path).await?;
+        Ok(thumbnail_path_str)
+    }
+
+    /// Directory of the scaled thumbnails
+    pub fn variant_directory(&self) -> PathBuf {
+        self.thumbnail_dir.join("variants")
+    }
+
+    /// A video's thumbnail in a configured size, scaled and encoded on first use. `version` identifies
+    /// the content of the thumbnail at `source_path`, so variants of an older thumbnail are never served.
+    /// Formats FFmpeg can't write fall back to JPEG.
+    pub async fn variant(
+        &self,
+        video_id: &str,
+        source_path: &Path,
+        version: &str,
+        size: &str,
+        format: Option<ThumbnailFormat>,
+    ) -> Result<ThumbnailVariant> {
+        let size = size.trim().to_lowercase();
+        if size == ORIGINAL_THUMBNAIL_SIZE {
+            return Ok(ThumbnailVariant {
+                path: source_path.to_path_buf(),
+                format: ThumbnailFormat::Jpeg,
+                tag: format!("{version}-{ORIGINAL_THUMBNAIL_SIZE}"),
+            });
+        }
+
+        let Some(width) = self.sizes.iter().find(|candidate| candidate.name == size).map(|size| size.width) else {
+            let names: Vec<&str> = self.sizes.iter().map(|size| size.name.as_str()).collect();
+            return Err(AppError::BadRequest(format!(
+                "Unknown thumbnail size '{size}', expected one of: {}, {ORIGINAL_THUMBNAIL_SIZE}",
+                names.join(", ")
+            )));
+        };
+
+        let support = ffmpeg_image_support().await;
+        let format = support.writable(format.unwrap_or(self.format));
+
+        let tag = format!("{version}-{size}-{}", format.extension());
+        let path = self.variant_directory().join(format!("{video_id}-{version}-{size}.{}", format.extension()));
+        if path.exists() {
+            return Ok(ThumbnailVariant { path, format, tag });
+        }
+
+        ensure_dir_exists(&self.variant_directory()).await?;
+        info!("Generating {size} {} thumbnail at {}", format.extension(), path.display());
+
+        let partial_path = partial_path(&path);
+        let mut command = Command::new("ffmpeg");
+        command
+            .arg("-i")
+            .arg(source_path)
+            .arg("-vf")
+            .arg(format!("scale='min({width},iw)':-2"))
+            .arg("-frames:v")
+            .arg("1");
+
+        match format {
+            ThumbnailFormat::Jpeg => command.args(["-q:v", "3"]),
+            ThumbnailFormat::Webp => command.args(["-c:v", "libwebp", "-quality", "80"]),
+            ThumbnailFormat::Avif => match support.avif_encoder {
+                Some("libsvtav1") => command.args(["-c:v", "libsvtav1", "-crf", "35", "-pix_fmt", "yuv420p"]),
+                _ => command.args(["-c:v", "libaom-av1", "-still-picture", "1", "-crf", "32", "-b:v", "0", "-cpu-used", "6", "-pix_fmt", "yuv420p"]),
+            },
+        };
+
+        let output = command
+            .arg("-y")
+            .arg(&partial_path)
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| {
+                error!("FFmpeg command failed: {e}");
+                AppError::FFmpeg(format!("Failed to execute FFmpeg: {e}"))
+            })?;
+
+        finish_partial(&output, &partial_path, &path).await?;
+        Ok(ThumbnailVariant { path, format, tag })
+    }
+
+    /// Remove the scaled thumbnails of a video
+    pub async fn delete_variants(&self, video_id: &str) -> Result<()> {
+        let mut entries = match fs::read_dir(self.variant_directory()).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(AppError::Io(e)),
+        };
+
+        let prefix = format!("{video_id}-");
+        while let Some(entry) = entries.next_entry().await.map_err(AppError::Io)? {
+            if entry.file_name().to_string_lossy().starts_with(&prefix) {
+                fs::remove_file(entry.path()).await.map_err(AppError::Io)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Hover preview of a video in the configured format, named after the video
+    pub fn preview_path(&self, video_id: &str) -> PathBuf {
+        self.thumbnail_dir.join("previews").join(format!("{video_id}.{}", self.preview_format.extension()))
+    }
+
+    pub fn preview_format(&self) -> PreviewFormat {
+        self.preview_format
+    }
+
+    /// Generate a short silent preview of a video, stitched from segments spread across it so it loops
+    /// through the whole clip. Returns whether a preview was written; videos without a duration are
+    /// skipped, as is everything unless previews are turned on.
+    pub async fn generate_preview(&self, video_id: &str, video_path: &str, duration_ms: Option<i64>) -> Result<bool> {
+        let Some(duration) = duration_ms.filter(|duration| *duration > 0).map(|duration| duration as f64 / 1000.0) else {
+            return Ok(false);
+        };
+        if !self.previews {
+            return Ok(false);
+        }
+
+        let preview_path = self.preview_path(video_id);
+        if let Some(preview_dir) = preview_path.parent() {
+            ensure_dir_exists(preview_dir).await?;
+        }
+
+        info!("Generating preview for {video_path} at {}", preview_path.display());
+
+        let mut command = Command::new("ffmpeg");
+        let segments = preview_segments(duration);
+        for (start, length) in &segments {
+            // Seeking each input separately only decodes the frames that are used
+            command
+                .arg("-ss")
+                .arg(format!("{start:.3}"))
+                .arg("-t")
+                .arg(format!("{length:.3}"))
+                .arg("-i")
+                .arg(video_path);
+        }
+
+        let mut filter = String::new();
+        for index in 0..segments.len() {
+            filter.push_str(&format!(
+                "[{index}:v]fps={PREVIEW_FRAME_RATE},scale={PREVIEW_WIDTH}:-2,setsar=1,format=yuv420p[v{index}];"
+            ));
+        }
+        for index in 0..segments.len() {
+            filter.push_str(&format!("[v{index}]"));
+        }
+        filter.push_str(&format!("concat=n={}:v=1:a=0[preview]", segments.len()));
+
+        command
+            .arg("-filter_complex")
+            .arg(&filter)
+            .arg("-map")
+            .arg("[preview]")
+            .arg("-an");
+
+        match self.preview_format {
+            PreviewFormat::Mp4 => command.args(["-c:v", "libx264", "-preset", "veryfast", "-crf", "28", "-movflags", "+faststart"]),
+            PreviewFormat::Webm => command.args(["-c:v", "libvpx-vp9", "-b:v", "0", "-crf", "40", "-deadline", "good", "-cpu-used", "5"]),
+        };
+
+        let partial_path = partial_path(&preview_path);
+        let output = command
+            .arg("-y")
+            .arg(&partial_path)
+            .kill_on_drop(true)
             .output()
+            .await
+            .map_err(|e| {
+                error!("FFmpeg command failed: {e}");
+                AppError::FFmpeg(format!("Failed to execute FFmpeg: {e}"))
+            })?;
+
+        finish_partial(&output, &partial_path, &preview_path).await?;
+        Ok(true)
+    }
+
+    /// Remove the hover previews of a video, in any format
+    pub async fn delete_preview(&self, video_id: &str) -> Result<()> {
+        for format in [PreviewFormat::Mp4, PreviewFormat::Webm] {
+            let path = self.thumbnail_dir.join("previews").join(format!("{video_id}.{}", format.extension()));
+            if path.exists() {
+                fs::remove_file(&path).await.map_err(AppError::Io)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sprite sheet image of a video, named after the video
+    pub fn sprite_sheet_path(&self, video_id: &str) -> PathBuf {
+        self.thumbnail_dir.join("sprites").join(format!("{video_id}.jpg"))
+    }
+
+    /// WebVTT thumbnails track pointing into the sprite sheet of a video
+    pub fn sprite_track_path(&self, video_id: &str) -> PathBuf {
+        self.thumbnail_dir.join("sprites").join(format!("{video_id}.vtt"))
+    }
+
+    /// Generate a sprite sheet of evenly spaced frames and a WebVTT track with the region of the
+    /// sheet to show for each stretch of the video. Returns whether a sheet was written; videos
+    /// without a duration and sprite sheets disabled by a frame count of 0 are skipped.
+    pub async fn generate_sprite_sheet(&self, video_id: &str, video_path: &str, duration_ms: Option<i64>) -> Result<bool> {
+        let Some(duration_ms) = duration_ms.filter(|duration| *duration > 0) else {
+            return Ok(false);
+        };
+        if self.sprite_frames == 0 {
+            return Ok(false);
+        }
+
+        // At most one frame per second, so short clips don't repeat frames
+        let frame_count = self.sprite_frames.min((duration_ms / 1000).max(1) as u32);
+        let columns = frame_count.min(SPRITE_COLUMNS);
+        let rows = frame_count.div_ceil(columns);
+
+        let sprite_path = self.sprite_sheet_path(video_id);
+        let track_path = self.sprite_track_path(video_id);
+        if let Some(sprite_dir) = sprite_path.parent() {
+            ensure_dir_exists(sprite_dir).await?;
+        }
+
+        info!("Generating {frame_count} frame sprite sheet for {video_path} at {}", sprite_path.display());
+
+        // Pick frames at a fixed rate so they cover the whole video, then tile them into one image
+        let filter = format!(
+            "fps={}/{duration_ms},scale={SPRITE_FRAME_WIDTH}:{SPRITE_FRAME_HEIGHT}:force_original_aspect_ratio=decrease,\
+             pad={SPRITE_FRAME_WIDTH}:{SPRITE_FRAME_HEIGHT}:(ow-iw)/2:(oh-ih)/2,tile={columns}x{rows}",
+            u64::from(frame_count) * 1000,
+        );
+
+        let output = Command::new("ffmpeg")
+            .arg("-i")
+            .arg(video_path)
+            .arg("-an")
+            .arg("-sn")
+            .arg("-vf")
+            .arg(&filter)
+            .arg("-frames:v")
+            .arg("1")
+            .arg("-y")
+            .arg(&sprite_path)
+            .kill_on_drop(true)
+            .output()
+            .await
             .map_err(|e| {
                 error!("FFmpeg command failed: {e}");
                 AppError::FFmpeg(format!("Failed to execute FFmpeg: {e}"))
@@ -61,23 +538,404 @@ impl ThumbnailService {
             return Err(AppError::FFmpeg(format!("FFmpeg error: {stderr}")));
         }
 
-        // Check if thumbnail was created
-        if !thumbnail_path.exists() {
-            return Err(AppError::FFmpeg("Thumbnail was not created".to_string()));
+        if !sprite_path.exists() {
+            return Err(AppError::FFmpeg("Sprite sheet was not created".to_string()));
         }
 
-        Ok(thumbnail_path_str)
+        fs::write(&track_path, sprite_track(frame_count, columns, duration_ms)).await.map_err(AppError::Io)?;
+
+        Ok(true)
+    }
+
+    /// Remove the sprite sheet and track of a video, if it has them
+    pub async fn delete_sprite_sheet(&self, video_id: &str) -> Result<()> {
+        for path in [self.sprite_sheet_path(video_id), self.sprite_track_path(video_id)] {
+            if path.exists() {
+                fs::remove_file(&path).await.map_err(AppError::Io)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Write the largest preview image embedded in a photo, returning whether one was found
+    async fn extract_preview_image(photo_path: &str, preview_path: &Path) -> bool {
+        for tag in ["-JpgFromRaw", "-PreviewImage", "-ThumbnailImage"] {
+            let output = match Command::new("exiftool")
+                .arg("-b")
+                .arg(tag)
+                .arg(photo_path)
+                .kill_on_drop(true)
+                .output()
+                .await
+            {
+                Ok(output) => output,
+                Err(e) => {
+                    warn!("Failed to run exiftool for {photo_path}: {e}");
+                    return false;
+                }
+            };
+
+            if output.status.success() && !output.stdout.is_empty() {
+                return fs::write(preview_path, &output.stdout).await.is_ok();
+            }
+        }
+
+        false
     }
 
     pub async fn delete_thumbnail(&self, thumbnail_path: &str) -> Result<()> {
-        let path = Path::new(thumbnail_path);
+        let path = self.resolve_path(thumbnail_path);
 
         // Only delete if the file is in our thumbnail directory
         if path.starts_with(&self.thumbnail_dir) && path.exists() {
-            fs::remove_file(path).await.map_err(AppError::Io)?;
+            fs::remove_file(&path).await.map_err(AppError::Io)?;
             info!("Deleted thumbnail: {thumbnail_path}");
         }
 
         Ok(())
     }
 }
+
+// FFmpeg writes next to the final file first, so a failed run never leaves a broken thumbnail in its place
+fn partial_path(path: &Path) -> PathBuf {
+    let extension = path.extension().map(|extension| extension.to_string_lossy().to_string()).unwrap_or_default();
+    path.with_extension(format!("partial.{extension}"))
+}
+
+// Start and length in seconds of the segments of a hover preview. Clips too short to skip through are
+// previewed from the start.
+fn preview_segments(duration: f64) -> Vec<(f64, f64)> {
+    let total = f64::from(PREVIEW_SEGMENTS) * PREVIEW_SEGMENT_SECONDS;
+    if duration <= total * 2.0 {
+        return vec![(0.0, duration.min(total))];
+    }
+
+    // Centre the segments on evenly spaced points, leaving out the very start and end
+    (1..=PREVIEW_SEGMENTS)
+        .map(|index| {
+            let middle = duration * f64::from(index) / f64::from(PREVIEW_SEGMENTS + 1);
+            (middle - PREVIEW_SEGMENT_SECONDS / 2.0, PREVIEW_SEGMENT_SECONDS)
+        })
+        .collect()
+}
+
+/// Short form of a thumbnail hash that versions its scaled copies
+pub fn thumbnail_version(thumbnail_hash: &str) -> &str {
+    &thumbnail_hash[..thumbnail_hash.len().min(16)]
+}
+
+// Check once which modern image formats the installed FFmpeg can write
+async fn ffmpeg_image_support() -> &'static FfmpegImageSupport {
+    FFMPEG_IMAGE_SUPPORT
+        .get_or_init(|| async {
+            let list = |kind: &'static str| async move {
+                match Command::new("ffmpeg").arg("-hide_banner").arg(kind).output().await {
+                    Ok(output) => String::from_utf8_lossy(&output.stdout).to_string(),
+                    Err(e) => {
+                        warn!("Failed to list FFmpeg {kind}: {e}");
+                        String::new()
+                    }
+                }
+            };
+            let encoders = list("-encoders").await;
+            let muxers = list("-muxers").await;
+
+            // Lines look like " V....D libwebp   libwebp WebP image (codec webp)"
+            let has = |list: &str, name: &str| list.lines().any(|line| line.split_whitespace().nth(1) == Some(name));
+            let support = FfmpegImageSupport {
+                webp: has(&encoders, "libwebp"),
+                avif_encoder: ["libaom-av1", "libsvtav1"]
+                    .into_iter()
+                    .find(|encoder| has(&encoders, encoder))
+                    .filter(|_| has(&muxers, "avif")),
+            };
+            info!("FFmpeg thumbnail formats: {support:?}");
+            support
+        })
+        .await
+}
+
+// Move a file written by FFmpeg into place, or clean it up if FFmpeg failed
+async fn finish_partial(output: &std::process::Output, partial_path: &Path, path: &Path) -> Result<()> {
+    if !output.status.success() {
+        let _ = fs::remove_file(partial_path).await;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        error!("FFmpeg error: {stderr}");
+        return Err(AppError::FFmpeg(format!("FFmpeg error: {stderr}")));
+    }
+
+    // Check if thumbnail was created
+    if !partial_path.exists() {
+        return Err(AppError::FFmpeg("Thumbnail was not created".to_string()));
+    }
+
+    fs::rename(partial_path, path).await.map_err(AppError::Io)
+}
+
+/// Check that a file is a complete JPEG image: not empty, with start and end of image markers
+pub async fn is_valid_jpeg(path: &Path) -> bool {
+    match fs::read(path).await {
+        Ok(contents) => contents.starts_with(&[0xFF, 0xD8, 0xFF]) && contents.ends_with(&[0xFF, 0xD9]),
+        Err(_) => false,
+    }
+}
+
+// WebVTT cues splitting the video into equal stretches, each showing its frame of the sprite sheet.
+// The image is referenced relative to the track so it resolves next to wherever the track is served.
+fn sprite_track(frame_count: u32, columns: u32, duration_ms: i64) -> String {
+    let mut track = String::from("WEBVTT\n");
+
+    for index in 0..frame_count {
+        let start = duration_ms * i64::from(index) / i64::from(frame_count);
+        let end = duration_ms * i64::from(index + 1) / i64::from(frame_count);
+        let x = index % columns * SPRITE_FRAME_WIDTH;
+        let y = index / columns * SPRITE_FRAME_HEIGHT;
+
+        track.push_str(&format!(
+            "\n{} --> {}\nsprite.jpg#xywh={x},{y},{SPRITE_FRAME_WIDTH},{SPRITE_FRAME_HEIGHT}\n",
+            vtt_timestamp(start),
+            vtt_timestamp(end),
+        ));
+    }
+
+    track
+}
+
+fn vtt_timestamp(ms: i64) -> String {
+    format!("{:02}:{:02}:{:02}.{:03}", ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000)
+}
+
+// Times in seconds of the frames to score for a thumbnail. Empty means the first frame is used as is.
+fn candidate_times(duration_ms: Option<i64>, position: ThumbnailPosition) -> Vec<f64> {
+    let duration = duration_ms.filter(|duration| *duration > 0).map(|duration| duration as f64 / 1000.0);
+
+    let start = match (position, duration) {
+        (ThumbnailPosition::First, _) => return Vec::new(),
+        (ThumbnailPosition::Auto, None) => return Vec::new(),
+        (ThumbnailPosition::Auto, Some(duration)) => {
+            // Spread evenly between 5% and 95% of the video, avoiding fades at either end
+            return (0..AUTO_CANDIDATES)
+                .map(|index| duration * (0.05 + 0.9 * index as f64 / (AUTO_CANDIDATES - 1) as f64))
+                .collect();
+        },
+        (ThumbnailPosition::Fraction(fraction), Some(duration)) => duration * fraction,
+        (ThumbnailPosition::Fraction(_), None) => return Vec::new(),
+        (ThumbnailPosition::Seconds(seconds), _) => seconds,
+    };
+
+    // Keep the candidates inside the video, falling back to its last second if the position is past the end
+    let last = duration.map_or(f64::MAX, |duration| (duration - 1.0).max(0.0));
+    let start = start.min(last);
+    (0..POSITION_CANDIDATES)
+        .map(|index| start + index as f64)
+        .take_while(|time| *time <= last)
+        .collect()
+}
+
+/// How good a frame looks as a thumbnail, measured on 8-bit grayscale pixels
+#[derive(Debug)]
+struct FrameScore {
+    /// Mean brightness
+    brightness: f64,
+    /// Standard deviation of the brightness
+    contrast: f64,
+    /// Mean absolute Laplacian, which drops for blurry frames
+    sharpness: f64,
+}
+
+impl FrameScore {
+    fn measure(pixels: &[u8], width: usize) -> Self {
+        let count = pixels.len() as f64;
+        let brightness = pixels.iter().map(|&pixel| f64::from(pixel)).sum::<f64>() / count;
+        let variance = pixels.iter().map(|&pixel| (f64::from(pixel) - brightness).powi(2)).sum::<f64>() / count;
+
+        let height = pixels.len() / width;
+        let pixel = |x: usize, y: usize| f64::from(pixels[y * width + x]);
+        let mut laplacian = 0.0;
+        for y in 1..height.saturating_sub(1) {
+            for x in 1..width - 1 {
+                laplacian += (4.0 * pixel(x, y) - pixel(x - 1, y) - pixel(x + 1, y) - pixel(x, y - 1) - pixel(x, y + 1)).abs();
+            }
+        }
+        let inner = (width.saturating_sub(2) * height.saturating_sub(2)).max(1) as f64;
+
+        Self {
+            brightness,
+            contrast: variance.sqrt(),
+            sharpness: laplacian / inner,
+        }
+    }
+
+    fn is_usable(&self) -> bool {
+        (MIN_BRIGHTNESS..=MAX_BRIGHTNESS).contains(&self.brightness) && self.contrast >= MIN_CONTRAST
+    }
+
+    // Sharp, contrasty frames score highest, less so the further their exposure is from mid-grey
+    fn value(&self) -> f64 {
+        let exposure = 1.0 - (self.brightness - 128.0).abs() / 256.0;
+        (self.sharpness + self.contrast / 4.0) * exposure
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A SCORE_WIDTH x SCORE_HEIGHT grayscale frame with each pixel given by its position
+    fn frame(pixel: impl Fn(usize, usize) -> u8) -> Vec<u8> {
+        (0..SCORE_HEIGHT).flat_map(|y| (0..SCORE_WIDTH).map(move |x| (x, y))).map(|(x, y)| pixel(x, y)).collect()
+    }
+
+    #[test]
+    fn rejects_black_blown_out_and_flat_frames() {
+        let cases = [
+            ("black", frame(|_, _| 0), false),
+            ("nearly black with noise", frame(|x, y| ((x * 7 + y * 13) % 16) as u8), false),
+            ("white", frame(|_, _| 255), false),
+            ("flat grey", frame(|_, _| 128), false),
+            ("low contrast grey", frame(|x, _| 124 + (x % 8) as u8), false),
+            ("horizontal gradient", frame(|x, _| (x * 255 / SCORE_WIDTH) as u8), true),
+            ("checkerboard", frame(|x, y| if (x / 8 + y / 8) % 2 == 0 { 40 } else { 200 }), true),
+        ];
+
+        for (name, pixels, usable) in cases {
+            assert_eq!(FrameScore::measure(&pixels, SCORE_WIDTH).is_usable(), usable, "{name}");
+        }
+    }
+
+    #[test]
+    fn prefers_sharp_well_exposed_frames() {
+        let sharp = FrameScore::measure(&frame(|x, y| if (x + y) % 2 == 0 { 60 } else { 190 }), SCORE_WIDTH);
+        let blurry = FrameScore::measure(&frame(|x, _| (x * 255 / SCORE_WIDTH) as u8), SCORE_WIDTH);
+        let dark = FrameScore::measure(&frame(|x, y| if (x + y) % 2 == 0 { 10 } else { 70 }), SCORE_WIDTH);
+
+        assert!(sharp.sharpness > blurry.sharpness);
+        assert!(sharp.value() > blurry.value());
+        assert!(sharp.value() > dark.value());
+    }
+
+    #[test]
+    fn candidate_times_stay_inside_the_video() {
+        let cases = [
+            ("auto", Some(60_000), ThumbnailPosition::Auto, AUTO_CANDIDATES),
+            ("auto on a very short clip", Some(40), ThumbnailPosition::Auto, AUTO_CANDIDATES),
+            ("auto with unknown duration", None, ThumbnailPosition::Auto, 0),
+            ("auto with zero duration", Some(0), ThumbnailPosition::Auto, 0),
+            ("first", Some(60_000), ThumbnailPosition::First, 0),
+            ("fraction", Some(60_000), ThumbnailPosition::Fraction(0.25), POSITION_CANDIDATES),
+            ("fraction near the end", Some(60_000), ThumbnailPosition::Fraction(0.99), 1),
+            ("fraction of a short clip", Some(500), ThumbnailPosition::Fraction(0.5), 1),
+            ("fraction with unknown duration", None, ThumbnailPosition::Fraction(0.5), 0),
+            ("seconds", Some(60_000), ThumbnailPosition::Seconds(10.0), POSITION_CANDIDATES),
+            ("seconds past the end", Some(3_000), ThumbnailPosition::Seconds(10.0), 1),
+            ("seconds in a short clip", Some(2_500), ThumbnailPosition::Seconds(0.0), 2),
+            ("seconds with unknown duration", None, ThumbnailPosition::Seconds(10.0), POSITION_CANDIDATES),
+        ];
+
+        for (name, duration_ms, position, count) in cases {
+            let times = candidate_times(duration_ms, position);
+            assert_eq!(times.len(), count, "{name}: {times:?}");
+
+            let end = duration_ms.filter(|duration| *duration > 0).map_or(f64::MAX, |duration| duration as f64 / 1000.0);
+            assert!(times.iter().all(|time| (0.0..end).contains(time)), "{name}: {times:?}");
+        }
+    }
+    #[test]
+    fn falls_back_to_jpeg_for_formats_ffmpeg_cant_write() {
+        let cases = [
+            ("webp without libwebp", false, None, ThumbnailFormat::Webp, ThumbnailFormat::Jpeg),
+            ("webp with libwebp", true, None, ThumbnailFormat::Webp, ThumbnailFormat::Webp),
+            ("avif without an encoder", true, None, ThumbnailFormat::Avif, ThumbnailFormat::Jpeg),
+            ("avif with an encoder", false, Some("libaom-av1"), ThumbnailFormat::Avif, ThumbnailFormat::Avif),
+            ("jpeg without either", false, None, ThumbnailFormat::Jpeg, ThumbnailFormat::Jpeg),
+        ];
+
+        for (name, webp, avif_encoder, format, expected) in cases {
+            let support = FfmpegImageSupport { webp, avif_encoder };
+            assert_eq!(support.writable(format), expected, "{name}");
+        }
+    }
+    #[test]
+    fn sprite_track_cues() {
+        let cue = |start: &str, end: &str, x: u32, y: u32| format!("\n{start} --> {end}\nsprite.jpg#xywh={x},{y},160,90\n");
+        let cases = [
+            ("one frame", 1, 1, 2_500, vec![cue("00:00:00.000", "00:00:02.500", 0, 0)]),
+            (
+                "second row",
+                3,
+                2,
+                3_000,
+                vec![
+                    cue("00:00:00.000", "00:00:01.000", 0, 0),
+                    cue("00:00:01.000", "00:00:02.000", 160, 0),
+                    cue("00:00:02.000", "00:00:03.000", 0, 90),
+                ],
+            ),
+            (
+                "uneven split",
+                3,
+                10,
+                1_000,
+                vec![
+                    cue("00:00:00.000", "00:00:00.333", 0, 0),
+                    cue("00:00:00.333", "00:00:00.666", 160, 0),
+                    cue("00:00:00.666", "00:00:01.000", 320, 0),
+                ],
+            ),
+            (
+                "past an hour",
+                2,
+                1,
+                7_384_002,
+                vec![
+                    cue("00:00:00.000", "01:01:32.001", 0, 0),
+                    cue("01:01:32.001", "02:03:04.002", 0, 90),
+                ],
+            ),
+        ];
+
+        for (name, frame_count, columns, duration_ms, cues) in cases {
+            assert_eq!(sprite_track(frame_count, columns, duration_ms), format!("WEBVTT\n{}", cues.concat()), "{name}");
+        }
+    }
+
+    #[test]
+    fn vtt_timestamps() {
+        let cases = [
+            (0, "00:00:00.000"),
+            (999, "00:00:00.999"),
+            (61_001, "00:01:01.001"),
+            (3_599_999, "00:59:59.999"),
+            (3_600_000, "01:00:00.000"),
+            (36_000_000 + 754_321, "10:12:34.321"),
+        ];
+
+        for (ms, expected) in cases {
+            assert_eq!(vtt_timestamp(ms), expected, "{ms}");
+        }
+    }
+    #[test]
+    fn preview_segments_stay_inside_the_clip() {
+        let cases = [0.5, 2.0, 3.0, 4.0, 6.5, 8.0, 8.5, 12.0, 60.0, 7_200.0];
+
+        for duration in cases {
+            let segments = preview_segments(duration);
+            let total: f64 = segments.iter().map(|(_, length)| length).sum();
+
+            // Clips too short for a 3 second preview are shown whole
+            if duration < 3.0 {
+                assert_eq!(segments, [(0.0, duration)], "{duration}");
+            } else {
+                assert!((3.0..=5.0).contains(&total), "{duration}: {segments:?}");
+            }
+
+            let mut previous_end = 0.0;
+            for (start, length) in &segments {
+                assert!(*start >= previous_end && *length > 0.0, "{duration}: {segments:?}");
+                previous_end = start + length;
+            }
+            assert!(previous_end <= duration, "{duration}: {segments:?}");
+        }
+    }
+}
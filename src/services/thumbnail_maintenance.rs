@@ -0,0 +1,246 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::fs;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+use crate::config::{Config, ThumbnailPosition};
+use crate::error::{AppError, Result};
+use crate::models::{ThumbnailCleanupResult, ThumbnailFailure, ThumbnailRegenerateResult, Video, MEDIA_TYPE_PHOTO};
+use crate::services::thumbnail::{is_valid_jpeg, thumbnail_version, ThumbnailService};
+use crate::services::video::VideoService;
+
+/// Files modified more recently than this are left alone, as a running scan may still be writing them
+const MIN_ORPHAN_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Keeps the thumbnail directory in step with the videos
+pub struct ThumbnailMaintenanceService;
+
+impl ThumbnailMaintenanceService {
+    /// Find the thumbnails, sprite sheets and previews that no video refers to, and delete them unless this is a dry run
+    pub async fn remove_orphans(
+        video_service: &VideoService,
+        thumbnail_service: &ThumbnailService,
+        dry_run: bool,
+    ) -> Result<ThumbnailCleanupResult> {
+        let references = video_service.find_thumbnail_paths().await?;
+        let video_ids: HashSet<String> = references.iter().map(|(id, _, _)| id.clone()).collect();
+        let variant_prefixes: HashSet<String> = references
+            .iter()
+            .filter_map(|(id, _, thumbnail_hash)| Some(format!("{id}-{}-", thumbnail_version(thumbnail_hash.as_deref()?))))
+            .collect();
+        let thumbnail_names: HashSet<String> = references
+            .iter()
+            .filter_map(|(_, thumbnail_path, _)| thumbnail_path.as_deref())
+            .filter_map(|thumbnail_path| Path::new(thumbnail_path).file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .collect();
+
+        let directory = thumbnail_service.directory();
+        let mut orphans = Vec::new();
+
+        // Thumbnails are referenced by file name, sprite sheets, tracks and previews by the video id they're named after
+        for (path, size) in list_old_files(directory).await? {
+            let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+            if !thumbnail_names.contains(&name) {
+                orphans.push((path, size));
+            }
+        }
+        for subdirectory in ["sprites", "previews"] {
+            for (path, size) in list_old_files(&directory.join(subdirectory)).await? {
+                let stem = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+                if !video_ids.contains(&stem) {
+                    orphans.push((path, size));
+                }
+            }
+        }
+
+        // Scaled thumbnails are named after the video and version of the thumbnail they were made from,
+        // as in "<uuid>-<version>-small.webp", so the prefix ends at the sixth hyphen
+        for (path, size) in list_old_files(&thumbnail_service.variant_directory()).await? {
+            let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+            let current = name
+                .match_indices('-')
+                .nth(5)
+                .is_some_and(|(index, _)| variant_prefixes.contains(&name[..=index]));
+            if !current {
+                orphans.push((path, size));
+            }
+        }
+
+        let mut files = Vec::new();
+        let mut bytes = 0;
+        for (path, size) in orphans {
+            if !dry_run {
+                if let Err(e) = fs::remove_file(&path).await {
+                    warn!("Failed to delete orphaned thumbnail {}: {}", path.display(), e);
+                    continue;
+                }
+            }
+            files.push(path.to_string_lossy().to_string());
+            bytes += size;
+        }
+
+        info!(
+            "{} {} orphaned thumbnail files ({} bytes)",
+            if dry_run { "Found" } else { "Deleted" },
+            files.len(),
+            bytes
+        );
+
+        Ok(ThumbnailCleanupResult { dry_run, files, bytes })
+    }
+
+    /// Regenerate the thumbnails of videos on disk whose thumbnail is missing or isn't a complete image
+    pub async fn repair(
+        video_service: Arc<VideoService>,
+        thumbnail_service: Arc<ThumbnailService>,
+        config: &Config,
+        semaphore: Arc<Semaphore>,
+        dry_run: bool,
+    ) -> Result<ThumbnailRegenerateResult> {
+        let videos = video_service.find_online().await?;
+        let checked = videos.len();
+
+        let mut broken = Vec::new();
+        for video in videos {
+            let valid = match &video.thumbnail_path {
+                Some(thumbnail_path) => is_valid_jpeg(Path::new(thumbnail_path)).await,
+                None => false,
+            };
+            if !valid {
+                broken.push(video);
+            }
+        }
+
+        info!("Found {} of {} videos with a missing or broken thumbnail", broken.len(), checked);
+
+        if dry_run {
+            return Ok(ThumbnailRegenerateResult {
+                dry_run,
+                checked,
+                regenerated: broken.into_iter().map(|video| video.id).collect(),
+                failed: Vec::new(),
+            });
+        }
+
+        let mut result = Self::regenerate(video_service, thumbnail_service, config, semaphore, broken).await;
+        result.checked = checked;
+        Ok(result)
+    }
+
+    /// Regenerate the thumbnails of some videos, within the limit on media tasks shared with scans.
+    /// Thumbnails chosen by hand are taken again from their chosen time, and originals linked to
+    /// a proxy copy the proxy's thumbnail again.
+    pub async fn regenerate(
+        video_service: Arc<VideoService>,
+        thumbnail_service: Arc<ThumbnailService>,
+        config: &Config,
+        semaphore: Arc<Semaphore>,
+        videos: Vec<Video>,
+    ) -> ThumbnailRegenerateResult {
+        let checked = videos.len();
+        let mut tasks = JoinSet::new();
+
+        for video in videos {
+            let position = config.media.source_for(&video.file_path)
+                .map(|source| source.thumbnail)
+                .unwrap_or_default();
+            let video_service = video_service.clone();
+            let thumbnail_service = thumbnail_service.clone();
+            let semaphore = semaphore.clone();
+
+            tasks.spawn(async move {
+                let _permit = semaphore.acquire().await.unwrap();
+                let result = Self::regenerate_video(&video_service, &thumbnail_service, &video, position).await;
+                (video, result)
+            });
+        }
+
+        let mut regenerated = Vec::new();
+        let mut failed = Vec::new();
+        while let Some(joined) = tasks.join_next().await {
+            match joined {
+                Ok((video, Ok(()))) => regenerated.push(video.id),
+                Ok((video, Err(e))) => {
+                    warn!("Failed to regenerate thumbnail for {}: {}", video.file_path, e);
+                    failed.push(ThumbnailFailure {
+                        video_id: video.id,
+                        file_path: video.file_path,
+                        error: e.to_string(),
+                    });
+                },
+                Err(e) => warn!("Thumbnail task failed: {}", e),
+            }
+        }
+
+        info!("Regenerated {} thumbnails, {} failed", regenerated.len(), failed.len());
+
+        ThumbnailRegenerateResult {
+            dry_run: false,
+            checked,
+            regenerated,
+            failed,
+        }
+    }
+
+    async fn regenerate_video(
+        video_service: &VideoService,
+        thumbnail_service: &ThumbnailService,
+        video: &Video,
+        position: ThumbnailPosition,
+    ) -> Result<()> {
+        if !Path::new(&video.file_path).exists() {
+            return Err(AppError::NotFound(format!("Video file not found: {}", video.file_path)));
+        }
+
+        // FFmpeg can't decode originals linked to a proxy
+        if let Some(proxy_video_id) = &video.proxy_video_id {
+            let proxy = video_service.find_by_id(proxy_video_id).await?;
+            let proxy_thumbnail_path = proxy
+                .thumbnail_path
+                .ok_or_else(|| AppError::NotFound(format!("Proxy has no thumbnail: {}", proxy.file_path)))?;
+            let thumbnail_path = thumbnail_service.copy_thumbnail(&video.id, &proxy_thumbnail_path).await?;
+            return video_service.update_thumbnail_path(&video.id, video.thumbnail_path.as_deref(), &thumbnail_path).await;
+        }
+
+        let thumbnail_path = match (video.media_type.as_str(), video.thumbnail_time) {
+            (MEDIA_TYPE_PHOTO, _) => thumbnail_service.generate_photo_thumbnail(&video.id, &video.file_path).await?,
+            (_, Some(time)) => thumbnail_service.generate_thumbnail_at(&video.id, &video.file_path, time).await?,
+            (_, None) => thumbnail_service.generate_thumbnail(&video.id, &video.file_path, video.duration, position).await?,
+        };
+
+        video_service.update_thumbnail_path(&video.id, video.thumbnail_path.as_deref(), &thumbnail_path).await
+    }
+}
+
+// Files directly in a directory that weren't modified recently, with their sizes
+async fn list_old_files(directory: &Path) -> Result<Vec<(std::path::PathBuf, u64)>> {
+    let mut files = Vec::new();
+    let mut entries = match fs::read_dir(directory).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(files),
+        Err(e) => return Err(AppError::Io(e)),
+    };
+
+    while let Some(entry) = entries.next_entry().await.map_err(AppError::Io)? {
+        let metadata = entry.metadata().await.map_err(AppError::Io)?;
+        if !metadata.is_file() {
+            continue;
+        }
+
+        let age = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .unwrap_or_default();
+        if age >= MIN_ORPHAN_AGE {
+            files.push((entry.path(), metadata.len()));
+        }
+    }
+
+    Ok(files)
+}
@@ -0,0 +1,381 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tokio::fs;
+use tokio::process::Command;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+use walkdir::WalkDir;
+
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::models::Video;
+use crate::utils::{ensure_dir_exists, get_file_extension};
+
+/// Length of each HLS segment in seconds
+const SEGMENT_SECONDS: f64 = 6.0;
+
+/// Transcoded video is scaled down to fit this size, turned to match portrait videos
+const MAX_LONG_SIDE: u32 = 1920;
+const MAX_SHORT_SIDE: u32 = 1080;
+
+/// Peak bit rate announced in the master playlist
+const BANDWIDTH: u32 = 8_000_000;
+
+/// Containers browsers play, with the video and audio codecs they play in them
+const DIRECT_PLAY_FORMATS: &[(&[&str], &[&str], &[&str])] = &[
+    (&["mp4", "m4v", "mov"], &["h264", "av1"], &["aac", "mp3"]),
+    (&["webm"], &["vp8", "vp9", "av1"], &["opus", "vorbis"]),
+];
+
+/// Transcodes videos browsers can't play into H.264/AAC HLS segments on demand, keeping the
+/// segments in a cache directory that is trimmed to its size limit, least recently used first
+pub struct TranscodeService {
+    cache_dir: PathBuf,
+    max_cache_bytes: u64,
+    /// Limits media tasks running at once, shared with scans and thumbnail maintenance
+    task_permits: Arc<Semaphore>,
+}
+
+impl TranscodeService {
+    pub fn new(config: &Config, task_permits: Arc<Semaphore>) -> Self {
+        Self {
+            cache_dir: PathBuf::from(&config.media.transcode_cache_path),
+            max_cache_bytes: config.media.transcode_cache_size_mb * 1024 * 1024,
+            task_permits,
+        }
+    }
+
+    /// Why browsers can't play a video's file as it is; empty when it can be played directly.
+    /// Codecs of videos that haven't been probed yet are taken on trust from the container.
+    pub fn transcode_reasons(video: &Video) -> Vec<String> {
+        let extension = get_file_extension(Path::new(&video.file_path)).unwrap_or_default();
+        let Some((_, video_codecs, audio_codecs)) = DIRECT_PLAY_FORMATS
+            .iter()
+            .find(|(extensions, _, _)| extensions.contains(&extension.as_str()))
+        else {
+            return vec![format!("Browsers don't play .{extension} files")];
+        };
+
+        let mut reasons = Vec::new();
+        if let Some(codec) = video.video_codec.as_deref() {
+            if !video_codecs.iter().any(|supported| codec.eq_ignore_ascii_case(supported)) {
+                reasons.push(format!("Browsers don't play {codec} video in .{extension} files"));
+            } else if codec.eq_ignore_ascii_case("h264") && video.bit_depth.is_some_and(|bit_depth| bit_depth > 8) {
+                reasons.push(format!("Browsers don't play {}-bit H.264", video.bit_depth.unwrap_or_default()));
+            }
+        }
+        if let Some(codec) = video.audio_codec.as_deref() {
+            if !audio_codecs.iter().any(|supported| codec.eq_ignore_ascii_case(supported)) {
+                reasons.push(format!("Browsers don't play {codec} audio in .{extension} files"));
+            }
+        }
+
+        reasons
+    }
+
+    /// Master playlist listing the single H.264/AAC rendition
+    pub fn master_playlist(video: &Video) -> String {
+        // Videos that were probed and have no audio track don't announce one
+        let has_audio = video.audio_codec.is_some() || video.video_codec.is_none();
+        let codecs = if has_audio { "avc1.640028,mp4a.40.2" } else { "avc1.640028" };
+
+        let mut stream = format!("#EXT-X-STREAM-INF:BANDWIDTH={BANDWIDTH},CODECS=\"{codecs}\"");
+        if let Some((width, height)) = output_size(video) {
+            stream.push_str(&format!(",RESOLUTION={width}x{height}"));
+        }
+
+        format!("#EXTM3U\n#EXT-X-VERSION:3\n{stream}\nindex.m3u8\n")
+    }
+
+    /// Media playlist of the segments covering the whole video
+    pub fn media_playlist(duration_ms: i64) -> String {
+        let duration = duration_ms as f64 / 1000.0;
+        let mut playlist = format!(
+            "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-PLAYLIST-TYPE:VOD\n#EXT-X-TARGETDURATION:{}\n#EXT-X-MEDIA-SEQUENCE:0\n",
+            SEGMENT_SECONDS.ceil() as u32
+        );
+
+        for index in 0..segment_count(duration_ms) {
+            let start = f64::from(index) * SEGMENT_SECONDS;
+            let length = (duration - start).min(SEGMENT_SECONDS);
+            playlist.push_str(&format!("#EXTINF:{length:.3},\n{index}.ts\n"));
+        }
+
+        playlist.push_str("#EXT-X-ENDLIST\n");
+        playlist
+    }
+
+    /// File of a segment of a video, transcoding it unless it is cached
+    pub async fn segment(&self, video: &Video, index: u32) -> Result<PathBuf> {
+        let duration_ms = video
+            .duration
+            .filter(|duration| *duration > 0)
+            .ok_or_else(|| AppError::BadRequest(format!("Duration of video {} isn't known", video.id)))?;
+        if index >= segment_count(duration_ms) {
+            return Err(AppError::NotFound(format!("No segment {index} in video {}", video.id)));
+        }
+
+        // Segments of a file that changed since are left for eviction
+        let segment_dir = self.cache_dir.join(format!(
+            "{}-{}-{}",
+            video.id,
+            video.file_size.unwrap_or_default(),
+            video.file_mtime.unwrap_or_default()
+        ));
+        let segment_path = segment_dir.join(format!("{index}.ts"));
+
+        if touch(&segment_path).await {
+            return Ok(segment_path);
+        }
+
+        let _permit = self
+            .task_permits
+            .acquire()
+            .await
+            .map_err(|e| AppError::InternalServerError(format!("Failed to wait for a transcode: {e}")))?;
+
+        // Another request may have transcoded it while this one waited
+        if touch(&segment_path).await {
+            return Ok(segment_path);
+        }
+
+        ensure_dir_exists(&segment_dir).await?;
+        let start = f64::from(index) * SEGMENT_SECONDS;
+        let length = (duration_ms as f64 / 1000.0 - start).min(SEGMENT_SECONDS);
+
+        info!("Transcoding segment {index} of {}", video.file_path);
+
+        // Requests for the same segment can race, so each writes a file of its own
+        let partial_path = segment_dir.join(format!("{index}.{}.partial.ts", Uuid::new_v4()));
+        let output = Command::new("ffmpeg")
+            .arg("-hide_banner")
+            .arg("-ss")
+            .arg(format!("{start:.3}"))
+            .arg("-t")
+            .arg(format!("{length:.3}"))
+            .arg("-i")
+            .arg(&video.file_path)
+            .args(["-map", "0:v:0", "-map", "0:a:0?"])
+            .arg("-vf")
+            .arg(format!(
+                "scale=w='if(gte(iw,ih),min({MAX_LONG_SIDE},iw),min({MAX_SHORT_SIDE},iw))':\
+                 h='if(gte(iw,ih),min({MAX_SHORT_SIDE},ih),min({MAX_LONG_SIDE},ih))':\
+                 force_original_aspect_ratio=decrease:force_divisible_by=2,format=yuv420p"
+            ))
+            .args(["-c:v", "libx264", "-preset", "veryfast", "-crf", "23", "-profile:v", "high"])
+            .args(["-c:a", "aac", "-b:a", "160k", "-ac", "2", "-ar", "48000"])
+            // Segments are transcoded on their own, so place each one at its time in the video
+            .arg("-output_ts_offset")
+            .arg(format!("{start:.3}"))
+            .args(["-muxdelay", "0", "-f", "mpegts", "-y"])
+            .arg(&partial_path)
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| {
+                error!("FFmpeg command failed: {e}");
+                AppError::FFmpeg(format!("Failed to execute FFmpeg: {e}"))
+            })?;
+
+        if !output.status.success() {
+            let _ = fs::remove_file(&partial_path).await;
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            error!("FFmpeg error: {stderr}");
+            return Err(AppError::FFmpeg(format!("FFmpeg error: {stderr}")));
+        }
+
+        fs::rename(&partial_path, &segment_path).await.map_err(AppError::Io)?;
+
+        let cache_dir = self.cache_dir.clone();
+        let max_cache_bytes = self.max_cache_bytes;
+        tokio::task::spawn_blocking(move || evict(&cache_dir, max_cache_bytes));
+
+        Ok(segment_path)
+    }
+}
+
+fn segment_count(duration_ms: i64) -> u32 {
+    (duration_ms as f64 / 1000.0 / SEGMENT_SECONDS).ceil() as u32
+}
+
+// Size of the transcoded picture, as the scale filter works it out, if the video's size is known
+fn output_size(video: &Video) -> Option<(u32, u32)> {
+    let (mut width, mut height) = (u32::try_from(video.width?).ok()?, u32::try_from(video.height?).ok()?);
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    // FFmpeg turns rotated videos upright while transcoding
+    if matches!(video.rotation, Some(90 | 270)) {
+        (width, height) = (height, width);
+    }
+
+    let (max_width, max_height) = if width >= height {
+        (MAX_LONG_SIDE, MAX_SHORT_SIDE)
+    } else {
+        (MAX_SHORT_SIDE, MAX_LONG_SIDE)
+    };
+    let scale = (f64::from(max_width.min(width)) / f64::from(width)).min(f64::from(max_height.min(height)) / f64::from(height));
+    let even = |size: f64| ((size / 2.0).round() as u32 * 2).max(2);
+
+    Some((even(f64::from(width) * scale), even(f64::from(height) * scale)))
+}
+
+// Mark a cached segment as just used, returning whether it exists
+async fn touch(path: &Path) -> bool {
+    let Ok(file) = fs::OpenOptions::new().append(true).open(path).await else {
+        return false;
+    };
+
+    if let Err(e) = file.into_std().await.set_modified(SystemTime::now()) {
+        warn!("Failed to mark {} as used: {}", path.display(), e);
+    }
+    true
+}
+
+// Delete the least recently used segments until the cache fits its size limit
+fn evict(cache_dir: &Path, max_bytes: u64) {
+    let mut files: Vec<(PathBuf, u64, SystemTime)> = WalkDir::new(cache_dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        // Segments still being written aren't counted
+        .filter(|entry| !entry.file_name().to_string_lossy().contains(".partial."))
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            Some((entry.into_path(), metadata.len(), metadata.modified().ok()?))
+        })
+        .collect();
+
+    let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+    if total <= max_bytes {
+        return;
+    }
+
+    files.sort_by_key(|(_, _, modified)| *modified);
+    let mut removed = 0;
+    for (path, size, _) in files {
+        if total <= max_bytes {
+            break;
+        }
+
+        match std::fs::remove_file(&path) {
+            Ok(()) => {
+                total -= size;
+                removed += 1;
+                // Drop the video's directory once its last segment is gone
+                if let Some(directory) = path.parent().filter(|directory| *directory != cache_dir) {
+                    let _ = std::fs::remove_dir(directory);
+                }
+            },
+            Err(e) => warn!("Failed to evict {}: {}", path.display(), e),
+        }
+    }
+
+    info!("Evicted {} transcoded segments, {} bytes left in the cache", removed, total);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    type Case<T> = (&'static str, Value, T);
+
+    // Video with the given fields set on top of the ones every video has
+    fn video(fields: Value) -> Video {
+        let mut video = json!({
+            "id": "video",
+            "file_path": "/media/clip.mp4",
+            "file_name": "clip.mp4",
+            "media_type": "video",
+            "created_at": "2025-01-01T00:00:00",
+            "updated_at": "2025-01-01T00:00:00",
+        });
+        if let (Some(video), Value::Object(fields)) = (video.as_object_mut(), fields) {
+            video.extend(fields);
+        }
+        serde_json::from_value(video).unwrap()
+    }
+
+    #[test]
+    fn transcode_reasons() {
+        let cases: &[Case<&[&str]>] = &[
+            ("h264 aac mp4", json!({"video_codec": "h264", "audio_codec": "aac"}), &[]),
+            ("not probed yet", json!({}), &[]),
+            ("codec case", json!({"video_codec": "H264", "audio_codec": "AAC"}), &[]),
+            ("vp9 opus webm", json!({"file_path": "/media/clip.webm", "video_codec": "vp9", "audio_codec": "opus"}), &[]),
+            ("container", json!({"file_path": "/media/clip.mkv", "video_codec": "h264"}), &["Browsers don't play .mkv files"]),
+            ("video codec", json!({"video_codec": "hevc"}), &["Browsers don't play hevc video in .mp4 files"]),
+            ("10-bit h264", json!({"video_codec": "h264", "bit_depth": 10}), &["Browsers don't play 10-bit H.264"]),
+            ("10-bit av1", json!({"video_codec": "av1", "bit_depth": 10}), &[]),
+            (
+                "both codecs",
+                json!({"file_path": "/media/clip.mov", "video_codec": "prores", "audio_codec": "pcm_s16le"}),
+                &["Browsers don't play prores video in .mov files", "Browsers don't play pcm_s16le audio in .mov files"],
+            ),
+            ("webm audio", json!({"file_path": "/media/clip.webm", "video_codec": "vp9", "audio_codec": "aac"}), &["Browsers don't play aac audio in .webm files"]),
+        ];
+
+        for (name, fields, expected) in cases {
+            assert_eq!(TranscodeService::transcode_reasons(&video(fields.clone())), *expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn output_sizes() {
+        let cases: &[Case<Option<(u32, u32)>>] = &[
+            ("unknown size", json!({}), None),
+            ("zero size", json!({"width": 0, "height": 1080}), None),
+            ("fits already", json!({"width": 1280, "height": 720}), Some((1280, 720))),
+            ("4k landscape", json!({"width": 3840, "height": 2160}), Some((1920, 1080))),
+            ("4k portrait", json!({"width": 2160, "height": 3840}), Some((1080, 1920))),
+            ("rotated 90", json!({"width": 3840, "height": 2160, "rotation": 90}), Some((1080, 1920))),
+            ("rotated 270", json!({"width": 1280, "height": 720, "rotation": 270}), Some((720, 1280))),
+            ("rotated 180", json!({"width": 1280, "height": 720, "rotation": 180}), Some((1280, 720))),
+            ("wider than 16:9", json!({"width": 4096, "height": 1716}), Some((1920, 804))),
+            ("odd size", json!({"width": 719, "height": 405}), Some((720, 406))),
+            ("odd scaled size", json!({"width": 2880, "height": 1800}), Some((1728, 1080))),
+            ("tiny", json!({"width": 1, "height": 1}), Some((2, 2))),
+        ];
+
+        for (name, fields, expected) in cases {
+            assert_eq!(output_size(&video(fields.clone())), *expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn segment_counts() {
+        let cases = [(0, 0), (1, 1), (5_999, 1), (6_000, 1), (6_001, 2), (60_000, 10), (3_600_500, 601)];
+
+        for (duration_ms, expected) in cases {
+            assert_eq!(segment_count(duration_ms), expected, "{duration_ms} ms");
+        }
+    }
+
+    #[test]
+    fn media_playlists() {
+        let cases: &[(i64, &[&str])] = &[
+            (6_000, &["6.000"]),
+            (14_250, &["6.000", "6.000", "2.250"]),
+            (12_001, &["6.000", "6.000", "0.001"]),
+            (500, &["0.500"]),
+        ];
+
+        for (duration_ms, lengths) in cases {
+            let playlist = TranscodeService::media_playlist(*duration_ms);
+            assert!(playlist.starts_with("#EXTM3U\n#EXT-X-VERSION:3\n"), "{duration_ms} ms");
+            assert!(playlist.contains("#EXT-X-TARGETDURATION:6\n"), "{duration_ms} ms");
+            assert!(playlist.ends_with("#EXT-X-ENDLIST\n"), "{duration_ms} ms");
+
+            let segments: String = lengths
+                .iter()
+                .enumerate()
+                .map(|(index, length)| format!("#EXTINF:{length},\n{index}.ts\n"))
+                .collect();
+            assert!(playlist.contains(&format!("#EXT-X-MEDIA-SEQUENCE:0\n{segments}#EXT-X-ENDLIST\n")), "{duration_ms} ms: {playlist}");
+        }
+    }
+}
@@ -1,13 +1,18 @@
 use sqlx::{Pool, Postgres, Row};
+use std::collections::HashSet;
 use tracing::{info, error};
 use uuid::Uuid;
 
 use crate::error::{AppError, Result};
-use crate::models::{Video, CreateVideoDto, UpdateVideoDto, VideoWithMetadata, VideoSearchParams};
+use crate::models::{Video, CreateVideoDto, UpdateVideoDto, VideoWithMetadata, VideoSearchParams, CreationTime, GpsPosition, MEDIA_TYPE_VIDEO};
 use crate::services::tag::TagService;
 use crate::services::person::PersonService;
 use crate::services::thumbnail::ThumbnailService;
 use crate::services::shoebox::ShoeboxService;
+use crate::utils::compute_content_hash;
+use crate::services::sidecar::SidecarMetadata;
+use crate::services::probe::MediaInfo;
+use crate::utils::FileFingerprint;
 
 pub struct VideoService {
     db: Pool<Postgres>,
@@ -35,7 +40,7 @@ impl VideoService {
     }
 
     // Helper method to transform thumbnail paths to web-compatible paths
-    fn transform_thumbnail_path(&self, thumbnail_path: Option<String>) -> Option<String> {
+    pub fn transform_thumbnail_path(&self, thumbnail_path: Option<String>) -> Option<String> {
         thumbnail_path.map(|path| {
             // Extract just the filename from the path
             let path = std::path::Path::new(&path);
@@ -98,6 +103,85 @@ impl VideoService {
         Ok(video)
     }
 
+    pub async fn find_missing_original_by_stem(&self, stem: &str) -> Result<Vec<Video>> {
+        let videos = sqlx::query_as::<_, Video>(
+            "SELECT * FROM videos
+             WHERE original_file_path IS NULL
+               AND original_match_status IS DISTINCT FROM 'manual'
+               AND file_name LIKE $1 || '.%'"
+        )
+        .bind(stem)
+        .fetch_all(&self.db)
+        .await
+        .map_err(AppError::Database)?;
+
+        // LIKE treats '_' and '%' in the stem as wildcards, so compare the stems exactly
+        Ok(videos
+            .into_iter()
+            .filter(|video| {
+                std::path::Path::new(&video.file_name)
+                    .file_stem()
+                    .is_some_and(|file_stem| file_stem.to_string_lossy() == stem)
+            })
+            .collect())
+    }
+
+    /// The online video whose original is a file, with its thumbnail path as stored
+    pub async fn find_proxy_for(&self, original_file_path: &str) -> Result<Option<Video>> {
+        let video = sqlx::query_as::<_, Video>(
+            "SELECT * FROM videos
+             WHERE original_file_path = $1 AND file_path <> $1 AND offline_since IS NULL
+             ORDER BY updated_at DESC
+             LIMIT 1"
+        )
+        .bind(original_file_path)
+        .fetch_optional(&self.db)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(video)
+    }
+
+    pub async fn find_offline(&self) -> Result<Vec<Video>> {
+        let mut videos = sqlx::query_as::<_, Video>(
+            "SELECT * FROM videos WHERE offline_since IS NOT NULL ORDER BY offline_since DESC"
+        )
+        .fetch_all(&self.db)
+        .await
+        .map_err(AppError::Database)?;
+
+        // Transform thumbnail paths
+        for video in &mut videos {
+            video.thumbnail_path = self.transform_thumbnail_path(video.thumbnail_path.clone());
+        }
+
+        Ok(videos)
+    }
+
+    pub async fn find_offline_by_size(&self, file_size: i64) -> Result<Vec<Video>> {
+        let videos = sqlx::query_as::<_, Video>(
+            "SELECT * FROM videos WHERE offline_since IS NOT NULL AND file_size = $1"
+        )
+        .bind(file_size)
+        .fetch_all(&self.db)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(videos)
+    }
+
+    pub async fn find_by_path_prefix(&self, prefix: &str) -> Result<Vec<Video>> {
+        let videos = sqlx::query_as::<_, Video>(
+            "SELECT * FROM videos WHERE starts_with(file_path, $1) ORDER BY file_path"
+        )
+        .bind(prefix)
+        .fetch_all(&self.db)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(videos)
+    }
+
     pub async fn find_with_metadata(&self, id: &str) -> Result<VideoWithMetadata> {
         let video = self.find_by_id(id).await?;
 
@@ -152,22 +236,29 @@ impl VideoService {
 
         // Insert video
         sqlx::query(
-            "INSERT INTO videos (id, file_path, file_name, title, description, created_date, file_size, thumbnail_path, rating, duration, original_file_path, location, event, created_at, updated_at)
-             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14::timestamp, $15::timestamp)"
+            "INSERT INTO videos (id, file_path, file_name, title, description, created_date, created_date_offset, file_size, thumbnail_path, rating, duration, original_file_path, exif_data, location, event, file_mtime, file_inode, partial_hash, content_hash, media_type, created_at, updated_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21::timestamp, $22::timestamp)"
         )
         .bind(&id)
         .bind(&dto.file_path)
         .bind(&dto.file_name)
         .bind(&dto.title)
         .bind(&dto.description)
-        .bind(&dto.created_date)
+        .bind(dto.created_date)
+        .bind(dto.created_date_offset)
         .bind(&dto.file_size)
         .bind(&dto.thumbnail_path)
         .bind(&dto.rating)
         .bind(&dto.duration)
         .bind(&dto.original_file_path)
+        .bind(&dto.exif_data)
         .bind(&dto.location)
         .bind(&dto.event)
+        .bind(dto.file_mtime)
+        .bind(dto.file_inode)
+        .bind(&dto.partial_hash)
+        .bind(&dto.content_hash)
+        .bind(&dto.media_type)
         .bind(&now)
         .bind(&now)
         .execute(&mut *tx)
@@ -350,7 +441,7 @@ impl VideoService {
         self.find_by_id(id).await
     }
 
-    pub async fn update_technical_metadata(&self, id: &str, file_size: Option<i64>, duration: Option<i64>, created_date: Option<String>, thumbnail_path: Option<String>, original_file_path: Option<String>, exif_data: Option<serde_json::Value>) -> Result<Video> {
+    pub async fn update_technical_metadata(&self, id: &str, file_size: Option<i64>, duration: Option<i64>, created_date: Option<CreationTime>, thumbnail_path: Option<String>, original_file_path: Option<String>, exif_data: Option<serde_json::Value>) -> Result<Video> {
         let mut tx = self.db.begin().await.map_err(AppError::Database)?;
 
         // Check if video exists
@@ -376,13 +467,22 @@ impl VideoService {
 
         if let Some(date) = &created_date {
             param_count += 1;
-            query.push_str(&format!(", created_date = ${}", param_count));
-            params.push(date.clone());
+            query.push_str(&format!(", created_date = ${}::timestamptz", param_count));
+            params.push(date.utc.to_rfc3339());
+
+            param_count += 1;
+            query.push_str(&format!(", created_date_offset = NULLIF(${}, '')::integer", param_count));
+            params.push(date.offset_minutes.map(|offset| offset.to_string()).unwrap_or_default());
         }
 
         if let Some(thumb) = &thumbnail_path {
             param_count += 1;
-            query.push_str(&format!(", thumbnail_path = ${}", param_count));
+            // Thumbnails chosen by hand are kept
+            query.push_str(&format!(
+                ", thumbnail_path = CASE WHEN thumbnail_time IS NULL THEN ${0} ELSE thumbnail_path END, \
+                 thumbnail_hash = CASE WHEN thumbnail_time IS NULL THEN NULL ELSE thumbnail_hash END",
+                param_count
+            ));
             params.push(thumb.clone());
         }
 
@@ -420,6 +520,486 @@ impl VideoService {
         self.find_by_id(id).await
     }
 
+    /// Store the fingerprint of a video's file. Hashes that weren't computed are cleared when the
+    /// file changed since its last scan and kept when it didn't.
+    pub async fn update_file_fingerprint(&self, id: &str, fingerprint: &FileFingerprint, file_changed: bool) -> Result<()> {
+        sqlx::query(
+            "UPDATE videos
+             SET file_mtime = $1, file_inode = $2,
+                 partial_hash = CASE WHEN $5 THEN $3 ELSE COALESCE($3, partial_hash) END,
+                 content_hash = CASE WHEN $5 THEN $4 ELSE COALESCE($4, content_hash) END
+             WHERE id = $6"
+        )
+        .bind(fingerprint.mtime)
+        .bind(fingerprint.inode)
+        .bind(&fingerprint.partial_hash)
+        .bind(&fingerprint.content_hash)
+        .bind(file_changed)
+        .bind(id)
+        .execute(&self.db)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    /// Store the codec, resolution, frame rate and audio details read by probing a file
+    pub async fn update_media_details(&self, id: &str, media_info: &MediaInfo) -> Result<()> {
+        sqlx::query(
+            "UPDATE videos
+             SET width = $1, height = $2, video_codec = $3, video_profile = $4, frame_rate = $5, bit_rate = $6,
+                 bit_depth = $7, color_primaries = $8, hdr = $9, rotation = $10,
+                 audio_codec = $11, audio_channels = $12, audio_sample_rate = $13
+             WHERE id = $14"
+        )
+        .bind(media_info.width)
+        .bind(media_info.height)
+        .bind(&media_info.video_codec)
+        .bind(&media_info.video_profile)
+        .bind(media_info.frame_rate)
+        .bind(media_info.bit_rate)
+        .bind(media_info.bit_depth)
+        .bind(&media_info.color_primaries)
+        .bind(media_info.hdr)
+        .bind(media_info.rotation)
+        .bind(&media_info.audio_codec)
+        .bind(media_info.audio_channels)
+        .bind(media_info.audio_sample_rate)
+        .bind(id)
+        .execute(&self.db)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    // Mark videos under a source path as offline when their files are no longer on disk
+    pub async fn mark_missing_offline(&self, source_path: &str, present_paths: &HashSet<String>) -> Result<usize> {
+        let rows = sqlx::query("SELECT id, file_path FROM videos WHERE offline_since IS NULL AND starts_with(file_path, $1)")
+            .bind(source_path)
+            .fetch_all(&self.db)
+            .await
+            .map_err(AppError::Database)?;
+
+        let missing_ids: Vec<String> = rows
+            .into_iter()
+            .filter_map(|row| {
+                let id: String = row.get("id");
+                let file_path: String = row.get("file_path");
+                let path = std::path::Path::new(&file_path);
+
+                // starts_with on strings also matches sibling directories sharing a prefix
+                let is_missing = path.starts_with(source_path)
+                    && !present_paths.contains(&file_path)
+                    && !path.exists();
+                is_missing.then_some(id)
+            })
+            .collect();
+
+        if missing_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let now = chrono::Utc::now().naive_utc();
+        sqlx::query("UPDATE videos SET offline_since = $1 WHERE id = ANY($2)")
+            .bind(now)
+            .bind(&missing_ids)
+            .execute(&self.db)
+            .await
+            .map_err(AppError::Database)?;
+
+        info!("Marked {} videos under {} as offline", missing_ids.len(), source_path);
+        Ok(missing_ids.len())
+    }
+
+    pub async fn mark_offline_by_path(&self, file_path: &str) -> Result<bool> {
+        let result = sqlx::query("UPDATE videos SET offline_since = $1 WHERE file_path = $2 AND offline_since IS NULL")
+            .bind(chrono::Utc::now().naive_utc())
+            .bind(file_path)
+            .execute(&self.db)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Mark the online videos whose files were under a directory as offline, returning how many
+    pub async fn mark_offline_under(&self, directory: &str) -> Result<u64> {
+        let prefix = format!("{}/", directory.trim_end_matches('/'));
+        let result = sqlx::query(
+            "UPDATE videos SET offline_since = $1 WHERE starts_with(file_path, $2) AND offline_since IS NULL"
+        )
+        .bind(chrono::Utc::now().naive_utc())
+        .bind(prefix)
+        .execute(&self.db)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn mark_online(&self, id: &str) -> Result<()> {
+        sqlx::query("UPDATE videos SET offline_since = NULL WHERE id = $1")
+            .bind(id)
+            .execute(&self.db)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    // Store ratings and keywords read from sidecar files.
+    // Ratings changed in Shoebox since the sidecar was last read win over the sidecar's.
+    pub async fn apply_sidecar_metadata(&self, id: &str, metadata: &SidecarMetadata) -> Result<()> {
+        let mut tx = self.db.begin().await.map_err(AppError::Database)?;
+        let now = chrono::Utc::now().naive_utc();
+
+        if let Some(rating) = metadata.rating {
+            sqlx::query(
+                "UPDATE videos SET rating = CASE WHEN rating IS NULL OR rating IS NOT DISTINCT FROM sidecar_rating THEN $1 ELSE rating END, \
+                 sidecar_rating = $1 WHERE id = $2",
+            )
+            .bind(rating)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(AppError::Database)?;
+        }
+
+        for tag_name in &metadata.tags {
+            let tag_id = self.tag_service.find_or_create_by_name(tag_name, &mut tx).await?;
+
+            sqlx::query("INSERT INTO video_tags (video_id, tag_id, created_at) VALUES ($1, $2, $3::timestamp) ON CONFLICT DO NOTHING")
+                .bind(id)
+                .bind(&tag_id)
+                .bind(now)
+                .execute(&mut *tx)
+                .await
+                .map_err(AppError::Database)?;
+        }
+
+        tx.commit().await.map_err(AppError::Database)?;
+        Ok(())
+    }
+
+    /// Store where a video was recorded and the place found for it by reverse geocoding.
+    /// With `fill_location` the place also becomes the location, unless one is already set.
+    pub async fn set_position(&self, id: &str, position: &GpsPosition, suggested_location: Option<&str>, fill_location: bool) -> Result<()> {
+        sqlx::query(
+            "UPDATE videos
+             SET latitude = $1,
+                 longitude = $2,
+                 altitude = COALESCE($3, altitude),
+                 suggested_location = COALESCE($4, suggested_location),
+                 location = COALESCE(NULLIF(location, ''), CASE WHEN $5 THEN $4 END)
+             WHERE id = $6"
+        )
+        .bind(position.latitude)
+        .bind(position.longitude)
+        .bind(position.altitude)
+        .bind(suggested_location)
+        .bind(fill_location)
+        .bind(id)
+        .execute(&self.db)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    // Store the outcome of automatic original file matching; manual overrides are left alone
+    pub async fn set_original_match(
+        &self,
+        id: &str,
+        original_file_path: Option<&str>,
+        status: Option<&str>,
+        candidates: Option<serde_json::Value>,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE videos SET original_file_path = $1, original_match_status = $2, original_candidates = $3
+             WHERE id = $4 AND original_match_status IS DISTINCT FROM 'manual'"
+        )
+        .bind(original_file_path)
+        .bind(status)
+        .bind(candidates)
+        .bind(id)
+        .execute(&self.db)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(())
+    }
+
+    // Override the original file of a video; rescans keep the override
+    pub async fn set_original_override(&self, id: &str, original_file_path: Option<&str>) -> Result<Video> {
+        let now = chrono::Utc::now().naive_utc();
+        let mut video = sqlx::query_as::<_, Video>(
+            "UPDATE videos
+             SET original_file_path = $1, original_match_status = 'manual', original_candidates = NULL, updated_at = $2
+             WHERE id = $3
+             RETURNING *"
+        )
+        .bind(original_file_path)
+        .bind(now)
+        .bind(id)
+        .fetch_optional(&self.db)
+        .await
+        .map_err(AppError::Database)?
+        .ok_or_else(|| AppError::NotFound(format!("Video not found: {id}")))?;
+
+        video.thumbnail_path = self.transform_thumbnail_path(video.thumbnail_path.clone());
+        Ok(video)
+    }
+
+    // Drop a manual override so the next scan matches the original automatically again
+    pub async fn clear_original_override(&self, id: &str) -> Result<Video> {
+        let now = chrono::Utc::now().naive_utc();
+        let mut video = sqlx::query_as::<_, Video>(
+            "UPDATE videos
+             SET original_file_path = NULL, original_match_status = NULL, original_candidates = NULL, updated_at = $1
+             WHERE id = $2
+             RETURNING *"
+        )
+        .bind(now)
+        .bind(id)
+        .fetch_optional(&self.db)
+        .await
+        .map_err(AppError::Database)?
+        .ok_or_else(|| AppError::NotFound(format!("Video not found: {id}")))?;
+
+        video.thumbnail_path = self.transform_thumbnail_path(video.thumbnail_path.clone());
+        Ok(video)
+    }
+
+    /// Replace a video's thumbnail with the frame at a time, in seconds. Later scans keep it.
+    pub async fn set_thumbnail_time(&self, id: &str, time: f64) -> Result<Video> {
+        let video = self.find_by_id(id).await?;
+
+        if video.media_type != MEDIA_TYPE_VIDEO {
+            return Err(AppError::BadRequest("Only videos can have their thumbnail taken from a time".to_string()));
+        }
+        if video.duration.is_some_and(|duration| time * 1000.0 >= duration as f64) {
+            return Err(AppError::BadRequest(format!("Time {time}s is past the end of the video")));
+        }
+        if !std::path::Path::new(&video.file_path).exists() {
+            return Err(AppError::NotFound(format!("Video file not found: {}", video.file_path)));
+        }
+
+        let thumbnail_path = self.thumbnail_service.generate_thumbnail_at(id, &video.file_path, time).await?;
+        let thumbnail_hash = compute_content_hash(std::path::Path::new(&thumbnail_path)).await?;
+
+        let now = chrono::Utc::now().naive_utc();
+        let mut updated_video = sqlx::query_as::<_, Video>(
+            "UPDATE videos
+             SET thumbnail_path = $1, thumbnail_hash = $2, thumbnail_time = $3, updated_at = $4
+             WHERE id = $5
+             RETURNING *"
+        )
+        .bind(&thumbnail_path)
+        .bind(&thumbnail_hash)
+        .bind(time)
+        .bind(now)
+        .bind(id)
+        .fetch_optional(&self.db)
+        .await
+        .map_err(AppError::Database)?
+        .ok_or_else(|| AppError::NotFound(format!("Video not found: {id}")))?;
+
+        self.delete_replaced_thumbnail(video.thumbnail_path.as_deref(), &thumbnail_path).await;
+
+        updated_video.thumbnail_path = self.transform_thumbnail_path(updated_video.thumbnail_path.clone());
+        Ok(updated_video)
+    }
+
+    /// Point a video at a newly generated thumbnail and remove the one it replaces
+    pub async fn update_thumbnail_path(&self, id: &str, previous_path: Option<&str>, thumbnail_path: &str) -> Result<()> {
+        let thumbnail_hash = compute_content_hash(std::path::Path::new(thumbnail_path)).await?;
+
+        sqlx::query("UPDATE videos SET thumbnail_path = $1, thumbnail_hash = $2 WHERE id = $3")
+            .bind(thumbnail_path)
+            .bind(&thumbnail_hash)
+            .bind(id)
+            .execute(&self.db)
+            .await
+            .map_err(AppError::Database)?;
+
+        self.delete_replaced_thumbnail(previous_path, thumbnail_path).await;
+        Ok(())
+    }
+
+    // Thumbnails from before they were named after their video have a name of their own
+    async fn delete_replaced_thumbnail(&self, previous_path: Option<&str>, thumbnail_path: &str) {
+        let Some(previous_path) = previous_path else {
+            return;
+        };
+
+        let file_name = |path: &str| std::path::Path::new(path).file_name().map(|name| name.to_os_string());
+        if file_name(previous_path) == file_name(thumbnail_path) {
+            return;
+        }
+
+        if let Err(e) = self.thumbnail_service.delete_thumbnail(previous_path).await {
+            error!("Failed to delete thumbnail: {}", e);
+        }
+    }
+
+    /// Let a video stand in for an original FFmpeg can't decode: the original takes the proxy's duration
+    /// and a copy of its thumbnail, and is played from the proxy's file
+    pub async fn link_proxy(&self, id: &str, previous_thumbnail_path: Option<&str>, proxy: &Video) -> Result<()> {
+        sqlx::query("UPDATE videos SET proxy_video_id = $1, duration = COALESCE($2, duration) WHERE id = $3")
+            .bind(&proxy.id)
+            .bind(proxy.duration)
+            .bind(id)
+            .execute(&self.db)
+            .await
+            .map_err(AppError::Database)?;
+
+        // The proxy may still be waiting for its thumbnail, in which case it shares it once it has one
+        if let Some(proxy_thumbnail_path) = &proxy.thumbnail_path {
+            let thumbnail_path = self.thumbnail_service.copy_thumbnail(id, proxy_thumbnail_path).await?;
+            self.update_thumbnail_path(id, previous_thumbnail_path, &thumbnail_path).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Video whose file is played for a video: its proxy for originals FFmpeg can't decode, otherwise itself
+    pub async fn playback_video(&self, video: &Video) -> Result<Video> {
+        match &video.proxy_video_id {
+            Some(proxy_video_id) => self.find_by_id(proxy_video_id).await,
+            None => Ok(video.clone()),
+        }
+    }
+
+    /// Hash of a video's thumbnail, computed and stored if the video doesn't have one yet
+    pub async fn thumbnail_hash(&self, video: &Video) -> Result<String> {
+        if let Some(thumbnail_hash) = &video.thumbnail_hash {
+            return Ok(thumbnail_hash.clone());
+        }
+
+        let thumbnail_path = video
+            .thumbnail_path
+            .as_deref()
+            .ok_or_else(|| AppError::NotFound(format!("Video has no thumbnail: {}", video.id)))?;
+        let thumbnail_file = self.thumbnail_service.resolve_path(thumbnail_path);
+        if !thumbnail_file.exists() {
+            return Err(AppError::NotFound(format!("Thumbnail file not found: {}", thumbnail_file.display())));
+        }
+
+        let thumbnail_hash = compute_content_hash(&thumbnail_file).await?;
+        sqlx::query("UPDATE videos SET thumbnail_hash = $1 WHERE id = $2")
+            .bind(&thumbnail_hash)
+            .bind(&video.id)
+            .execute(&self.db)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(thumbnail_hash)
+    }
+
+    /// Id, stored thumbnail path and thumbnail hash of every video, including offline ones
+    pub async fn find_thumbnail_paths(&self) -> Result<Vec<(String, Option<String>, Option<String>)>> {
+        let rows = sqlx::query_as::<_, (String, Option<String>, Option<String>)>("SELECT id, thumbnail_path, thumbnail_hash FROM videos")
+            .fetch_all(&self.db)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(rows)
+    }
+
+    /// Videos whose files are on disk, with their thumbnail paths as stored
+    pub async fn find_online(&self) -> Result<Vec<Video>> {
+        let videos = sqlx::query_as::<_, Video>(
+            "SELECT * FROM videos WHERE offline_since IS NULL ORDER BY file_path"
+        )
+        .fetch_all(&self.db)
+        .await
+        .map_err(AppError::Database)?;
+
+        Ok(videos)
+    }
+
+    // Let the next scan pick the thumbnail again
+    pub async fn clear_thumbnail_time(&self, id: &str) -> Result<Video> {
+        let now = chrono::Utc::now().naive_utc();
+        let mut video = sqlx::query_as::<_, Video>(
+            "UPDATE videos
+             SET thumbnail_time = NULL, updated_at = $1
+             WHERE id = $2
+             RETURNING *"
+        )
+        .bind(now)
+        .bind(id)
+        .fetch_optional(&self.db)
+        .await
+        .map_err(AppError::Database)?
+        .ok_or_else(|| AppError::NotFound(format!("Video not found: {id}")))?;
+
+        video.thumbnail_path = self.transform_thumbnail_path(video.thumbnail_path.clone());
+        Ok(video)
+    }
+
+    pub async fn find_ambiguous_originals(&self) -> Result<Vec<Video>> {
+        let mut videos = sqlx::query_as::<_, Video>(
+            "SELECT * FROM videos WHERE original_match_status = 'ambiguous' ORDER BY file_path"
+        )
+        .fetch_all(&self.db)
+        .await
+        .map_err(AppError::Database)?;
+
+        // Transform thumbnail paths
+        for video in &mut videos {
+            video.thumbnail_path = self.transform_thumbnail_path(video.thumbnail_path.clone());
+        }
+
+        Ok(videos)
+    }
+
+    // Point an offline video at the new location of its file, keeping all of its metadata
+    pub async fn relink(&self, id: &str, file_path: &str, file_name: &str) -> Result<Option<Video>> {
+        let now = chrono::Utc::now().naive_utc();
+        let result = sqlx::query(
+            "UPDATE videos SET file_path = $1, file_name = $2, offline_since = NULL, updated_at = $3
+             WHERE id = $4 AND offline_since IS NOT NULL"
+        )
+        .bind(file_path)
+        .bind(file_name)
+        .bind(now)
+        .bind(id)
+        .execute(&self.db)
+        .await
+        .map_err(AppError::Database)?;
+
+        // Another task may have relinked this video first
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        info!("Relinked video {} to {}", id, file_path);
+        self.find_by_id(id).await.map(Some)
+    }
+
+    pub async fn purge_offline(&self, video_ids: Option<Vec<String>>) -> Result<usize> {
+        let offline_videos = self.find_offline().await?;
+        let mut count = 0;
+
+        for video in offline_videos {
+            if let Some(ids) = &video_ids {
+                if !ids.contains(&video.id) {
+                    continue;
+                }
+            }
+
+            match self.delete(&video.id).await {
+                Ok(_) => count += 1,
+                Err(e) => error!("Failed to purge offline video {}: {}", video.id, e),
+            }
+        }
+
+        info!("Purged {} offline videos", count);
+        Ok(count)
+    }
+
     pub async fn add_to_default_shoebox(&self, video_id: &str, default_shoebox: &str) -> Result<()> {
         info!("Adding video {} to default shoebox: {}", video_id, default_shoebox);
 
@@ -457,6 +1037,16 @@ impl VideoService {
             }
         }
 
+        if let Err(e) = self.thumbnail_service.delete_sprite_sheet(id).await {
+            error!("Failed to delete sprite sheet: {}", e);
+        }
+        if let Err(e) = self.thumbnail_service.delete_preview(id).await {
+            error!("Failed to delete preview: {}", e);
+        }
+        if let Err(e) = self.thumbnail_service.delete_variants(id).await {
+            error!("Failed to delete scaled thumbnails: {}", e);
+        }
+
         info!("Deleted video: {}", id);
         Ok(())
     }
@@ -586,18 +1176,85 @@ impl VideoService {
             conditions.push("(v.rating IS NULL AND v.description IS NULL AND v.location IS NULL AND v.event IS NULL AND NOT EXISTS (SELECT 1 FROM video_tags WHERE video_id = v.id) AND NOT EXISTS (SELECT 1 FROM video_people WHERE video_id = v.id))".to_string());
         }
 
+        if let Some(offline) = params.offline {
+            if offline {
+                conditions.push("v.offline_since IS NOT NULL".to_string());
+            } else {
+                conditions.push("v.offline_since IS NULL".to_string());
+            }
+        }
+
+        if let Some(media_type) = &params.media_type {
+            param_count += 1;
+            conditions.push(format!("v.media_type = ${}", param_count));
+            query_params.push(media_type.clone());
+        }
+
+        // Date ranges are by the local day the file was recorded
         if let Some(start_date) = &params.start_date {
             param_count += 1;
-            conditions.push(format!("date(v.created_date) >= date(${}) ", param_count));
+            conditions.push(format!("v.created_date_local >= ${}::date", param_count));
             query_params.push(start_date.clone());
         }
 
         if let Some(end_date) = &params.end_date {
             param_count += 1;
-            conditions.push(format!("date(v.created_date) <= date(${}) ", param_count));
+            conditions.push(format!("v.created_date_local < ${}::date + 1", param_count));
             query_params.push(end_date.clone());
         }
 
+        if let Some(video_codec) = &params.video_codec {
+            param_count += 1;
+            conditions.push(format!("LOWER(v.video_codec) = LOWER(${})", param_count));
+            query_params.push(video_codec.clone());
+        }
+
+        if let Some(audio_codec) = &params.audio_codec {
+            param_count += 1;
+            conditions.push(format!("LOWER(v.audio_codec) = LOWER(${})", param_count));
+            query_params.push(audio_codec.clone());
+        }
+
+        // Resolutions compare the long and short sides, so portrait clips match too
+        if let Some(min_width) = params.min_width {
+            param_count += 1;
+            conditions.push(format!("GREATEST(v.width, v.height) >= ${}::integer", param_count));
+            query_params.push(min_width.to_string());
+        }
+
+        if let Some(min_height) = params.min_height {
+            param_count += 1;
+            conditions.push(format!("LEAST(v.width, v.height) >= ${}::integer", param_count));
+            query_params.push(min_height.to_string());
+        }
+
+        // Allow for rates like 59.94 when asking for 60
+        if let Some(min_frame_rate) = params.min_frame_rate {
+            param_count += 1;
+            conditions.push(format!("v.frame_rate >= ${}::double precision * 0.99", param_count));
+            query_params.push(min_frame_rate.to_string());
+        }
+
+        if let Some(max_frame_rate) = params.max_frame_rate {
+            param_count += 1;
+            conditions.push(format!("v.frame_rate <= ${}::double precision * 1.01", param_count));
+            query_params.push(max_frame_rate.to_string());
+        }
+
+        if let Some(min_bit_depth) = params.min_bit_depth {
+            param_count += 1;
+            conditions.push(format!("v.bit_depth >= ${}::integer", param_count));
+            query_params.push(min_bit_depth.to_string());
+        }
+
+        if let Some(hdr) = params.hdr {
+            if hdr {
+                conditions.push("v.hdr".to_string());
+            } else {
+                conditions.push("NOT COALESCE(v.hdr, false)".to_string());
+            }
+        }
+
         if let Some(min_duration) = params.min_duration {
             // Convert from seconds to milliseconds
             let min_duration_ms = min_duration * 1000;
@@ -633,11 +1290,14 @@ impl VideoService {
                 "title" => query.push_str(&format!(" ORDER BY v.title {order}")),
                 "rating" => query.push_str(&format!(" ORDER BY v.rating {order}")),
                 "file_size" => query.push_str(&format!(" ORDER BY v.file_size {order}")),
-                "created_date" => query.push_str(&format!(" ORDER BY to_timestamp(v.created_date, 'YYYY-MM-DD\"T\"HH24:MI:SS.MS\"Z\"') {order}")),
+                "resolution" => query.push_str(&format!(" ORDER BY v.width * v.height {order}")),
+                "frame_rate" => query.push_str(&format!(" ORDER BY v.frame_rate {order}")),
+                "bit_rate" => query.push_str(&format!(" ORDER BY v.bit_rate {order}")),
+                "created_date" => query.push_str(&format!(" ORDER BY v.created_date {order}")),
                 _ => query.push_str(" ORDER BY v.created_date DESC"),
             }
         } else {
-            query.push_str(" ORDER BY to_timestamp(v.created_date, 'YYYY-MM-DD\"T\"HH24:MI:SS.MS\"Z\"') DESC");
+            query.push_str(" ORDER BY v.created_date DESC");
         }
 
         if let Some(limit) = params.limit {
@@ -676,14 +1336,44 @@ impl VideoService {
                 title: row.get("title"),
                 description: row.get("description"),
                 created_date: row.get("created_date"),
+                created_date_offset: row.get("created_date_offset"),
+                created_date_local: row.get("created_date_local"),
                 file_size: row.get("file_size"),
                 thumbnail_path: row.get("thumbnail_path"),
+                thumbnail_time: row.get("thumbnail_time"),
+                thumbnail_hash: row.get("thumbnail_hash"),
                 rating: row.get("rating"),
                 duration: row.get("duration"),
                 original_file_path: row.get("original_file_path"),
+                proxy_video_id: row.get("proxy_video_id"),
                 exif_data: row.get("exif_data"),
                 location: row.get("location"),
                 event: row.get("event"),
+                file_mtime: row.get("file_mtime"),
+                file_inode: row.get("file_inode"),
+                partial_hash: row.get("partial_hash"),
+                content_hash: row.get("content_hash"),
+                offline_since: row.get("offline_since"),
+                original_match_status: row.get("original_match_status"),
+                original_candidates: row.get("original_candidates"),
+                latitude: row.get("latitude"),
+                longitude: row.get("longitude"),
+                altitude: row.get("altitude"),
+                suggested_location: row.get("suggested_location"),
+                width: row.get("width"),
+                height: row.get("height"),
+                video_codec: row.get("video_codec"),
+                video_profile: row.get("video_profile"),
+                frame_rate: row.get("frame_rate"),
+                bit_rate: row.get("bit_rate"),
+                bit_depth: row.get("bit_depth"),
+                color_primaries: row.get("color_primaries"),
+                hdr: row.get("hdr"),
+                rotation: row.get("rotation"),
+                audio_codec: row.get("audio_codec"),
+                audio_channels: row.get("audio_channels"),
+                audio_sample_rate: row.get("audio_sample_rate"),
+                media_type: row.get("media_type"),
                 created_at: row.get("created_at"),
                 updated_at: row.get("updated_at"),
             };
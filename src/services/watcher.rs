@@ -0,0 +1,517 @@
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use sqlx::{Pool, Postgres};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tracing::{info, warn, error};
+use walkdir::WalkDir;
+
+use crate::config::MediaPathConfig;
+use crate::error::{AppError, Result};
+use crate::models::SCAN_JOB_COMPLETED;
+use crate::services::{
+    AppState, ScanContext, ScanJobService, ScanProgress, ScannerService, VideoService, ThumbnailService,
+    TagService, PersonService, ShoeboxService, ProbeService,
+};
+use crate::services::geocoder::GeocoderService;
+
+/// How often pending files are checked for a stable size
+const SETTLE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Which directory of a media source a watched file was found in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum WatchedKind {
+    Media,
+    Original,
+}
+
+/// A file that changed recently and is waiting for its size to stop changing
+struct PendingFile {
+    source_index: usize,
+    kind: WatchedKind,
+    size: Option<u64>,
+    last_change: Instant,
+}
+
+/// A change the watcher acts on right away rather than once a file settles
+#[derive(Debug, PartialEq, Eq)]
+enum WatchChange {
+    /// A media file was removed or moved away
+    FileRemoved(PathBuf),
+    /// A directory in a media path was removed or moved away, or a file that can't be told apart from one
+    DirectoryRemoved(PathBuf),
+    /// A directory was created or moved into a source, possibly with files already in it
+    DirectoryAdded(PathBuf),
+}
+
+/// A file whose size has settled, with the index of its source
+type SettledFile = (PathBuf, usize, WatchedKind);
+
+pub struct WatcherService;
+
+impl WatcherService {
+    /// Start watching the path and original path of every source with watching enabled
+    pub fn start(state: AppState) -> Result<()> {
+        let sources: Vec<MediaPathConfig> = state.config.media.source_paths
+            .iter()
+            .filter(|source| source.watch)
+            .cloned()
+            .collect();
+
+        if sources.is_empty() {
+            info!("No media sources have watching enabled");
+            return Ok(());
+        }
+
+        // Forward filesystem events from the watcher thread into the async runtime
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |result: notify::Result<Event>| {
+            match result {
+                Ok(event) => {
+                    let _ = sender.send(event);
+                },
+                Err(e) => {
+                    error!("Filesystem watch error: {}", e);
+                }
+            }
+        })
+        .map_err(|e| AppError::InternalServerError(format!("Failed to create filesystem watcher: {e}")))?;
+
+        for source in &sources {
+            for dir in std::iter::once(&source.path).chain(source.original_path.iter()) {
+                let dir_path = Path::new(dir);
+                if !dir_path.exists() {
+                    warn!("Cannot watch missing path: {}", dir);
+                    continue;
+                }
+
+                match watcher.watch(dir_path, RecursiveMode::Recursive) {
+                    Ok(_) => info!("Watching {} for new media", dir),
+                    Err(e) => error!("Failed to watch {}: {}", dir, e),
+                }
+            }
+        }
+
+        tokio::spawn(Self::run(state, sources, watcher, receiver));
+        Ok(())
+    }
+
+    async fn run(
+        state: AppState,
+        sources: Vec<MediaPathConfig>,
+        // Kept alive for as long as events are processed
+        _watcher: RecommendedWatcher,
+        mut events: UnboundedReceiver<Event>,
+    ) {
+        let db = state.db.clone();
+        let config = state.config.clone();
+
+        let context = ScanContext {
+            video_service: Arc::new(VideoService::new(
+                db.clone(),
+                TagService::new(db.clone()),
+                PersonService::new(db.clone()),
+                ThumbnailService::new(&config),
+                ShoeboxService::new(db.clone()),
+            )),
+            thumbnail_service: Arc::new(ThumbnailService::new(&config)),
+            probe_service: Arc::new(ProbeService::default()),
+            geocoder: GeocoderService::load_for_sources(&config, &sources).await,
+            original_files: ScannerService::prescan_original_files(&sources).await,
+            progress: ScanProgress::default(),
+            force_full_rescan: false,
+            partial_hash: config.media.partial_hash,
+            content_hash: config.media.content_hash,
+        };
+
+        let semaphore = state.task_permits.clone();
+        let settle_time = Duration::from_secs(config.media.watch_settle_seconds);
+        let mut pending: HashMap<PathBuf, PendingFile> = HashMap::new();
+        let mut interval = tokio::time::interval(SETTLE_CHECK_INTERVAL);
+
+        // Settled files whose ingest found a scan running go back to waiting
+        let (deferred_sender, mut deferred) = mpsc::unbounded_channel::<SettledFile>();
+
+        loop {
+            tokio::select! {
+                event = events.recv() => {
+                    match event {
+                        Some(event) => {
+                            for change in Self::handle_event(&sources, &mut pending, event) {
+                                match change {
+                                    WatchChange::FileRemoved(path) => Self::mark_removed_file(&context, &path).await,
+                                    WatchChange::DirectoryRemoved(path) => Self::mark_removed_directory(&context, &path).await,
+                                    WatchChange::DirectoryAdded(path) => {
+                                        let files = Self::list_files(path).await;
+                                        for path in files {
+                                            Self::add_pending(&sources, &mut pending, path);
+                                        }
+                                    },
+                                }
+                            }
+                        },
+                        None => break,
+                    }
+                }
+                Some((path, source_index, kind)) = deferred.recv() => {
+                    pending.entry(path).or_insert(PendingFile {
+                        source_index,
+                        kind,
+                        size: None,
+                        last_change: Instant::now(),
+                    });
+                }
+                _ = interval.tick() => {
+                    // Files that settle during a scan wait for it to finish, so the scan and the
+                    // watcher never process the same file at once
+                    if state.scan_status.read().await.in_progress {
+                        continue;
+                    }
+
+                    let settled = Self::take_settled_files(&mut pending, settle_time).await;
+                    if settled.is_empty() {
+                        continue;
+                    }
+
+                    // Each batch of files that settled together is recorded as a scan job of its own
+                    let mut context = context.clone();
+                    context.progress = ScanProgress::default();
+                    let job_id = Self::create_job(&db, &sources, &settled).await;
+                    let mut tasks = Vec::new();
+
+                    for (path, source_index, kind) in settled {
+                        let source = sources[source_index].clone();
+                        let context = context.clone();
+                        let semaphore = semaphore.clone();
+                        let scan_status = state.scan_status.clone();
+                        let deferred_sender = deferred_sender.clone();
+
+                        tasks.push(tokio::spawn(async move {
+                            let _permit = semaphore.acquire().await.unwrap();
+
+                            // A scan may have started while this ingest waited for its turn
+                            if scan_status.read().await.in_progress {
+                                let _ = deferred_sender.send((path, source_index, kind));
+                                return;
+                            }
+
+                            match kind {
+                                WatchedKind::Media => Self::ingest_media_file(&context, path, &source).await,
+                                WatchedKind::Original => Self::ingest_original_file(&context, path, &source).await,
+                            }
+                        }));
+                    }
+
+                    if let Some(job_id) = job_id {
+                        tokio::spawn(Self::finish_job(ScanJobService::new(db.clone()), job_id, context.progress, tasks));
+                    }
+                }
+            }
+        }
+
+        warn!("Filesystem watcher stopped");
+    }
+
+    // Record created or modified files as pending until their size settles, and return the
+    // changes to act on right away
+    fn handle_event(sources: &[MediaPathConfig], pending: &mut HashMap<PathBuf, PendingFile>, event: Event) -> Vec<WatchChange> {
+        let mut changes = Vec::new();
+
+        // Renaming a file away from its path looks like a removal to the watcher
+        let is_removal = matches!(
+            event.kind,
+            EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From))
+        );
+
+        for path in event.paths {
+            if is_removal {
+                pending.remove(&path);
+                match Self::classify(sources, &path) {
+                    Some((_, WatchedKind::Media)) => changes.push(WatchChange::FileRemoved(path)),
+                    Some((_, WatchedKind::Original)) => {},
+                    // The path is gone, so whether it was a directory can only be guessed from its name
+                    None if Self::in_media_path(sources, &path) => changes.push(WatchChange::DirectoryRemoved(path)),
+                    None => {},
+                }
+                continue;
+            }
+
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                continue;
+            }
+
+            // Files in a directory moved into a source don't get events of their own
+            if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_))) && path.is_dir() {
+                if Self::in_watched_path(sources, &path) {
+                    changes.push(WatchChange::DirectoryAdded(path));
+                }
+                continue;
+            }
+
+            Self::add_pending(sources, pending, path);
+        }
+
+        changes
+    }
+
+    // Wait for a file to settle if it is media or an original file of a watched source
+    fn add_pending(sources: &[MediaPathConfig], pending: &mut HashMap<PathBuf, PendingFile>, path: PathBuf) {
+        let Some((source_index, kind)) = Self::classify(sources, &path) else {
+            return;
+        };
+
+        pending
+            .entry(path)
+            .and_modify(|file| file.last_change = Instant::now())
+            .or_insert(PendingFile {
+                source_index,
+                kind,
+                size: None,
+                last_change: Instant::now(),
+            });
+    }
+
+    // Every file under a directory, walked off the async runtime
+    async fn list_files(directory: PathBuf) -> Vec<PathBuf> {
+        let walk = tokio::task::spawn_blocking(move || {
+            WalkDir::new(&directory)
+                .follow_links(true)
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file())
+                .map(|entry| entry.into_path())
+                .collect()
+        });
+
+        match walk.await {
+            Ok(files) => files,
+            Err(e) => {
+                error!("Failed to list the files of a new directory: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    // Whether a path is inside the media path of a source and not excluded from it
+    fn in_media_path(sources: &[MediaPathConfig], path: &Path) -> bool {
+        sources
+            .iter()
+            .any(|source| path.starts_with(&source.path) && !source.is_excluded(path))
+    }
+
+    // Whether a path is inside the media path or the original path of a source and not excluded
+    fn in_watched_path(sources: &[MediaPathConfig], path: &Path) -> bool {
+        sources.iter().any(|source| {
+            std::iter::once(&source.path)
+                .chain(source.original_path.iter())
+                .any(|root| path.starts_with(root))
+                && !source.is_excluded(path)
+        })
+    }
+
+    async fn mark_removed_file(context: &ScanContext, path: &Path) {
+        let file_path = path.to_string_lossy().to_string();
+        match context.video_service.mark_offline_by_path(&file_path).await {
+            Ok(true) => info!("Watched file removed, marked video as offline: {}", file_path),
+            Ok(false) => {},
+            Err(e) => error!("Error marking {} as offline: {}", file_path, e),
+        }
+    }
+
+    async fn mark_removed_directory(context: &ScanContext, path: &Path) {
+        let directory = path.to_string_lossy().to_string();
+        match context.video_service.mark_offline_under(&directory).await {
+            Ok(0) => {},
+            Ok(count) => info!("Watched directory removed, marked {} videos as offline: {}", count, directory),
+            Err(e) => error!("Error marking videos under {} as offline: {}", directory, e),
+        }
+    }
+
+    // Find the source a path belongs to and whether it is a media or an original file
+    fn classify(sources: &[MediaPathConfig], path: &Path) -> Option<(usize, WatchedKind)> {
+        for (index, source) in sources.iter().enumerate() {
+            if path.starts_with(&source.path) && source.classify(path).is_some() {
+                return Some((index, WatchedKind::Media));
+            }
+
+            if let Some(original_path) = &source.original_path {
+                if path.starts_with(original_path)
+                    && !source.is_excluded(path)
+                    && ScannerService::matches_original_extension(path, source.original_extension.as_deref())
+                {
+                    return Some((index, WatchedKind::Original));
+                }
+            }
+        }
+
+        None
+    }
+
+    // Remove and return the pending files whose size hasn't changed for the settle time
+    async fn take_settled_files(
+        pending: &mut HashMap<PathBuf, PendingFile>,
+        settle_time: Duration,
+    ) -> Vec<SettledFile> {
+        let mut settled = Vec::new();
+        let mut gone = Vec::new();
+
+        for (path, file) in pending.iter_mut() {
+            let metadata = match tokio::fs::metadata(path).await {
+                Ok(metadata) if metadata.is_file() => metadata,
+                _ => {
+                    // The file was removed or renamed before it settled
+                    gone.push(path.clone());
+                    continue;
+                }
+            };
+
+            if file.size != Some(metadata.len()) {
+                file.size = Some(metadata.len());
+                file.last_change = Instant::now();
+            } else if file.last_change.elapsed() >= settle_time {
+                settled.push((path.clone(), file.source_index, file.kind));
+            }
+        }
+
+        for path in gone {
+            pending.remove(&path);
+        }
+        for (path, _, _) in &settled {
+            pending.remove(path);
+        }
+
+        settled
+    }
+
+    // Record the start of a scan job over the sources of a batch of settled files
+    async fn create_job(db: &Pool<Postgres>, sources: &[MediaPathConfig], settled: &[SettledFile]) -> Option<String> {
+        let mut names: Vec<String> = Vec::new();
+        for (_, source_index, _) in settled {
+            let source = &sources[*source_index];
+            let name = source.name.clone().unwrap_or_else(|| source.path.clone());
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+
+        match ScanJobService::new(db.clone()).create(&names, false).await {
+            Ok(job) => Some(job.id),
+            Err(e) => {
+                error!("Error recording scan job for watched files: {}", e);
+                None
+            }
+        }
+    }
+
+    // Store the counts and errors of a batch of watched files once all of them were processed
+    async fn finish_job(
+        scan_job_service: ScanJobService,
+        job_id: String,
+        progress: ScanProgress,
+        tasks: Vec<tokio::task::JoinHandle<()>>,
+    ) {
+        for task in tasks {
+            if let Err(e) = task.await {
+                error!("Watched file task failed: {}", e);
+            }
+        }
+
+        let counts = ScannerService::record_job_progress(&scan_job_service, &job_id, &progress).await;
+        if let Err(e) = scan_job_service.finish(&job_id, SCAN_JOB_COMPLETED, &counts).await {
+            error!("Error finishing scan job {}: {}", job_id, e);
+        }
+    }
+
+    async fn ingest_media_file(context: &ScanContext, path: PathBuf, source: &MediaPathConfig) {
+        info!("Ingesting watched file: {}", path.display());
+        ScannerService::process_file(context, path.to_string_lossy().to_string(), source).await;
+    }
+
+    async fn ingest_original_file(context: &ScanContext, path: PathBuf, source: &MediaPathConfig) {
+        let Some(stem) = path.file_stem().map(|stem| stem.to_string_lossy().to_string()) else {
+            return;
+        };
+
+        info!("Registering watched original file: {}", path.display());
+        if let Some(original_path) = &source.original_path {
+            context.original_files.lock().await.insert(Path::new(original_path), &path);
+        }
+
+        // Link the new original to videos from this source that were ingested before it arrived
+        let videos = match context.video_service.find_missing_original_by_stem(&stem).await {
+            Ok(videos) => videos,
+            Err(e) => {
+                error!("Error looking up videos for original {}: {}", path.display(), e);
+                return;
+            }
+        };
+
+        let mut context = context.clone();
+        context.force_full_rescan = true;
+
+        for video in videos.into_iter().filter(|video| Path::new(&video.file_path).starts_with(&source.path)) {
+            info!("Linking original {} to video {}", path.display(), video.file_path);
+            ScannerService::process_file(&context, video.file_path, source).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, RemoveKind};
+
+    #[tokio::test]
+    async fn handles_directories_moved_in_and_removed() {
+        let root = std::env::temp_dir().join(format!("shoebox-watch-{}", uuid::Uuid::new_v4()));
+        let trip = root.join("trip");
+        std::fs::create_dir_all(trip.join("day 2")).unwrap();
+        for file in ["clip.mp4", "notes.txt", "day 2/clip.mov"] {
+            std::fs::write(trip.join(file), b"").unwrap();
+        }
+
+        let sources = [MediaPathConfig {
+            path: root.to_string_lossy().to_string(),
+            ..MediaPathConfig::default()
+        }];
+        let mut pending = HashMap::new();
+        let event = |kind: EventKind, path: &Path| Event::new(kind).add_path(path.to_path_buf());
+
+        // A directory moved in is listed, and only its media files wait to settle
+        let changes = WatcherService::handle_event(
+            &sources,
+            &mut pending,
+            event(EventKind::Modify(ModifyKind::Name(RenameMode::To)), &trip),
+        );
+        assert_eq!(changes, [WatchChange::DirectoryAdded(trip.clone())]);
+        let mut files = WatcherService::list_files(trip.clone()).await;
+        files.sort();
+        for file in files {
+            WatcherService::add_pending(&sources, &mut pending, file);
+        }
+        let mut waiting: Vec<_> = pending.keys().cloned().collect();
+        waiting.sort();
+        assert_eq!(waiting, [trip.join("clip.mp4"), trip.join("day 2/clip.mov")]);
+
+        let cases = [
+            ("removed media file", EventKind::Remove(RemoveKind::File), trip.join("clip.mp4"), vec![WatchChange::FileRemoved(trip.join("clip.mp4"))]),
+            ("removed directory", EventKind::Remove(RemoveKind::Folder), trip.clone(), vec![WatchChange::DirectoryRemoved(trip.clone())]),
+            ("directory moved away", EventKind::Modify(ModifyKind::Name(RenameMode::From)), trip.clone(), vec![WatchChange::DirectoryRemoved(trip.clone())]),
+            ("removed outside the source", EventKind::Remove(RemoveKind::Folder), PathBuf::from("/elsewhere/trip"), vec![]),
+            ("created directory", EventKind::Create(CreateKind::Folder), trip.join("day 2"), vec![WatchChange::DirectoryAdded(trip.join("day 2"))]),
+            ("created file", EventKind::Create(CreateKind::File), trip.join("notes.txt"), vec![]),
+        ];
+
+        for (name, kind, path, expected) in cases {
+            assert_eq!(WatcherService::handle_event(&sources, &mut pending, event(kind, &path)), expected, "{name}");
+        }
+
+        // Removed files no longer wait to settle
+        assert!(!pending.contains_key(&trip.join("clip.mp4")));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
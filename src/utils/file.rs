@@ -1,8 +1,12 @@
+use sha2::{Digest, Sha256};
+use std::io::{Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
 use tokio::fs;
 use tracing::error;
 
 use crate::error::{AppError, Result};
+use crate::models::{MEDIA_TYPE_PHOTO, MEDIA_TYPE_VIDEO};
 
 /// Check if a file exists
 pub async fn file_exists(path: &Path) -> bool {
@@ -15,12 +19,37 @@ pub fn get_file_extension(path: &Path) -> Option<String> {
         .map(|ext| ext.to_string_lossy().to_lowercase())
 }
 
-/// Check if a file is a video file
-pub fn is_video_file(path: &Path) -> bool {
-    if let Some(ext) = get_file_extension(path) {
-        return ["mp4", "mov", "mkv"].contains(&ext.as_str());
+/// Extensions of video files the scanner ingests by default
+pub const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mov", "mkv", "braw", "avi", "wmv", "flv", "webm"];
+
+/// Extensions of camera originals FFmpeg can't decode, which take what they can from a proxy
+pub const PROXY_ONLY_EXTENSIONS: &[&str] = &["braw"];
+
+/// Extensions of still images the scanner ingests by default, including camera RAW formats
+pub const PHOTO_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "heic", "heif", "tif", "tiff", "webp",
+    "dng", "cr2", "cr3", "nef", "arw", "raf", "orf", "rw2",
+];
+
+/// Media type of a file based on the default extension lists, `None` if it isn't media
+pub fn media_type(path: &Path) -> Option<&'static str> {
+    let ext = get_file_extension(path)?;
+    (VIDEO_EXTENSIONS.contains(&ext.as_str()) || PHOTO_EXTENSIONS.contains(&ext.as_str()))
+        .then(|| media_type_for_extension(&ext))
+}
+
+/// Media type of an accepted extension; anything that isn't a known photo format is treated as video
+pub fn media_type_for_extension(ext: &str) -> &'static str {
+    if PHOTO_EXTENSIONS.contains(&ext) {
+        MEDIA_TYPE_PHOTO
+    } else {
+        MEDIA_TYPE_VIDEO
     }
-    false
+}
+
+/// Check if a file is a video FFmpeg can't decode, so it needs a proxy to be seen or played
+pub fn needs_proxy(path: &Path) -> bool {
+    get_file_extension(path).is_some_and(|ext| PROXY_ONLY_EXTENSIONS.contains(&ext.as_str()))
 }
 
 /// Create directory if it doesn't exist
@@ -77,3 +106,112 @@ pub async fn copy_file(source: &Path, dest: &Path) -> Result<()> {
 
     Ok(())
 }
+
+/// Number of bytes read from each end of a file when computing its partial hash
+const PARTIAL_HASH_CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// Cheap identity of a file on disk, used to detect files that changed between scans
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileFingerprint {
+    pub size: i64,
+    pub mtime: Option<i64>,
+    pub inode: Option<i64>,
+    pub partial_hash: Option<String>,
+    pub content_hash: Option<String>,
+}
+
+impl FileFingerprint {
+    /// Build a fingerprint from file metadata, without hashing any content
+    pub fn from_metadata(metadata: &std::fs::Metadata) -> Self {
+        // Modification time in milliseconds since the unix epoch
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_millis() as i64);
+
+        #[cfg(unix)]
+        let inode = {
+            use std::os::unix::fs::MetadataExt;
+            Some(metadata.ino() as i64)
+        };
+        #[cfg(not(unix))]
+        let inode = None;
+
+        Self {
+            size: metadata.len() as i64,
+            mtime,
+            inode,
+            partial_hash: None,
+            content_hash: None,
+        }
+    }
+
+    /// Check whether the stored size, mtime and inode of a file still match this fingerprint
+    pub fn matches(&self, size: Option<i64>, mtime: Option<i64>, inode: Option<i64>) -> bool {
+        if size != Some(self.size) || mtime.is_none() || mtime != self.mtime {
+            return false;
+        }
+
+        // Only compare inodes when both sides have one
+        match (inode, self.inode) {
+            (Some(stored), Some(current)) => stored == current,
+            _ => true,
+        }
+    }
+}
+
+/// Hash the first and last megabyte of a file together with its size
+pub async fn compute_partial_hash(path: &Path) -> Result<String> {
+    let path = path.to_path_buf();
+
+    tokio::task::spawn_blocking(move || -> std::io::Result<String> {
+        let mut file = std::fs::File::open(&path)?;
+        let size = file.metadata()?.len();
+        let mut hasher = Sha256::new();
+        hasher.update(size.to_le_bytes());
+
+        // Read the head of the file
+        let mut buffer = Vec::with_capacity(PARTIAL_HASH_CHUNK_SIZE as usize);
+        (&mut file).take(PARTIAL_HASH_CHUNK_SIZE).read_to_end(&mut buffer)?;
+        hasher.update(&buffer);
+
+        // Read the tail of the file, without overlapping the head
+        if size > PARTIAL_HASH_CHUNK_SIZE {
+            let tail_start = (size - PARTIAL_HASH_CHUNK_SIZE).max(PARTIAL_HASH_CHUNK_SIZE);
+            buffer.clear();
+            file.seek(SeekFrom::Start(tail_start))?;
+            file.read_to_end(&mut buffer)?;
+            hasher.update(&buffer);
+        }
+
+        Ok(format!("{:x}", hasher.finalize()))
+    })
+    .await
+    .map_err(|e| AppError::InternalServerError(format!("Partial hash task failed: {e}")))?
+    .map_err(AppError::Io)
+}
+
+/// Hash the full content of a file
+pub async fn compute_content_hash(path: &Path) -> Result<String> {
+    let path = path.to_path_buf();
+
+    tokio::task::spawn_blocking(move || -> std::io::Result<String> {
+        let mut file = std::fs::File::open(&path)?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0u8; PARTIAL_HASH_CHUNK_SIZE as usize];
+
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+
+        Ok(format!("{:x}", hasher.finalize()))
+    })
+    .await
+    .map_err(|e| AppError::InternalServerError(format!("Content hash task failed: {e}")))?
+    .map_err(AppError::Io)
+}
@@ -0,0 +1,109 @@
+use std::path::Path;
+
+/// Check if a glob pattern matches a path relative to a source root, ignoring case.
+///
+/// `*` and `?` match within a single path segment and `**` matches any number of segments.
+/// Patterns without a `/`, such as `*.LRF`, match the name of a file or directory at any depth.
+/// A pattern that matches a directory also matches everything inside it.
+pub fn glob_matches(pattern: &str, relative_path: &Path) -> bool {
+    let pattern = pattern.trim().trim_start_matches('/').to_lowercase();
+    let pattern = pattern.trim_end_matches('/');
+    if pattern.is_empty() {
+        return false;
+    }
+
+    let segments: Vec<String> = relative_path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().to_lowercase())
+        .collect();
+
+    if !pattern.contains('/') {
+        return segments.iter().any(|segment| wildcard_matches(pattern, segment));
+    }
+
+    let pattern_segments: Vec<&str> = pattern.split('/').collect();
+
+    // Try the path itself and each of its parent directories
+    (1..=segments.len()).any(|len| segments_match(&pattern_segments, &segments[..len]))
+}
+
+// Match path segments against pattern segments, where "**" matches zero or more segments
+fn segments_match(pattern: &[&str], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => {
+            segments_match(rest, path) || (!path.is_empty() && segments_match(pattern, &path[1..]))
+        },
+        Some((first, rest)) => {
+            !path.is_empty()
+                && wildcard_matches(first, &path[0])
+                && segments_match(rest, &path[1..])
+        },
+    }
+}
+
+// Match a single segment against a pattern with `*` and `?` wildcards
+fn wildcard_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            // Remember the star and first try matching nothing
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, star_text)) = backtrack {
+            // Let the last star swallow one more character
+            p = star + 1;
+            t = star_text + 1;
+            backtrack = Some((star, t));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_wildcards() {
+        let cases = [
+            ("*.lrf", "2024/DJI_0001.LRF", true),
+            ("*.lrf", "2024/DJI_0001.MP4", false),
+            ("DJI_000?.MP4", "DJI_0001.MP4", true),
+            ("DJI_000?.MP4", "DJI_00010.MP4", false),
+            ("DJI_000?.MP4", "DJI_000.MP4", false),
+            ("?", "a/b", true),
+            ("proxy", "2024/Proxy/clip.mov", true),
+            ("2024/*", "2024/clip.mov", true),
+            ("2024/*", "2023/clip.mov", false),
+            ("2024/*.mov", "2024/day/clip.mov", false),
+            ("*/clip.mov", "2024/day/clip.mov", false),
+            ("**/clip.mov", "2024/day/clip.mov", true),
+            ("**/clip.mov", "clip.mov", true),
+            ("2024/**/*.mov", "2024/clip.mov", true),
+            ("2024/**/*.mov", "2024/a/b/clip.mov", true),
+            ("2024/**/*.mov", "2023/a/clip.mov", false),
+            ("**/cache/**", "a/cache/b/clip.mov", true),
+            ("**/cache/**", "a/caches/clip.mov", false),
+            ("/2024/", "2024/clip.mov", true),
+            ("a*b*c", "aXbYbc", true),
+            ("a*b*c", "aXbYcd", false),
+            ("", "clip.mov", false),
+            ("  ", "clip.mov", false),
+        ];
+
+        for (pattern, path, expected) in cases {
+            assert_eq!(glob_matches(pattern, Path::new(path)), expected, "{pattern} against {path}");
+        }
+    }
+}
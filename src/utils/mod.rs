@@ -1,3 +1,7 @@
 mod file;
+mod glob;
+mod path_template;
 
 pub use file::*;
+pub use glob::*;
+pub use path_template::*;
@@ -0,0 +1,200 @@
+use std::path::Path;
+
+/// A template such as `{year}/{event}/{person}/*` matched against paths relative to a source root.
+///
+/// Each `/`-separated part matches one directory or file name. `{name}` captures a non-empty part of
+/// a name, `*` matches any part of a name and a `**` part matches any number of directories.
+/// Literal text is compared ignoring case.
+#[derive(Clone, Debug)]
+pub struct PathTemplate {
+    segments: Vec<TemplateSegment>,
+}
+
+#[derive(Clone, Debug)]
+enum TemplateSegment {
+    AnyDirectories,
+    Name(Vec<Token>),
+}
+
+#[derive(Clone, Debug)]
+enum Token {
+    Literal(String),
+    Capture(String),
+    Wildcard,
+}
+
+impl PathTemplate {
+    /// Parse a template, returning `None` if its braces are unbalanced or a capture is unnamed
+    pub fn parse(template: &str) -> Option<Self> {
+        let template = template.trim().trim_matches('/');
+        if template.is_empty() {
+            return None;
+        }
+
+        let segments = template
+            .split('/')
+            .map(|segment| match segment {
+                "**" => Some(TemplateSegment::AnyDirectories),
+                _ => parse_tokens(segment).map(TemplateSegment::Name),
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self { segments })
+    }
+
+    /// Values captured from a relative path, in template order, or `None` if the path doesn't match
+    pub fn captures(&self, relative_path: &Path) -> Option<Vec<(String, String)>> {
+        let names: Vec<String> = relative_path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+            .collect();
+
+        let mut captures = Vec::new();
+        match_segments(&self.segments, &names, &mut captures).then_some(captures)
+    }
+}
+
+fn parse_tokens(segment: &str) -> Option<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut literal = String::new();
+    let mut chars = segment.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                let rest = chars.as_str();
+                let end = rest.find('}')?;
+                let name = rest[..end].trim().to_lowercase();
+                if name.is_empty() || name.contains('{') {
+                    return None;
+                }
+                chars = rest[end + 1..].chars();
+
+                if !literal.is_empty() {
+                    tokens.push(Token::Literal(std::mem::take(&mut literal)));
+                }
+                tokens.push(Token::Capture(name));
+            },
+            '}' => return None,
+            '*' => {
+                if !literal.is_empty() {
+                    tokens.push(Token::Literal(std::mem::take(&mut literal)));
+                }
+                tokens.push(Token::Wildcard);
+            },
+            _ => literal.extend(c.to_lowercase()),
+        }
+    }
+
+    if !literal.is_empty() {
+        tokens.push(Token::Literal(literal));
+    }
+
+    Some(tokens)
+}
+
+fn match_segments(segments: &[TemplateSegment], names: &[String], captures: &mut Vec<(String, String)>) -> bool {
+    match segments.split_first() {
+        None => names.is_empty(),
+        Some((TemplateSegment::AnyDirectories, rest)) => {
+            (0..=names.len()).any(|skip| match_segments(rest, &names[skip..], captures))
+        },
+        Some((TemplateSegment::Name(tokens), rest)) => {
+            let Some((name, remaining)) = names.split_first() else {
+                return false;
+            };
+
+            let captured = captures.len();
+            let chars: Vec<char> = name.chars().collect();
+            if match_tokens(tokens, &chars, captures) && match_segments(rest, remaining, captures) {
+                return true;
+            }
+            captures.truncate(captured);
+            false
+        },
+    }
+}
+
+// Match the tokens of one template part against a whole name, backtracking over captures and wildcards
+fn match_tokens(tokens: &[Token], name: &[char], captures: &mut Vec<(String, String)>) -> bool {
+    let Some((token, rest)) = tokens.split_first() else {
+        return name.is_empty();
+    };
+
+    match token {
+        Token::Literal(literal) => {
+            let len = literal.chars().count();
+            len <= name.len()
+                && name[..len].iter().flat_map(|c| c.to_lowercase()).eq(literal.chars())
+                && match_tokens(rest, &name[len..], captures)
+        },
+        Token::Wildcard => (0..=name.len()).any(|len| match_tokens(rest, &name[len..], captures)),
+        Token::Capture(capture) => {
+            for len in 1..=name.len() {
+                captures.push((capture.clone(), name[..len].iter().collect()));
+                if match_tokens(rest, &name[len..], captures) {
+                    return true;
+                }
+                captures.pop();
+            }
+            false
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Captures = Option<Vec<(&'static str, &'static str)>>;
+
+    #[test]
+    fn parses_templates() {
+        let cases = [
+            ("{year}/{event}/*", true),
+            ("/{year}/", true),
+            ("**/{event}", true),
+            ("{year}-{month}", true),
+            ("", false),
+            ("/", false),
+            ("{}/*", false),
+            ("{ }/*", false),
+            ("{year/*", false),
+            ("year}/*", false),
+            ("{a{b}}", false),
+        ];
+
+        for (template, valid) in cases {
+            assert_eq!(PathTemplate::parse(template).is_some(), valid, "{template}");
+        }
+    }
+
+    #[test]
+    fn captures_fields_from_paths() {
+        let cases: [(&str, &str, Captures); 12] = [
+            ("{year}/{event}/*", "2024/Wedding/clip.mov", Some(vec![("year", "2024"), ("event", "Wedding")])),
+            ("{Year}/{event}/*", "2024/Wedding/clip.mov", Some(vec![("year", "2024"), ("event", "Wedding")])),
+            // Missing directories
+            ("{year}/{event}/*", "2024/clip.mov", None),
+            ("{year}/{event}/{person}/*", "2024/Wedding/clip.mov", None),
+            // Extra directories
+            ("{year}/{event}/*", "2024/Wedding/Day 1/clip.mov", None),
+            ("{year}/**/{event}/*", "2024/a/b/Wedding/clip.mov", Some(vec![("year", "2024"), ("event", "Wedding")])),
+            ("{year}/**/{event}/*", "2024/Wedding/clip.mov", Some(vec![("year", "2024"), ("event", "Wedding")])),
+            // Captures can't be empty
+            ("{date} {event}/*", "2024-06-01 /clip.mov", None),
+            ("{date} {event}/*", "2024-06-01 Beach Day/clip.mov", Some(vec![("date", "2024-06-01"), ("event", "Beach Day")])),
+            ("{year}-{month}/*", "2024/clip.mov", None),
+            ("TRIP {location}/*", "trip Iceland/clip.mov", Some(vec![("location", "Iceland")])),
+            ("TRIP {location}/*", "Trips Iceland/clip.mov", None),
+        ];
+
+        for (template, path, expected) in cases {
+            let captures = PathTemplate::parse(template).unwrap().captures(Path::new(path));
+            let expected = expected.map(|values| {
+                values.into_iter().map(|(name, value)| (name.to_string(), value.to_string())).collect::<Vec<_>>()
+            });
+            assert_eq!(captures, expected, "{template} against {path}");
+        }
+    }
+}